@@ -0,0 +1,291 @@
+use crate::line_stream::{decode_lines, sse_data, LineDecoder};
+use crate::provider::{
+    ChatMessage, ChatRole, LlmProvider, ModelResponse, TokenStream, ToolCall, ToolDefinition, Usage,
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use openduo_core::config::Config;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use tracing::{debug, error, instrument};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Provider for the Anthropic Messages API, including internal proxies that
+/// expose the same `/v1/messages` contract.
+pub struct AnthropicProvider {
+    client: Client,
+    endpoint: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl AnthropicProvider {
+    pub fn new(config: &Config) -> Result<Self> {
+        let base_url = config
+            .llm_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("OPENDUO_LLM_URL must be set for the anthropic provider"))?;
+        let model = config
+            .llm_model
+            .clone()
+            .ok_or_else(|| anyhow!("OPENDUO_LLM_MODEL must be set for the anthropic provider"))?;
+        let client = config.http.build_client()?;
+        Ok(Self {
+            client,
+            endpoint: format!("{}/messages", base_url.trim_end_matches('/')),
+            api_key: config.llm_api_key.clone(),
+            model,
+        })
+    }
+
+    /// Build the JSON request body for a streamed Messages API call.
+    pub fn request_body(&self, messages: &[ChatMessage], tools: &[ToolDefinition]) -> Value {
+        let system: Vec<&str> = messages
+            .iter()
+            .filter(|m| matches!(m.role, ChatRole::System))
+            .map(|m| m.content.as_str())
+            .collect();
+
+        // The API requires alternating roles, so consecutive messages that
+        // map to the same role (e.g. several tool results) share one turn.
+        let mut turns: Vec<(&str, Vec<Value>)> = Vec::new();
+        for m in messages {
+            let (role, blocks) = match m.role {
+                ChatRole::System => continue,
+                ChatRole::User => ("user", text_block(&m.content)),
+                ChatRole::Assistant => {
+                    let mut blocks = text_block(&m.content);
+                    blocks.extend(m.tool_calls.iter().map(|tc| {
+                        json!({
+                            "type": "tool_use",
+                            "id": tc.id,
+                            "name": tc.name,
+                            "input": tc.arguments,
+                        })
+                    }));
+                    ("assistant", blocks)
+                }
+                ChatRole::Tool => match &m.tool_call_id {
+                    Some(id) => (
+                        "user",
+                        vec![json!({
+                            "type": "tool_result",
+                            "tool_use_id": id,
+                            "content": m.content,
+                        })],
+                    ),
+                    None => ("user", text_block(&m.content)),
+                },
+            };
+            if blocks.is_empty() {
+                continue;
+            }
+            match turns.last_mut() {
+                Some((last_role, last_blocks)) if *last_role == role => last_blocks.extend(blocks),
+                _ => turns.push((role, blocks)),
+            }
+        }
+
+        let messages: Vec<Value> = turns
+            .into_iter()
+            .map(|(role, content)| json!({ "role": role, "content": content }))
+            .collect();
+
+        let mut body = json!({
+            "model": self.model,
+            "max_tokens": DEFAULT_MAX_TOKENS,
+            "messages": messages,
+            "stream": true,
+        });
+        if !system.is_empty() {
+            body["system"] = json!(system.join("\n\n"));
+        }
+        if !tools.is_empty() {
+            body["tools"] = tools
+                .iter()
+                .map(|t| {
+                    json!({
+                        "name": t.name,
+                        "description": t.description,
+                        "input_schema": t.parameters,
+                    })
+                })
+                .collect();
+        }
+        body
+    }
+
+    fn headers(&self) -> Result<HeaderMap> {
+        let mut map = HeaderMap::new();
+        map.insert(
+            reqwest::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        map.insert(
+            HeaderName::from_static("anthropic-version"),
+            HeaderValue::from_static(ANTHROPIC_VERSION),
+        );
+        if let Some(key) = &self.api_key {
+            map.insert(
+                HeaderName::from_static("x-api-key"),
+                HeaderValue::from_str(key)?,
+            );
+        }
+        Ok(map)
+    }
+}
+
+fn text_block(text: &str) -> Vec<Value> {
+    if text.is_empty() {
+        Vec::new()
+    } else {
+        vec![json!({ "type": "text", "text": text })]
+    }
+}
+
+#[async_trait]
+impl LlmProvider for AnthropicProvider {
+    #[instrument(skip(self, messages, tools))]
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<TokenStream> {
+        let body = self.request_body(&messages, &tools);
+        debug!(
+            "Sending to Anthropic Messages endpoint: {} ({} messages, {} tools)",
+            self.endpoint,
+            messages.len(),
+            tools.len()
+        );
+
+        let resp = self
+            .client
+            .post(&self.endpoint)
+            .headers(self.headers()?)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| {
+                error!("Failed to connect to {}: {}", self.endpoint, e);
+                anyhow!("Failed to connect to model endpoint: {}", e)
+            })?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body_text = resp.text().await.unwrap_or_default();
+            error!("Model endpoint returned HTTP {}: {}", status, body_text);
+            return Err(anyhow!(
+                "Model endpoint returned HTTP {} — {}",
+                status,
+                body_text
+            ));
+        }
+
+        Ok(decode_lines(resp, AnthropicStreamDecoder::default()))
+    }
+}
+
+#[derive(Debug, Default)]
+struct PartialToolUse {
+    id: String,
+    name: String,
+    input_json: String,
+}
+
+/// Decodes Messages API SSE events. `tool_use` blocks are assembled from
+/// `input_json_delta` fragments and emitted when their block stops.
+#[derive(Debug, Default)]
+pub struct AnthropicStreamDecoder {
+    tool_blocks: BTreeMap<u64, PartialToolUse>,
+    /// Prompt tokens from `message_start`, reported with the output count.
+    input_tokens: u64,
+}
+
+impl LineDecoder for AnthropicStreamDecoder {
+    fn decode_line(&mut self, line: &str) -> Vec<Result<ModelResponse>> {
+        // `event:` lines duplicate the `type` field carried in each payload.
+        let Some(data) = sse_data(line) else {
+            return Vec::new();
+        };
+        if data.is_empty() {
+            return Vec::new();
+        }
+        let val: Value = match serde_json::from_str(data) {
+            Ok(v) => v,
+            Err(e) => return vec![Err(anyhow!("Invalid stream event: {} ({})", e, data))],
+        };
+        let index = val["index"].as_u64().unwrap_or(0);
+
+        match val["type"].as_str().unwrap_or("") {
+            "content_block_start" => {
+                let block = &val["content_block"];
+                if block["type"] == "tool_use" {
+                    self.tool_blocks.insert(
+                        index,
+                        PartialToolUse {
+                            id: block["id"].as_str().unwrap_or_default().to_string(),
+                            name: block["name"].as_str().unwrap_or_default().to_string(),
+                            input_json: String::new(),
+                        },
+                    );
+                }
+                Vec::new()
+            }
+            "content_block_delta" => {
+                let delta = &val["delta"];
+                match delta["type"].as_str() {
+                    Some("text_delta") => match delta["text"].as_str() {
+                        Some(t) if !t.is_empty() => vec![Ok(ModelResponse::Token(t.to_string()))],
+                        _ => Vec::new(),
+                    },
+                    Some("input_json_delta") => {
+                        if let (Some(block), Some(part)) = (
+                            self.tool_blocks.get_mut(&index),
+                            delta["partial_json"].as_str(),
+                        ) {
+                            block.input_json.push_str(part);
+                        }
+                        Vec::new()
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            "content_block_stop" => match self.tool_blocks.remove(&index) {
+                Some(block) => {
+                    let arguments = if block.input_json.trim().is_empty() {
+                        json!({})
+                    } else {
+                        serde_json::from_str(&block.input_json)
+                            .unwrap_or(Value::String(block.input_json))
+                    };
+                    vec![Ok(ModelResponse::ToolCall(ToolCall {
+                        id: block.id,
+                        name: block.name,
+                        arguments,
+                    }))]
+                }
+                None => Vec::new(),
+            },
+            "message_start" => {
+                let usage = &val["message"]["usage"];
+                self.input_tokens = usage["input_tokens"].as_u64().unwrap_or(0);
+                Vec::new()
+            }
+            "message_delta" => match val["usage"]["output_tokens"].as_u64() {
+                Some(output) => vec![Ok(ModelResponse::Usage(Usage {
+                    prompt_tokens: self.input_tokens,
+                    completion_tokens: output,
+                }))],
+                None => Vec::new(),
+            },
+            "message_stop" => vec![Ok(ModelResponse::Done)],
+            "error" => vec![Err(anyhow!("Model endpoint error: {}", val["error"]))],
+            _ => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Asking the user before a tool call changes anything in GitLab.
+
+use futures::future::BoxFuture;
+use openduo_core::gitlab_client::PlannedRequest;
+use openduo_tools::registry::RiskLevel;
+use serde::Serialize;
+use serde_json::Value;
+
+/// A tool call waiting for the user's go-ahead.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApprovalRequest {
+    pub tool_call_id: String,
+    pub tool: String,
+    pub arguments: Value,
+    pub risk: RiskLevel,
+    /// The exact GitLab writes the call would make, secrets redacted.
+    /// Empty when the preview could not tell, e.g. invalid arguments.
+    pub requests: Vec<PlannedRequest>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    Approve,
+    /// Run the call with these arguments instead.
+    Edit(Value),
+    Reject {
+        reason: Option<String>,
+    },
+}
+
+/// Decides on tool calls `ReactLoop` will not run unasked: anything that
+/// is not a read.
+pub trait Approver: Send + Sync {
+    /// Whether the user allowed `tool` for the rest of the session.
+    fn always_allowed(&self, tool: &str) -> bool;
+
+    /// Put `request` to the user. Returns the id the answer will refer to
+    /// and a future resolving to the decision; dropping the future
+    /// withdraws the request.
+    fn request(&self, request: &ApprovalRequest) -> (String, BoxFuture<'static, Decision>);
+}
@@ -1,6 +1,7 @@
 use crate::provider::{
     ChatMessage, ChatRole, LlmProvider, ModelResponse, TokenStream, ToolDefinition,
 };
+use crate::tool_protocol::{extract_tool_calls, render_tool_call, render_tool_instructions};
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures::StreamExt;
@@ -17,10 +18,7 @@ pub struct GitLabAiProvider {
 
 impl GitLabAiProvider {
     pub fn new(config: &Config) -> Result<Self> {
-        let client = Client::builder()
-            .use_native_tls()
-            .build()
-            .map_err(|e| anyhow!("Failed to build reqwest client: {}", e))?;
+        let client = config.http.build_client()?;
         let gateway_url = format!(
             "{}/api/v4/chat/completions",
             config.gitlab_url.trim_end_matches('/')
@@ -35,25 +33,20 @@ impl GitLabAiProvider {
 
 #[async_trait]
 impl LlmProvider for GitLabAiProvider {
-    #[instrument(skip(self, messages, _tools))]
+    #[instrument(skip(self, messages, tools))]
     async fn chat_stream(
         &self,
         messages: Vec<ChatMessage>,
-        _tools: Vec<ToolDefinition>,
+        tools: Vec<ToolDefinition>,
     ) -> Result<TokenStream> {
         // GitLab Duo Chat API uses Bearer auth, not PRIVATE-TOKEN
         let headers = AuthHeaders::new(&self.pat).to_bearer_header_map()?;
 
-        // Extract the last user message as the content to send
-        let content = messages
-            .iter()
-            .rev()
-            .find(|m| matches!(m.role, ChatRole::User))
-            .map(|m| m.content.clone())
-            .unwrap_or_default();
-
-        // GitLab Duo Chat API request format
-        let body = json!({ "content": content });
+        // The endpoint only accepts a single `content` string, so the whole
+        // conversation is rendered into it and GitLab's server-side thread is
+        // reset on every call to avoid replaying turns twice.
+        let content = render_transcript(&messages, &tools);
+        let body = json!({ "content": content, "with_clean_history": true });
         debug!(
             "Sending to GitLab Duo Chat: {} (content length: {})",
             self.gateway_url,
@@ -106,7 +99,7 @@ impl LlmProvider for GitLabAiProvider {
                 };
                 futures::stream::iter(events)
             });
-            Ok(Box::pin(stream))
+            Ok(extract_tool_calls(Box::pin(stream)))
         } else {
             // Non-streaming response — read entire body as text
             let body_text = raw_resp.text().await?;
@@ -123,9 +116,51 @@ impl LlmProvider for GitLabAiProvider {
                 Ok(ModelResponse::Token(response_text)),
                 Ok(ModelResponse::Done),
             ];
-            Ok(Box::pin(futures::stream::iter(events)))
+            Ok(extract_tool_calls(Box::pin(futures::stream::iter(events))))
+        }
+    }
+}
+
+/// Render the conversation as a single prompt for the Duo Chat endpoint.
+///
+/// A lone user message with no system prompt or tools is sent verbatim.
+pub fn render_transcript(messages: &[ChatMessage], tools: &[ToolDefinition]) -> String {
+    if let [only] = messages {
+        if matches!(only.role, ChatRole::User) && tools.is_empty() {
+            return only.content.clone();
+        }
+    }
+
+    let mut preamble: Vec<String> = messages
+        .iter()
+        .filter(|m| matches!(m.role, ChatRole::System))
+        .map(|m| m.content.clone())
+        .collect();
+    if !tools.is_empty() {
+        preamble.push(render_tool_instructions(tools));
+    }
+
+    preamble.push("Conversation so far:".to_string());
+    let mut out = preamble.join("\n\n");
+    out.push('\n');
+    for m in messages {
+        let speaker = match m.role {
+            ChatRole::System => continue,
+            ChatRole::User => "User",
+            ChatRole::Assistant => "Assistant",
+            ChatRole::Tool => "Tool result",
+        };
+        let mut body = m.content.clone();
+        for call in &m.tool_calls {
+            if !body.is_empty() {
+                body.push('\n');
+            }
+            body.push_str(&render_tool_call(call));
         }
+        out.push_str(&format!("\n### {}\n{}\n", speaker, body));
     }
+    out.push_str("\nWrite the next Assistant reply.");
+    out
 }
 
 /// Parse SSE data: lines from a chunk, extracting text tokens.
@@ -1,4 +1,11 @@
+pub mod anthropic_provider;
+pub mod approval;
 pub mod gitlab_provider;
+pub mod line_stream;
+pub mod ollama_provider;
+pub mod openai_provider;
 pub mod prompt;
 pub mod provider;
 pub mod react_loop;
+pub mod testing;
+pub mod tool_protocol;
@@ -0,0 +1,96 @@
+use crate::provider::{ModelResponse, TokenStream};
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use std::collections::VecDeque;
+
+/// Accumulates raw bytes and yields complete lines. Network chunks do not
+/// respect line boundaries, so a JSON payload may arrive split in two.
+#[derive(Debug, Default)]
+pub struct LineBuffer {
+    buf: Vec<u8>,
+}
+
+impl LineBuffer {
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(bytes);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=pos).collect();
+            let text = String::from_utf8_lossy(&line);
+            lines.push(text.trim_end_matches(['\r', '\n']).to_string());
+        }
+        lines
+    }
+
+    pub fn finish(&mut self) -> Option<String> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let rest = std::mem::take(&mut self.buf);
+        Some(String::from_utf8_lossy(&rest).trim_end().to_string())
+    }
+}
+
+/// Turns individual lines of a streamed response into model events.
+pub trait LineDecoder: Send + 'static {
+    fn decode_line(&mut self, line: &str) -> Vec<Result<ModelResponse>>;
+
+    /// Called once when the body ends, to flush partially assembled state.
+    fn finish(&mut self) -> Vec<Result<ModelResponse>> {
+        Vec::new()
+    }
+}
+
+/// Extract the payload of an SSE `data:` line.
+pub fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(|d| d.trim())
+}
+
+/// Decode a line-oriented streaming HTTP body (SSE or NDJSON) with `decoder`.
+pub fn decode_lines<D: LineDecoder>(resp: reqwest::Response, decoder: D) -> TokenStream {
+    struct State<D> {
+        body: futures::stream::BoxStream<'static, reqwest::Result<Vec<u8>>>,
+        lines: LineBuffer,
+        decoder: D,
+        pending: VecDeque<Result<ModelResponse>>,
+        exhausted: bool,
+    }
+
+    let state = State {
+        body: resp.bytes_stream().map(|r| r.map(|b| b.to_vec())).boxed(),
+        lines: LineBuffer::default(),
+        decoder,
+        pending: VecDeque::new(),
+        exhausted: false,
+    };
+
+    let stream = futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop_front() {
+                return Some((event, state));
+            }
+            if state.exhausted {
+                return None;
+            }
+            match state.body.next().await {
+                Some(Ok(bytes)) => {
+                    for line in state.lines.push(&bytes) {
+                        state.pending.extend(state.decoder.decode_line(&line));
+                    }
+                }
+                Some(Err(e)) => {
+                    state.pending.push_back(Err(anyhow!(e)));
+                    state.exhausted = true;
+                }
+                None => {
+                    if let Some(line) = state.lines.finish() {
+                        state.pending.extend(state.decoder.decode_line(&line));
+                    }
+                    state.pending.extend(state.decoder.finish());
+                    state.exhausted = true;
+                }
+            }
+        }
+    });
+    Box::pin(stream)
+}
@@ -0,0 +1,175 @@
+use crate::line_stream::{decode_lines, LineDecoder};
+use crate::provider::{
+    ChatMessage, ChatRole, LlmProvider, ModelResponse, TokenStream, ToolCall, ToolDefinition, Usage,
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use openduo_core::config::Config;
+use reqwest::Client;
+use serde_json::{json, Value};
+use tracing::{debug, error, instrument};
+
+const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+
+/// Provider for a local Ollama server using the `/api/chat` NDJSON stream.
+pub struct OllamaProvider {
+    client: Client,
+    endpoint: String,
+    model: String,
+}
+
+impl OllamaProvider {
+    pub fn new(config: &Config) -> Result<Self> {
+        let base_url = config.llm_url.as_deref().unwrap_or(DEFAULT_OLLAMA_URL);
+        let model = config
+            .llm_model
+            .clone()
+            .ok_or_else(|| anyhow!("OPENDUO_LLM_MODEL must be set for the ollama provider"))?;
+        let client = config.http.build_client()?;
+        Ok(Self {
+            client,
+            endpoint: format!("{}/api/chat", base_url.trim_end_matches('/')),
+            model,
+        })
+    }
+
+    /// Build the JSON request body for a streamed `/api/chat` call.
+    pub fn request_body(&self, messages: &[ChatMessage], tools: &[ToolDefinition]) -> Value {
+        let messages: Vec<Value> = messages
+            .iter()
+            .map(|m| {
+                let role = match m.role {
+                    ChatRole::System => "system",
+                    ChatRole::User => "user",
+                    ChatRole::Assistant => "assistant",
+                    ChatRole::Tool => "tool",
+                };
+                let mut msg = json!({ "role": role, "content": m.content });
+                if !m.tool_calls.is_empty() {
+                    msg["tool_calls"] = m
+                        .tool_calls
+                        .iter()
+                        .map(|tc| {
+                            json!({ "function": { "name": tc.name, "arguments": tc.arguments } })
+                        })
+                        .collect();
+                }
+                msg
+            })
+            .collect();
+
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": true,
+        });
+        if !tools.is_empty() {
+            body["tools"] = tools
+                .iter()
+                .map(|t| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": t.name,
+                            "description": t.description,
+                            "parameters": t.parameters,
+                        }
+                    })
+                })
+                .collect();
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    #[instrument(skip(self, messages, tools))]
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<TokenStream> {
+        let body = self.request_body(&messages, &tools);
+        debug!(
+            "Sending to Ollama: {} ({} messages, {} tools)",
+            self.endpoint,
+            messages.len(),
+            tools.len()
+        );
+
+        let resp = self
+            .client
+            .post(&self.endpoint)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| {
+                error!("Failed to connect to Ollama at {}: {}", self.endpoint, e);
+                anyhow!("Failed to connect to Ollama: {}", e)
+            })?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body_text = resp.text().await.unwrap_or_default();
+            error!("Ollama returned HTTP {}: {}", status, body_text);
+            return Err(anyhow!("Ollama returned HTTP {} — {}", status, body_text));
+        }
+
+        Ok(decode_lines(resp, OllamaStreamDecoder))
+    }
+}
+
+/// Decodes Ollama's newline-delimited JSON chat stream. Tool calls arrive
+/// whole in a single chunk, so no assembly state is needed.
+#[derive(Debug, Default)]
+pub struct OllamaStreamDecoder;
+
+impl LineDecoder for OllamaStreamDecoder {
+    fn decode_line(&mut self, line: &str) -> Vec<Result<ModelResponse>> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Vec::new();
+        }
+        let val: Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(e) => return vec![Err(anyhow!("Invalid Ollama chunk: {} ({})", e, line))],
+        };
+        if let Some(err) = val["error"].as_str() {
+            return vec![Err(anyhow!("Ollama error: {}", err))];
+        }
+
+        let mut events = Vec::new();
+        let message = &val["message"];
+        if let Some(token) = message["content"].as_str() {
+            if !token.is_empty() {
+                events.push(Ok(ModelResponse::Token(token.to_string())));
+            }
+        }
+        if let Some(calls) = message["tool_calls"].as_array() {
+            for call in calls {
+                let function = &call["function"];
+                let arguments = match &function["arguments"] {
+                    Value::String(s) => serde_json::from_str(s).unwrap_or(Value::String(s.clone())),
+                    Value::Null => json!({}),
+                    other => other.clone(),
+                };
+                events.push(Ok(ModelResponse::ToolCall(ToolCall {
+                    id: call["id"].as_str().unwrap_or_default().to_string(),
+                    name: function["name"].as_str().unwrap_or_default().to_string(),
+                    arguments,
+                })));
+            }
+        }
+        if val["done"].as_bool() == Some(true) {
+            if let Some(completion) = val["eval_count"].as_u64() {
+                events.push(Ok(ModelResponse::Usage(Usage {
+                    prompt_tokens: val["prompt_eval_count"].as_u64().unwrap_or(0),
+                    completion_tokens: completion,
+                })));
+            }
+            events.push(Ok(ModelResponse::Done));
+        }
+        events
+    }
+}
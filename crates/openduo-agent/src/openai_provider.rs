@@ -0,0 +1,279 @@
+use crate::line_stream::{decode_lines, sse_data, LineDecoder};
+use crate::provider::{
+    ChatMessage, ChatRole, LlmProvider, ModelResponse, TokenStream, ToolCall, ToolDefinition, Usage,
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use openduo_core::{auth::AuthHeaders, config::Config};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use tracing::{debug, error, instrument};
+
+/// Provider for OpenAI-compatible `/v1/chat/completions` endpoints
+/// (vLLM, LiteLLM, llama.cpp server) using native function calling.
+pub struct OpenAiProvider {
+    client: Client,
+    endpoint: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl OpenAiProvider {
+    pub fn new(config: &Config) -> Result<Self> {
+        let base_url = config
+            .llm_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("OPENDUO_LLM_URL must be set for the openai provider"))?;
+        let model = config
+            .llm_model
+            .clone()
+            .ok_or_else(|| anyhow!("OPENDUO_LLM_MODEL must be set for the openai provider"))?;
+        let client = config.http.build_client()?;
+        Ok(Self {
+            client,
+            endpoint: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+            api_key: config.llm_api_key.clone(),
+            model,
+        })
+    }
+
+    /// Build the JSON request body for a streamed chat completion.
+    pub fn request_body(&self, messages: &[ChatMessage], tools: &[ToolDefinition]) -> Value {
+        let messages: Vec<Value> = messages.iter().map(openai_message).collect();
+
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": true,
+            // Usage then arrives in a final chunk with no choices.
+            "stream_options": { "include_usage": true },
+        });
+        if !tools.is_empty() {
+            body["tools"] = tools
+                .iter()
+                .map(|t| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": t.name,
+                            "description": t.description,
+                            "parameters": t.parameters,
+                        }
+                    })
+                })
+                .collect();
+        }
+        body
+    }
+
+    fn headers(&self) -> Result<HeaderMap> {
+        match &self.api_key {
+            Some(key) => AuthHeaders::new(key).to_bearer_header_map(),
+            None => {
+                let mut map = HeaderMap::new();
+                map.insert(
+                    reqwest::header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                );
+                Ok(map)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiProvider {
+    #[instrument(skip(self, messages, tools))]
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<TokenStream> {
+        let body = self.request_body(&messages, &tools);
+        debug!(
+            "Sending to OpenAI-compatible endpoint: {} ({} messages, {} tools)",
+            self.endpoint,
+            messages.len(),
+            tools.len()
+        );
+
+        let resp = self
+            .client
+            .post(&self.endpoint)
+            .headers(self.headers()?)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| {
+                error!("Failed to connect to {}: {}", self.endpoint, e);
+                anyhow!("Failed to connect to model endpoint: {}", e)
+            })?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body_text = resp.text().await.unwrap_or_default();
+            error!("Model endpoint returned HTTP {}: {}", status, body_text);
+            return Err(anyhow!(
+                "Model endpoint returned HTTP {} — {}",
+                status,
+                body_text
+            ));
+        }
+
+        Ok(decode_lines(resp, OpenAiStreamDecoder::default()))
+    }
+}
+
+fn openai_message(m: &ChatMessage) -> Value {
+    match m.role {
+        ChatRole::System => json!({ "role": "system", "content": m.content }),
+        ChatRole::User => json!({ "role": "user", "content": m.content }),
+        ChatRole::Assistant if !m.tool_calls.is_empty() => {
+            let calls: Vec<Value> = m
+                .tool_calls
+                .iter()
+                .map(|tc| {
+                    json!({
+                        "id": tc.id,
+                        "type": "function",
+                        "function": {
+                            "name": tc.name,
+                            "arguments": tc.arguments.to_string(),
+                        }
+                    })
+                })
+                .collect();
+            let content = if m.content.is_empty() {
+                Value::Null
+            } else {
+                json!(m.content)
+            };
+            json!({ "role": "assistant", "content": content, "tool_calls": calls })
+        }
+        ChatRole::Assistant => json!({ "role": "assistant", "content": m.content }),
+        ChatRole::Tool => match &m.tool_call_id {
+            Some(id) => json!({ "role": "tool", "tool_call_id": id, "content": m.content }),
+            // Results recorded without a call id cannot be paired, so send them as user turns.
+            None => json!({ "role": "user", "content": m.content }),
+        },
+    }
+}
+
+#[derive(Debug, Default)]
+struct PartialToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Decodes `chat.completion.chunk` SSE events, assembling tool calls whose
+/// name and arguments arrive as incremental deltas keyed by `index`.
+#[derive(Debug, Default)]
+pub struct OpenAiStreamDecoder {
+    calls: BTreeMap<u64, PartialToolCall>,
+    /// A `finish_reason` arrived; `Done` waits for `[DONE]` so the usage
+    /// chunk that follows it is not lost.
+    finished: bool,
+    done: bool,
+}
+
+impl OpenAiStreamDecoder {
+    fn flush_calls(&mut self) -> Vec<Result<ModelResponse>> {
+        std::mem::take(&mut self.calls)
+            .into_values()
+            .map(|c| {
+                let arguments = if c.arguments.trim().is_empty() {
+                    json!({})
+                } else {
+                    serde_json::from_str(&c.arguments).unwrap_or(Value::String(c.arguments))
+                };
+                Ok(ModelResponse::ToolCall(ToolCall {
+                    id: c.id,
+                    name: c.name,
+                    arguments,
+                }))
+            })
+            .collect()
+    }
+
+    fn complete(&mut self) -> Vec<Result<ModelResponse>> {
+        if self.done {
+            return Vec::new();
+        }
+        self.done = true;
+        let mut events = self.flush_calls();
+        events.push(Ok(ModelResponse::Done));
+        events
+    }
+}
+
+impl LineDecoder for OpenAiStreamDecoder {
+    fn decode_line(&mut self, line: &str) -> Vec<Result<ModelResponse>> {
+        let Some(data) = sse_data(line) else {
+            return Vec::new();
+        };
+        if data.is_empty() {
+            return Vec::new();
+        }
+        if data == "[DONE]" {
+            return self.complete();
+        }
+        let val: Value = match serde_json::from_str(data) {
+            Ok(v) => v,
+            Err(e) => return vec![Err(anyhow!("Invalid stream chunk: {} ({})", e, data))],
+        };
+        if let Some(err) = val.get("error") {
+            return vec![Err(anyhow!("Model endpoint error: {}", err))];
+        }
+
+        let mut events = Vec::new();
+        // Only sent by servers that report usage on streamed responses.
+        if let Some(usage) = val.get("usage").filter(|u| u.is_object()) {
+            events.push(Ok(ModelResponse::Usage(Usage {
+                prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+                completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+            })));
+        }
+        let choice = &val["choices"][0];
+        let delta = &choice["delta"];
+        if let Some(token) = delta["content"].as_str() {
+            if !token.is_empty() {
+                events.push(Ok(ModelResponse::Token(token.to_string())));
+            }
+        }
+        if let Some(deltas) = delta["tool_calls"].as_array() {
+            for d in deltas {
+                let index = d["index"].as_u64().unwrap_or(0);
+                let call = self.calls.entry(index).or_default();
+                if let Some(id) = d["id"].as_str() {
+                    call.id.push_str(id);
+                }
+                if let Some(name) = d["function"]["name"].as_str() {
+                    call.name.push_str(name);
+                }
+                if let Some(args) = d["function"]["arguments"].as_str() {
+                    call.arguments.push_str(args);
+                }
+            }
+        }
+        if choice["finish_reason"].is_string() {
+            self.finished = true;
+            events.extend(self.flush_calls());
+        }
+        events
+    }
+
+    fn finish(&mut self) -> Vec<Result<ModelResponse>> {
+        // Some servers close the connection without `[DONE]`, or without a
+        // finish_reason at all.
+        if self.done {
+            Vec::new()
+        } else if self.finished {
+            self.complete()
+        } else {
+            self.flush_calls()
+        }
+    }
+}
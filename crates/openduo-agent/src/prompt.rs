@@ -1,12 +1,12 @@
-use crate::provider::{ChatMessage, ChatRole};
+use crate::provider::{ChatMessage, ChatRole, ToolCall};
 
 pub struct PromptBuilder;
 
 impl PromptBuilder {
     pub fn build_initial(gitlab_url: &str) -> Vec<ChatMessage> {
-        vec![ChatMessage {
-            role: ChatRole::System,
-            content: format!(
+        vec![ChatMessage::new(
+            ChatRole::System,
+            format!(
                 "You are OpenDuo, an AI assistant integrated with GitLab at {}. \
                 You help the user interact with their GitLab instance by using available tools. \
                 Always think step-by-step. Use tools to fetch real data before answering. \
@@ -14,27 +14,30 @@ impl PromptBuilder {
                 When you have enough information, provide a clear, concise answer.",
                 gitlab_url
             ),
-        }]
+        )]
     }
 
     pub fn append_user(history: &mut Vec<ChatMessage>, content: &str) {
-        history.push(ChatMessage {
-            role: ChatRole::User,
-            content: content.to_string(),
-        });
+        history.push(ChatMessage::new(ChatRole::User, content));
     }
 
     pub fn append_assistant(history: &mut Vec<ChatMessage>, content: &str) {
-        history.push(ChatMessage {
-            role: ChatRole::Assistant,
-            content: content.to_string(),
-        });
+        history.push(ChatMessage::new(ChatRole::Assistant, content));
     }
 
-    pub fn append_tool_result(history: &mut Vec<ChatMessage>, tool_name: &str, result: &str) {
-        history.push(ChatMessage {
-            role: ChatRole::Tool,
-            content: format!("Tool `{}` returned:\n{}", tool_name, result),
-        });
+    /// Record an assistant turn that requested tools, with any text it produced.
+    pub fn append_tool_calls(history: &mut Vec<ChatMessage>, content: &str, calls: &[ToolCall]) {
+        let mut msg = ChatMessage::new(ChatRole::Assistant, content);
+        msg.tool_calls = calls.to_vec();
+        history.push(msg);
+    }
+
+    pub fn append_tool_result(history: &mut Vec<ChatMessage>, call: &ToolCall, result: &str) {
+        let mut msg = ChatMessage::new(
+            ChatRole::Tool,
+            format!("Tool `{}` returned:\n{}", call.name, result),
+        );
+        msg.tool_call_id = Some(call.id.clone());
+        history.push(msg);
     }
 }
@@ -3,6 +3,13 @@ use async_trait::async_trait;
 use futures::Stream;
 use serde::{Deserialize, Serialize};
 use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::anthropic_provider::AnthropicProvider;
+use crate::gitlab_provider::GitLabAiProvider;
+use crate::ollama_provider::OllamaProvider;
+use crate::openai_provider::OpenAiProvider;
+use openduo_core::config::{Config, ProviderKind};
 
 // Re-export from openduo-core so consumers keep using openduo_agent::provider::ToolDefinition
 pub use openduo_core::types::ToolDefinition;
@@ -20,18 +27,55 @@ pub enum ChatRole {
 pub struct ChatMessage {
     pub role: ChatRole,
     pub content: String,
+    /// Tool calls requested by an assistant turn.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// For `ChatRole::Tool` messages, the id of the call this result answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+impl ChatMessage {
+    pub fn new(role: ChatRole, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCall {
+    /// Provider-assigned call id. Empty when the backend has no native ids;
+    /// `ReactLoop` fills one in before recording the call.
+    #[serde(default)]
+    pub id: String,
     pub name: String,
     pub arguments: serde_json::Value,
 }
 
+/// Token counts for one model call, as reported by the backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub enum ModelResponse {
     Token(String),
     ToolCall(ToolCall),
+    /// Sent before `Done` by backends that report token counts.
+    Usage(Usage),
     Done,
 }
 
@@ -45,3 +89,13 @@ pub trait LlmProvider: Send + Sync {
         tools: Vec<ToolDefinition>,
     ) -> Result<TokenStream>;
 }
+
+/// Construct the provider selected by `config.provider`.
+pub fn provider_from_config(config: &Config) -> Result<Arc<dyn LlmProvider>> {
+    Ok(match config.provider {
+        ProviderKind::GitLab => Arc::new(GitLabAiProvider::new(config)?),
+        ProviderKind::OpenAi => Arc::new(OpenAiProvider::new(config)?),
+        ProviderKind::Anthropic => Arc::new(AnthropicProvider::new(config)?),
+        ProviderKind::Ollama => Arc::new(OllamaProvider::new(config)?),
+    })
+}
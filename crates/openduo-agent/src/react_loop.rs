@@ -1,18 +1,96 @@
+use crate::approval::{ApprovalRequest, Approver, Decision};
 use crate::prompt::PromptBuilder;
-use crate::provider::{ChatMessage, LlmProvider, ModelResponse};
+use crate::provider::{ChatMessage, ChatRole, LlmProvider, ModelResponse, ToolCall, Usage};
 use anyhow::Result;
 use futures::StreamExt;
-use openduo_tools::registry::ToolRegistry;
+use openduo_tools::registry::{RiskLevel, ToolRegistry};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
+/// Note appended to the assistant's reply when a turn is cancelled.
+pub const CANCELLED_NOTE: &str = "(Cancelled by the user.)";
+
+/// Observation for a cancelled call that had already been sent.
+const CANCELLED_IN_FLIGHT: &str = "Cancelled while running: the result is unknown and any \
+    change this call makes may already have been applied. Check the current state before \
+    retrying it.";
+
+/// Observation for a call cancelled before it was sent.
+const CANCELLED_NOT_RUN: &str = "Cancelled; this call was not run.";
+
+/// Returned by `ReactLoop::run` when its cancellation token fires. History
+/// is still consistent: every tool call has a result and the turn ends with
+/// an assistant message.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("turn cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Progress reported by `ReactLoop::run` while a turn runs.
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    /// A model call is starting; counts from 1.
+    Iteration(usize),
+    Token(String),
+    ToolCallStarted {
+        id: String,
+        name: String,
+        arguments: serde_json::Value,
+    },
+    ToolCallFinished {
+        id: String,
+        name: String,
+        duration: Duration,
+        /// Size of the result handed back to the model.
+        result_bytes: usize,
+        error: Option<String>,
+    },
+    /// Token counts for the model call that just ended.
+    Usage(Usage),
+    /// The turn waits for the user to answer `approval_id`.
+    ApprovalRequested {
+        approval_id: String,
+        request: ApprovalRequest,
+    },
+}
+
 pub struct ReactLoop {
     max_iterations: usize,
+    cancel: CancellationToken,
+    approver: Option<Arc<dyn Approver>>,
 }
 
 impl ReactLoop {
     pub fn new(max_iterations: usize) -> Self {
-        Self { max_iterations }
+        Self {
+            max_iterations,
+            cancel: CancellationToken::new(),
+            approver: None,
+        }
+    }
+
+    /// Ask `approver` before running any tool that is not a read. Without
+    /// one, every call runs as soon as the model asks.
+    pub fn with_approver(mut self, approver: Arc<dyn Approver>) -> Self {
+        self.approver = Some(approver);
+        self
+    }
+
+    /// Stop the turn when `cancel` fires, abandoning any model stream or
+    /// tool call in flight.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
     }
 
     pub async fn run(
@@ -21,7 +99,7 @@ impl ReactLoop {
         history: &mut Vec<ChatMessage>,
         provider: &Arc<dyn LlmProvider>,
         tools: &ToolRegistry,
-        on_token: impl Fn(String) + Send + Sync,
+        on_event: impl Fn(AgentEvent) + Send + Sync,
     ) -> Result<String> {
         PromptBuilder::append_user(history, user_message);
         let tool_defs = tools.definitions();
@@ -29,38 +107,72 @@ impl ReactLoop {
 
         for iteration in 0..self.max_iterations {
             info!("ReAct iteration {}", iteration + 1);
-            let mut stream = provider
-                .chat_stream(history.clone(), tool_defs.clone())
-                .await
-                .map_err(|e| {
-                    error!("LLM provider error: {:#}", e);
-                    e
-                })?;
+            on_event(AgentEvent::Iteration(iteration + 1));
             let mut current_response = String::new();
+            let mut stream = tokio::select! {
+                biased;
+                _ = self.cancel.cancelled() => return Err(cancel_turn(history, &current_response)),
+                stream = provider.chat_stream(history.clone(), tool_defs.clone()) => {
+                    stream.map_err(|e| {
+                        error!("LLM provider error: {:#}", e);
+                        e
+                    })?
+                }
+            };
             let mut tool_calls: Vec<crate::provider::ToolCall> = Vec::new();
 
-            while let Some(event) = stream.next().await {
+            loop {
+                let event = tokio::select! {
+                    biased;
+                    _ = self.cancel.cancelled() => return Err(cancel_turn(history, &current_response)),
+                    event = stream.next() => event,
+                };
+                let Some(event) = event else { break };
                 match event? {
                     ModelResponse::Token(token) => {
-                        on_token(token.clone());
+                        on_event(AgentEvent::Token(token.clone()));
                         current_response.push_str(&token);
                     }
                     ModelResponse::ToolCall(tc) => {
                         tool_calls.push(tc);
                     }
+                    ModelResponse::Usage(usage) => on_event(AgentEvent::Usage(usage)),
                     ModelResponse::Done => break,
                 }
             }
 
             if !tool_calls.is_empty() {
-                for tc in tool_calls {
-                    info!("Executing tool: {}", tc.name);
-                    let result = tools
-                        .execute(&tc.name, tc.arguments)
-                        .await
-                        .unwrap_or_else(|e| format!("Tool error: {}", e));
-                    PromptBuilder::append_assistant(history, &format!("[Using tool: {}]", tc.name));
-                    PromptBuilder::append_tool_result(history, &tc.name, &result);
+                // Providers without call ids get random ones, unique across
+                // the turns a history may hold.
+                for tc in tool_calls.iter_mut().filter(|tc| tc.id.is_empty()) {
+                    tc.id = format!("call_{:016x}", fastrand::u64(..));
+                }
+                PromptBuilder::append_tool_calls(history, &current_response, &tool_calls);
+                for (n, tc) in tool_calls.iter().enumerate() {
+                    let executing = AtomicBool::new(false);
+                    let (arguments, result) = tokio::select! {
+                        biased;
+                        _ = self.cancel.cancelled() => {
+                            // Answer the calls that never finished so the
+                            // history stays valid for the next turn. A call
+                            // already sent may have changed GitLab anyway.
+                            let in_flight = if executing.load(Ordering::SeqCst) {
+                                CANCELLED_IN_FLIGHT
+                            } else {
+                                CANCELLED_NOT_RUN
+                            };
+                            PromptBuilder::append_tool_result(history, tc, in_flight);
+                            for tc in &tool_calls[n + 1..] {
+                                PromptBuilder::append_tool_result(history, tc, CANCELLED_NOT_RUN);
+                            }
+                            return Err(cancel_turn(history, ""));
+                        }
+                        outcome = self.run_tool(tc, tools, &executing, &on_event) => outcome,
+                    };
+                    if arguments != tc.arguments {
+                        replace_arguments(history, &tc.id, arguments);
+                    }
+                    PromptBuilder::append_tool_result(history, tc, &result);
                 }
             } else {
                 final_response = current_response.clone();
@@ -73,7 +185,7 @@ impl ReactLoop {
                 final_response = "I've reached the maximum number of reasoning steps. \
                     Please try rephrasing your question."
                     .to_string();
-                on_token(final_response.clone());
+                on_event(AgentEvent::Token(final_response.clone()));
                 PromptBuilder::append_assistant(history, &final_response);
             }
         }
@@ -81,3 +193,99 @@ impl ReactLoop {
         Ok(final_response)
     }
 }
+
+impl ReactLoop {
+    /// Run one tool call, asking for approval first if it needs it; sets
+    /// `executing` once the call is actually sent. Returns the arguments it
+    /// ran with and the observation for the model.
+    async fn run_tool(
+        &self,
+        tc: &ToolCall,
+        tools: &ToolRegistry,
+        executing: &AtomicBool,
+        on_event: &(impl Fn(AgentEvent) + Send + Sync),
+    ) -> (serde_json::Value, String) {
+        let mut arguments = tc.arguments.clone();
+        let risk = tools.risk_level(&tc.name);
+        if let Some(approver) = self
+            .approver
+            .as_ref()
+            .filter(|a| risk != RiskLevel::Read && !a.always_allowed(&tc.name))
+        {
+            let request = ApprovalRequest {
+                tool_call_id: tc.id.clone(),
+                tool: tc.name.clone(),
+                arguments: arguments.clone(),
+                risk,
+                requests: tools.preview(&tc.name, arguments.clone()).await,
+            };
+            let (approval_id, decision) = approver.request(&request);
+            info!("Waiting for approval of {}", tc.name);
+            on_event(AgentEvent::ApprovalRequested {
+                approval_id,
+                request,
+            });
+            match decision.await {
+                Decision::Approve => {}
+                Decision::Edit(edited) => arguments = edited,
+                // Never started, so no tool call events.
+                Decision::Reject { reason } => {
+                    info!("User rejected {}", tc.name);
+                    let mut result =
+                        "The user rejected this tool call; it was not run.".to_string();
+                    if let Some(reason) = reason {
+                        result.push_str(&format!(" Their reason: {}", reason));
+                    }
+                    return (arguments, result);
+                }
+            }
+        }
+
+        info!("Executing tool: {}", tc.name);
+        on_event(AgentEvent::ToolCallStarted {
+            id: tc.id.clone(),
+            name: tc.name.clone(),
+            arguments: arguments.clone(),
+        });
+        executing.store(true, Ordering::SeqCst);
+        let started = Instant::now();
+        let (result, error) = match tools.execute(&tc.name, arguments.clone()).await {
+            Ok(result) => (result, None),
+            Err(e) => (format!("Tool error: {}", e), Some(e.to_string())),
+        };
+        on_event(AgentEvent::ToolCallFinished {
+            id: tc.id.clone(),
+            name: tc.name.clone(),
+            duration: started.elapsed(),
+            result_bytes: result.len(),
+            error,
+        });
+        (arguments, result)
+    }
+}
+
+/// Record the arguments the user edited a call to, so the model sees what
+/// actually ran.
+fn replace_arguments(history: &mut [ChatMessage], call_id: &str, arguments: serde_json::Value) {
+    let call = history
+        .iter_mut()
+        .rev()
+        .filter(|m| matches!(m.role, ChatRole::Assistant))
+        .flat_map(|m| m.tool_calls.iter_mut())
+        .find(|c| c.id == call_id);
+    if let Some(call) = call {
+        call.arguments = arguments;
+    }
+}
+
+/// Close the turn with whatever the model had said so far.
+fn cancel_turn(history: &mut Vec<ChatMessage>, partial: &str) -> anyhow::Error {
+    info!("Turn cancelled");
+    let content = if partial.is_empty() {
+        CANCELLED_NOTE.to_string()
+    } else {
+        format!("{}\n\n{}", partial, CANCELLED_NOTE)
+    };
+    PromptBuilder::append_assistant(history, &content);
+    Cancelled.into()
+}
@@ -0,0 +1,253 @@
+//! Deterministic stand-ins for an LLM backend and a GitLab tool, for
+//! exercising `ReactLoop` without network access.
+
+use crate::approval::{ApprovalRequest, Approver, Decision};
+use crate::provider::{
+    ChatMessage, LlmProvider, ModelResponse, TokenStream, ToolCall, ToolDefinition,
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
+use openduo_core::gitlab_client::PlannedRequest;
+use openduo_tools::registry::{RiskLevel, Tool};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// One scripted reply to a `chat_stream` call.
+pub enum ScriptedTurn {
+    /// Stream these events; `Err` items are yielded as stream errors.
+    Events(Vec<std::result::Result<ModelResponse, String>>),
+    /// Fail the `chat_stream` call itself, as a connection error would.
+    Fail(String),
+    /// Stream these events, then wait forever without finishing, like a
+    /// model that is still generating.
+    Stall(Vec<ModelResponse>),
+}
+
+impl ScriptedTurn {
+    /// A plain text answer followed by `Done`.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Events(vec![
+            Ok(ModelResponse::Token(text.into())),
+            Ok(ModelResponse::Done),
+        ])
+    }
+
+    /// A single tool call followed by `Done`.
+    pub fn tool_call(name: impl Into<String>, arguments: Value) -> Self {
+        Self::tool_calls(vec![(name.into(), arguments)])
+    }
+
+    /// Several tool calls in one turn, followed by `Done`.
+    pub fn tool_calls(calls: Vec<(String, Value)>) -> Self {
+        let mut events: Vec<std::result::Result<ModelResponse, String>> = calls
+            .into_iter()
+            .map(|(name, arguments)| {
+                Ok(ModelResponse::ToolCall(ToolCall {
+                    id: String::new(),
+                    name,
+                    arguments,
+                }))
+            })
+            .collect();
+        events.push(Ok(ModelResponse::Done));
+        Self::Events(events)
+    }
+}
+
+/// An `LlmProvider` that replays queued turns in order and records the
+/// conversation it was handed on every call.
+#[derive(Default)]
+pub struct ScriptedProvider {
+    turns: Mutex<VecDeque<ScriptedTurn>>,
+    requests: Mutex<Vec<Vec<ChatMessage>>>,
+}
+
+impl ScriptedProvider {
+    pub fn new(turns: Vec<ScriptedTurn>) -> Self {
+        Self {
+            turns: Mutex::new(turns.into()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Queue another turn after the existing ones.
+    pub fn push(&self, turn: ScriptedTurn) {
+        self.turns.lock().unwrap().push_back(turn);
+    }
+
+    /// The message history passed to each `chat_stream` call, in order.
+    pub fn requests(&self) -> Vec<Vec<ChatMessage>> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Number of scripted turns not yet consumed.
+    pub fn remaining(&self) -> usize {
+        self.turns.lock().unwrap().len()
+    }
+}
+
+#[async_trait]
+impl LlmProvider for ScriptedProvider {
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        _tools: Vec<ToolDefinition>,
+    ) -> Result<TokenStream> {
+        self.requests.lock().unwrap().push(messages);
+        let turn = self
+            .turns
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| anyhow!("ScriptedProvider has no scripted turns left"))?;
+        match turn {
+            ScriptedTurn::Fail(msg) => Err(anyhow!(msg)),
+            ScriptedTurn::Events(events) => {
+                let events: Vec<Result<ModelResponse>> = events
+                    .into_iter()
+                    .map(|e| e.map_err(|m| anyhow!(m)))
+                    .collect();
+                Ok(Box::pin(futures::stream::iter(events)))
+            }
+            ScriptedTurn::Stall(events) => Ok(Box::pin(
+                futures::stream::iter(events.into_iter().map(Ok)).chain(futures::stream::pending()),
+            )),
+        }
+    }
+}
+
+/// A `Tool` that returns a canned result and records every argument set it
+/// was invoked with.
+pub struct FakeTool {
+    name: String,
+    result: std::result::Result<String, String>,
+    invocations: Arc<Mutex<Vec<Value>>>,
+    hang: bool,
+    risk: RiskLevel,
+}
+
+impl FakeTool {
+    pub fn new(name: impl Into<String>, result: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            result: Ok(result.into()),
+            invocations: Arc::default(),
+            hang: false,
+            risk: RiskLevel::Read,
+        }
+    }
+
+    /// A tool whose calls never return, like a slow GitLab request.
+    pub fn hanging(name: impl Into<String>) -> Self {
+        Self {
+            hang: true,
+            ..Self::new(name, "")
+        }
+    }
+
+    /// A tool whose every call fails with `error`.
+    pub fn failing(name: impl Into<String>, error: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            result: Err(error.into()),
+            invocations: Arc::default(),
+            hang: false,
+            risk: RiskLevel::Read,
+        }
+    }
+
+    /// Report `risk`; the preview of a non-read call is a single
+    /// `POST fake://<name>` carrying the arguments.
+    pub fn with_risk(mut self, risk: RiskLevel) -> Self {
+        self.risk = risk;
+        self
+    }
+
+    /// Shared handle to the recorded arguments; stays valid after the tool
+    /// is boxed into a `ToolRegistry`.
+    pub fn invocations(&self) -> Arc<Mutex<Vec<Value>>> {
+        self.invocations.clone()
+    }
+}
+
+#[async_trait]
+impl Tool for FakeTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn description(&self) -> &str {
+        "Test double that returns a canned result."
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({ "type": "object", "properties": {}, "required": [] })
+    }
+    fn risk_level(&self) -> RiskLevel {
+        self.risk
+    }
+    async fn preview(&self, args: Value) -> Vec<PlannedRequest> {
+        vec![PlannedRequest {
+            method: "POST".to_string(),
+            url: format!("fake://{}", self.name),
+            body: Some(args),
+        }]
+    }
+    async fn execute(&self, args: Value) -> Result<String> {
+        self.invocations.lock().unwrap().push(args);
+        if self.hang {
+            futures::future::pending::<()>().await;
+        }
+        self.result.clone().map_err(|e| anyhow!(e))
+    }
+}
+
+/// An `Approver` that answers with scripted decisions, rejecting once they
+/// run out, and records what it was asked.
+#[derive(Default)]
+pub struct ScriptedApprover {
+    decisions: Mutex<VecDeque<Decision>>,
+    requests: Arc<Mutex<Vec<ApprovalRequest>>>,
+    always: Vec<String>,
+}
+
+impl ScriptedApprover {
+    pub fn new(decisions: Vec<Decision>) -> Self {
+        Self {
+            decisions: Mutex::new(decisions.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Treat `tool` as allowed for the whole session.
+    pub fn always_allowing(mut self, tool: impl Into<String>) -> Self {
+        self.always.push(tool.into());
+        self
+    }
+
+    pub fn requests(&self) -> Arc<Mutex<Vec<ApprovalRequest>>> {
+        self.requests.clone()
+    }
+}
+
+impl Approver for ScriptedApprover {
+    fn always_allowed(&self, tool: &str) -> bool {
+        self.always.iter().any(|t| t == tool)
+    }
+
+    fn request(&self, request: &ApprovalRequest) -> (String, BoxFuture<'static, Decision>) {
+        let mut requests = self.requests.lock().unwrap();
+        requests.push(request.clone());
+        let decision = self
+            .decisions
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or(Decision::Reject { reason: None });
+        (
+            format!("approval-{}", requests.len()),
+            futures::future::ready(decision).boxed(),
+        )
+    }
+}
@@ -0,0 +1,207 @@
+use crate::provider::{ModelResponse, TokenStream, ToolCall, ToolDefinition};
+use anyhow::Result;
+use futures::StreamExt;
+use serde_json::Value;
+use std::collections::VecDeque;
+use tracing::warn;
+
+pub const TOOL_CALL_OPEN: &str = "<tool_call>";
+pub const TOOL_CALL_CLOSE: &str = "</tool_call>";
+
+/// Render tool schemas and calling instructions for endpoints without native
+/// function calling. The model replies with `<tool_call>{json}</tool_call>` blocks.
+pub fn render_tool_instructions(tools: &[ToolDefinition]) -> String {
+    let mut sorted: Vec<&ToolDefinition> = tools.iter().collect();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut out = format!(
+        "You can call tools to read and modify GitLab data. To call a tool, reply with \
+        a block of exactly this form:\n\
+        {open}{{\"name\": \"<tool name>\", \"arguments\": {{ ... }}}}{close}\n\
+        The arguments must be a JSON object matching the tool's parameter schema. \
+        You may emit several blocks in one reply. After the blocks, stop and wait: \
+        the tool results will be sent back to you. When you can answer the user, \
+        reply in plain text without any {open} block.\n\nAvailable tools:\n",
+        open = TOOL_CALL_OPEN,
+        close = TOOL_CALL_CLOSE,
+    );
+    for tool in sorted {
+        out.push_str(&format!(
+            "- {}: {}\n  Parameters: {}\n",
+            tool.name, tool.description, tool.parameters
+        ));
+    }
+    out
+}
+
+/// Render a recorded call back into the `<tool_call>` form the model emitted.
+pub fn render_tool_call(call: &ToolCall) -> String {
+    format!(
+        "{}{}{}",
+        TOOL_CALL_OPEN,
+        serde_json::json!({ "name": call.name, "arguments": call.arguments }),
+        TOOL_CALL_CLOSE
+    )
+}
+
+/// Incremental parser that separates `<tool_call>` blocks from visible text.
+///
+/// Text that might be the beginning of an opening tag is held back until the
+/// next chunk disambiguates it, so tag bytes never reach the token stream.
+#[derive(Debug, Default)]
+pub struct ToolCallParser {
+    buffer: String,
+    in_call: bool,
+}
+
+impl ToolCallParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a chunk of model output, returning the events it completes.
+    pub fn push(&mut self, chunk: &str) -> Vec<ModelResponse> {
+        self.buffer.push_str(chunk);
+        let mut events = Vec::new();
+        loop {
+            if self.in_call {
+                let Some(end) = self.buffer.find(TOOL_CALL_CLOSE) else {
+                    break;
+                };
+                let body: String = self.buffer.drain(..end).collect();
+                self.buffer.drain(..TOOL_CALL_CLOSE.len());
+                self.in_call = false;
+                events.push(parse_call_body(&body));
+            } else if let Some(start) = self.buffer.find(TOOL_CALL_OPEN) {
+                let text: String = self.buffer.drain(..start).collect();
+                self.buffer.drain(..TOOL_CALL_OPEN.len());
+                self.in_call = true;
+                if !text.is_empty() {
+                    events.push(ModelResponse::Token(text));
+                }
+            } else {
+                let keep = partial_tag_suffix_len(&self.buffer, TOOL_CALL_OPEN);
+                let emit_to = self.buffer.len() - keep;
+                if emit_to > 0 {
+                    let text: String = self.buffer.drain(..emit_to).collect();
+                    events.push(ModelResponse::Token(text));
+                }
+                break;
+            }
+        }
+        events
+    }
+
+    /// Flush anything still buffered at end of stream.
+    pub fn finish(&mut self) -> Vec<ModelResponse> {
+        let rest = std::mem::take(&mut self.buffer);
+        if self.in_call {
+            self.in_call = false;
+            // Models occasionally stop before writing the closing tag.
+            return vec![parse_call_body(&rest)];
+        }
+        if rest.is_empty() {
+            Vec::new()
+        } else {
+            vec![ModelResponse::Token(rest)]
+        }
+    }
+}
+
+/// Wrap a provider stream so `<tool_call>` blocks inside tokens become
+/// `ModelResponse::ToolCall` events.
+pub fn extract_tool_calls(inner: TokenStream) -> TokenStream {
+    struct State {
+        inner: TokenStream,
+        parser: ToolCallParser,
+        pending: VecDeque<Result<ModelResponse>>,
+        exhausted: bool,
+    }
+
+    let state = State {
+        inner,
+        parser: ToolCallParser::new(),
+        pending: VecDeque::new(),
+        exhausted: false,
+    };
+
+    let stream = futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop_front() {
+                return Some((event, state));
+            }
+            if state.exhausted {
+                return None;
+            }
+            match state.inner.next().await {
+                Some(Ok(ModelResponse::Token(text))) => {
+                    state
+                        .pending
+                        .extend(state.parser.push(&text).into_iter().map(Ok));
+                }
+                Some(Ok(ModelResponse::Done)) => {
+                    state
+                        .pending
+                        .extend(state.parser.finish().into_iter().map(Ok));
+                    state.pending.push_back(Ok(ModelResponse::Done));
+                }
+                Some(other) => state.pending.push_back(other),
+                None => {
+                    state
+                        .pending
+                        .extend(state.parser.finish().into_iter().map(Ok));
+                    state.exhausted = true;
+                }
+            }
+        }
+    });
+    Box::pin(stream)
+}
+
+fn parse_call_body(body: &str) -> ModelResponse {
+    let trimmed = strip_code_fence(body.trim());
+    match serde_json::from_str::<Value>(trimmed) {
+        Ok(Value::Object(mut obj)) => match obj.remove("name") {
+            Some(Value::String(name)) => {
+                let arguments = match obj.remove("arguments") {
+                    // Some models double-encode the arguments as a JSON string.
+                    Some(Value::String(s)) => serde_json::from_str(&s).unwrap_or(Value::String(s)),
+                    Some(v) => v,
+                    None => Value::Object(Default::default()),
+                };
+                ModelResponse::ToolCall(ToolCall {
+                    id: String::new(),
+                    name,
+                    arguments,
+                })
+            }
+            _ => malformed(body),
+        },
+        _ => malformed(body),
+    }
+}
+
+fn malformed(body: &str) -> ModelResponse {
+    warn!("Ignoring malformed tool call block: {}", body);
+    ModelResponse::Token(format!("{}{}{}", TOOL_CALL_OPEN, body, TOOL_CALL_CLOSE))
+}
+
+fn strip_code_fence(s: &str) -> &str {
+    let Some(rest) = s.strip_prefix("```") else {
+        return s;
+    };
+    let rest = rest.strip_prefix("json").unwrap_or(rest);
+    rest.strip_suffix("```").unwrap_or(rest).trim()
+}
+
+/// Length of the longest suffix of `buffer` that is a proper prefix of `tag`.
+fn partial_tag_suffix_len(buffer: &str, tag: &str) -> usize {
+    let max = buffer.len().min(tag.len() - 1);
+    (1..=max)
+        .rev()
+        .find(|&k| {
+            let start = buffer.len() - k;
+            buffer.is_char_boundary(start) && tag.starts_with(&buffer[start..])
+        })
+        .unwrap_or(0)
+}
@@ -0,0 +1,115 @@
+use openduo_agent::anthropic_provider::{AnthropicProvider, AnthropicStreamDecoder};
+use openduo_agent::line_stream::LineDecoder;
+use openduo_agent::prompt::PromptBuilder;
+use openduo_agent::provider::{ModelResponse, ToolCall, ToolDefinition, Usage};
+use openduo_core::config::{Config, ProviderKind};
+use serde_json::json;
+use serial_test::serial;
+
+fn anthropic_config() -> Config {
+    unsafe {
+        std::env::set_var("GITLAB_URL", "https://gitlab.example.com");
+        std::env::set_var("GITLAB_PAT", "glpat-test");
+    }
+    let mut config = Config::from_env().unwrap();
+    config.provider = ProviderKind::Anthropic;
+    config.llm_url = Some("https://llm-proxy.example.com/v1".to_string());
+    config.llm_model = Some("claude-sonnet".to_string());
+    config
+}
+
+#[test]
+#[serial]
+fn test_request_body_separates_system_and_maps_tool_results() {
+    let provider = AnthropicProvider::new(&anthropic_config()).unwrap();
+    let calls = vec![
+        ToolCall {
+            id: "toolu_1".to_string(),
+            name: "get_issue".to_string(),
+            arguments: json!({ "project_id": "g/p", "issue_iid": 1 }),
+        },
+        ToolCall {
+            id: "toolu_2".to_string(),
+            name: "get_issue".to_string(),
+            arguments: json!({ "project_id": "g/p", "issue_iid": 2 }),
+        },
+    ];
+    let mut history = PromptBuilder::build_initial("https://gitlab.example.com");
+    PromptBuilder::append_user(&mut history, "Compare issues 1 and 2");
+    PromptBuilder::append_tool_calls(&mut history, "Fetching both.", &calls);
+    PromptBuilder::append_tool_result(&mut history, &calls[0], "{}");
+    PromptBuilder::append_tool_result(&mut history, &calls[1], "{}");
+    let tools = vec![ToolDefinition {
+        name: "get_issue".to_string(),
+        description: "Get a specific issue by IID.".to_string(),
+        parameters: json!({ "type": "object" }),
+    }];
+
+    let body = provider.request_body(&history, &tools);
+    assert!(body["system"].as_str().unwrap().contains("OpenDuo"));
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[1]["role"], "assistant");
+    assert_eq!(messages[1]["content"][1]["type"], "tool_use");
+    assert_eq!(messages[1]["content"][1]["id"], "toolu_1");
+    // Both results are merged into a single user turn
+    assert_eq!(messages[2]["role"], "user");
+    assert_eq!(messages[2]["content"][0]["tool_use_id"], "toolu_1");
+    assert_eq!(messages[2]["content"][1]["tool_use_id"], "toolu_2");
+    assert_eq!(body["tools"][0]["input_schema"]["type"], "object");
+}
+
+#[test]
+fn test_decoder_assembles_tool_use_block() {
+    let mut decoder = AnthropicStreamDecoder::default();
+    let lines = [
+        "event: content_block_start",
+        r#"data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+        r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Checking."}}"#,
+        r#"data: {"type":"content_block_stop","index":0}"#,
+        r#"data: {"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_9","name":"get_project","input":{}}}"#,
+        r#"data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"project_"}}"#,
+        r#"data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"id\": \"g/p\"}"}}"#,
+        r#"data: {"type":"content_block_stop","index":1}"#,
+        r#"data: {"type":"message_delta","delta":{"stop_reason":"tool_use"}}"#,
+        r#"data: {"type":"message_stop"}"#,
+    ];
+    let events: Vec<ModelResponse> = lines
+        .iter()
+        .flat_map(|l| decoder.decode_line(l))
+        .map(|e| e.unwrap())
+        .collect();
+    assert_eq!(events.len(), 3);
+    assert!(matches!(&events[0], ModelResponse::Token(t) if t == "Checking."));
+    match &events[1] {
+        ModelResponse::ToolCall(tc) => {
+            assert_eq!(tc.id, "toolu_9");
+            assert_eq!(tc.name, "get_project");
+            assert_eq!(tc.arguments, json!({ "project_id": "g/p" }));
+        }
+        other => panic!("expected tool call, got {:?}", other),
+    }
+    assert!(matches!(events[2], ModelResponse::Done));
+}
+
+#[test]
+fn test_decoder_reports_usage() {
+    let mut decoder = AnthropicStreamDecoder::default();
+    let lines = [
+        r#"data: {"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":310,"output_tokens":1}}}"#,
+        r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#,
+        r#"data: {"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":15}}"#,
+        r#"data: {"type":"message_stop"}"#,
+    ];
+    let events: Vec<ModelResponse> = lines
+        .iter()
+        .flat_map(|l| decoder.decode_line(l))
+        .map(|e| e.unwrap())
+        .collect();
+    let usage = Usage {
+        prompt_tokens: 310,
+        completion_tokens: 15,
+    };
+    assert!(matches!(&events[1], ModelResponse::Usage(u) if *u == usage));
+    assert!(matches!(events[2], ModelResponse::Done));
+}
@@ -1,4 +1,5 @@
-use openduo_agent::gitlab_provider::GitLabAiProvider;
+use openduo_agent::gitlab_provider::{render_transcript, GitLabAiProvider};
+use openduo_agent::provider::{ChatMessage, ChatRole};
 use openduo_core::config::Config;
 use serial_test::serial;
 
@@ -12,3 +13,26 @@ fn test_provider_constructs() {
     let config = Config::from_env().unwrap();
     let _provider = GitLabAiProvider::new(&config).unwrap();
 }
+
+#[test]
+fn test_transcript_includes_every_role() {
+    let messages = vec![
+        ChatMessage::new(ChatRole::System, "You are OpenDuo."),
+        ChatMessage::new(ChatRole::User, "List open issues in g/p"),
+        ChatMessage::new(ChatRole::Assistant, "[Using tool: list_issues]"),
+        ChatMessage::new(ChatRole::Tool, "Tool `list_issues` returned:\n[]"),
+        ChatMessage::new(ChatRole::User, "now close the second one"),
+    ];
+    let rendered = render_transcript(&messages, &[]);
+    assert!(rendered.starts_with("You are OpenDuo."));
+    let user = rendered.find("List open issues in g/p").unwrap();
+    let tool = rendered.find("Tool `list_issues` returned").unwrap();
+    let follow_up = rendered.find("now close the second one").unwrap();
+    assert!(user < tool && tool < follow_up);
+}
+
+#[test]
+fn test_transcript_sends_lone_user_message_verbatim() {
+    let messages = vec![ChatMessage::new(ChatRole::User, "hello")];
+    assert_eq!(render_transcript(&messages, &[]), "hello");
+}
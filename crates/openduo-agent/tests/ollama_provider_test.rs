@@ -0,0 +1,75 @@
+use openduo_agent::line_stream::LineDecoder;
+use openduo_agent::ollama_provider::{OllamaProvider, OllamaStreamDecoder};
+use openduo_agent::provider::{ChatMessage, ChatRole, ModelResponse, ToolDefinition, Usage};
+use openduo_core::config::{Config, ProviderKind};
+use serde_json::json;
+use serial_test::serial;
+
+fn ollama_config() -> Config {
+    unsafe {
+        std::env::set_var("GITLAB_URL", "https://gitlab.example.com");
+        std::env::set_var("GITLAB_PAT", "glpat-test");
+    }
+    let mut config = Config::from_env().unwrap();
+    config.provider = ProviderKind::Ollama;
+    config.llm_model = Some("llama3.1".to_string());
+    config
+}
+
+#[test]
+#[serial]
+fn test_request_body_includes_tools() {
+    let provider = OllamaProvider::new(&ollama_config()).unwrap();
+    let messages = vec![ChatMessage::new(ChatRole::User, "list my projects")];
+    let tools = vec![ToolDefinition {
+        name: "list_projects".to_string(),
+        description: "List projects the current user is a member of.".to_string(),
+        parameters: json!({ "type": "object", "properties": {} }),
+    }];
+    let body = provider.request_body(&messages, &tools);
+    assert_eq!(body["model"], "llama3.1");
+    assert_eq!(body["tools"][0]["function"]["name"], "list_projects");
+}
+
+#[test]
+fn test_decoder_reads_ndjson_tool_calls() {
+    let mut decoder = OllamaStreamDecoder;
+    let lines = [
+        r#"{"model":"llama3.1","message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"get_mr","arguments":{"project_id":"g/p","mr_iid":7}}}]},"done":false}"#,
+        r#"{"model":"llama3.1","message":{"role":"assistant","content":""},"done":true,"done_reason":"stop"}"#,
+    ];
+    let events: Vec<ModelResponse> = lines
+        .iter()
+        .flat_map(|l| decoder.decode_line(l))
+        .map(|e| e.unwrap())
+        .collect();
+    match &events[0] {
+        ModelResponse::ToolCall(tc) => {
+            assert_eq!(tc.name, "get_mr");
+            assert_eq!(tc.arguments, json!({ "project_id": "g/p", "mr_iid": 7 }));
+        }
+        other => panic!("expected tool call, got {:?}", other),
+    }
+    assert!(matches!(events[1], ModelResponse::Done));
+}
+
+#[test]
+fn test_decoder_reports_usage_on_final_chunk() {
+    let mut decoder = OllamaStreamDecoder;
+    let events = decoder.decode_line(
+        r#"{"message":{"role":"assistant","content":""},"done":true,"prompt_eval_count":42,"eval_count":7}"#,
+    );
+    let usage = Usage {
+        prompt_tokens: 42,
+        completion_tokens: 7,
+    };
+    assert!(matches!(&events[0], Ok(ModelResponse::Usage(u)) if *u == usage));
+    assert!(matches!(events[1], Ok(ModelResponse::Done)));
+}
+
+#[test]
+fn test_decoder_surfaces_errors() {
+    let mut decoder = OllamaStreamDecoder;
+    let events = decoder.decode_line(r#"{"error":"model 'llama3.1' not found"}"#);
+    assert!(events[0].is_err());
+}
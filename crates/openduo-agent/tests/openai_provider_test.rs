@@ -0,0 +1,134 @@
+use openduo_agent::line_stream::LineDecoder;
+use openduo_agent::openai_provider::{OpenAiProvider, OpenAiStreamDecoder};
+use openduo_agent::prompt::PromptBuilder;
+use openduo_agent::provider::{
+    ChatMessage, ChatRole, ModelResponse, ToolCall, ToolDefinition, Usage,
+};
+use openduo_core::config::{Config, ProviderKind};
+use serde_json::json;
+use serial_test::serial;
+
+fn openai_config() -> Config {
+    unsafe {
+        std::env::set_var("GITLAB_URL", "https://gitlab.example.com");
+        std::env::set_var("GITLAB_PAT", "glpat-test");
+    }
+    let mut config = Config::from_env().unwrap();
+    config.provider = ProviderKind::OpenAi;
+    config.llm_url = Some("http://localhost:8000/v1/".to_string());
+    config.llm_model = Some("qwen2.5-coder".to_string());
+    config
+}
+
+#[test]
+#[serial]
+fn test_provider_requires_llm_url() {
+    let mut config = openai_config();
+    config.llm_url = None;
+    assert!(OpenAiProvider::new(&config).is_err());
+}
+
+#[test]
+#[serial]
+fn test_request_body_includes_function_tools() {
+    let provider = OpenAiProvider::new(&openai_config()).unwrap();
+    let messages = vec![ChatMessage::new(ChatRole::User, "hi")];
+    let tools = vec![ToolDefinition {
+        name: "get_project".to_string(),
+        description: "Get details of a GitLab project.".to_string(),
+        parameters: json!({ "type": "object" }),
+    }];
+    let body = provider.request_body(&messages, &tools);
+    assert_eq!(body["model"], "qwen2.5-coder");
+    assert_eq!(body["stream"], true);
+    assert_eq!(body["stream_options"]["include_usage"], true);
+    assert_eq!(body["messages"][0]["role"], "user");
+    assert_eq!(body["tools"][0]["type"], "function");
+    assert_eq!(body["tools"][0]["function"]["name"], "get_project");
+}
+
+#[test]
+fn test_decoder_assembles_tool_call_deltas() {
+    let mut decoder = OpenAiStreamDecoder::default();
+    let lines = [
+        r#"data: {"choices":[{"delta":{"role":"assistant","tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"get_issue","arguments":""}}]}}]}"#,
+        r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"project_id\":"}}]}}]}"#,
+        r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"g/p\",\"issue_iid\":3}"}}]}}]}"#,
+        r#"data: {"choices":[{"delta":{},"finish_reason":"tool_calls"}]}"#,
+        "data: [DONE]",
+    ];
+    let events: Vec<ModelResponse> = lines
+        .iter()
+        .flat_map(|l| decoder.decode_line(l))
+        .map(|e| e.unwrap())
+        .collect();
+    assert_eq!(events.len(), 2);
+    match &events[0] {
+        ModelResponse::ToolCall(tc) => {
+            assert_eq!(tc.name, "get_issue");
+            assert_eq!(tc.arguments, json!({ "project_id": "g/p", "issue_iid": 3 }));
+        }
+        other => panic!("expected tool call, got {:?}", other),
+    }
+    assert!(matches!(events[1], ModelResponse::Done));
+}
+
+#[test]
+fn test_decoder_streams_content_tokens() {
+    let mut decoder = OpenAiStreamDecoder::default();
+    let events = decoder.decode_line(r#"data: {"choices":[{"delta":{"content":"Hello"}}]}"#);
+    assert!(matches!(&events[0], Ok(ModelResponse::Token(t)) if t == "Hello"));
+}
+
+#[test]
+fn test_decoder_reports_usage_before_done() {
+    let mut decoder = OpenAiStreamDecoder::default();
+    // With `include_usage`, usage follows the finish_reason chunk.
+    let lines = [
+        r#"data: {"choices":[{"delta":{"content":"Hi"},"finish_reason":null}],"usage":null}"#,
+        r#"data: {"choices":[{"delta":{},"finish_reason":"stop"}],"usage":null}"#,
+        r#"data: {"choices":[],"usage":{"prompt_tokens":50,"completion_tokens":9,"total_tokens":59}}"#,
+        "data: [DONE]",
+    ];
+    let mut events: Vec<_> = lines.iter().flat_map(|l| decoder.decode_line(l)).collect();
+    events.extend(decoder.finish());
+    let events: Vec<ModelResponse> = events.into_iter().map(|e| e.unwrap()).collect();
+    let usage = Usage {
+        prompt_tokens: 50,
+        completion_tokens: 9,
+    };
+    assert_eq!(events.len(), 3, "{events:?}");
+    assert!(matches!(&events[0], ModelResponse::Token(t) if t == "Hi"));
+    assert!(matches!(&events[1], ModelResponse::Usage(u) if *u == usage));
+    assert!(matches!(events[2], ModelResponse::Done));
+}
+
+#[test]
+fn test_decoder_finishes_when_done_marker_is_missing() {
+    let mut decoder = OpenAiStreamDecoder::default();
+    let events = decoder.decode_line(r#"data: {"choices":[{"delta":{},"finish_reason":"stop"}]}"#);
+    assert!(events.is_empty());
+    assert!(matches!(decoder.finish()[..], [Ok(ModelResponse::Done)]));
+}
+
+#[test]
+#[serial]
+fn test_request_body_pairs_tool_results_with_calls() {
+    let provider = OpenAiProvider::new(&openai_config()).unwrap();
+    let call = ToolCall {
+        id: "call_abc".to_string(),
+        name: "get_current_user".to_string(),
+        arguments: json!({}),
+    };
+    let mut history = vec![ChatMessage::new(ChatRole::User, "who am I?")];
+    PromptBuilder::append_tool_calls(&mut history, "", std::slice::from_ref(&call));
+    PromptBuilder::append_tool_result(&mut history, &call, "{\"username\":\"jdoe\"}");
+    let body = provider.request_body(&history, &[]);
+    assert_eq!(body["messages"][1]["tool_calls"][0]["id"], "call_abc");
+    assert_eq!(
+        body["messages"][1]["tool_calls"][0]["function"]["arguments"],
+        "{}"
+    );
+    assert_eq!(body["messages"][2]["role"], "tool");
+    assert_eq!(body["messages"][2]["tool_call_id"], "call_abc");
+}
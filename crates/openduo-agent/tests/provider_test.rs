@@ -2,11 +2,18 @@ use openduo_agent::provider::{ChatMessage, ChatRole};
 
 #[test]
 fn test_chat_message_serializes() {
-    let msg = ChatMessage {
-        role: ChatRole::User,
-        content: "hello".to_string(),
-    };
+    let msg = ChatMessage::new(ChatRole::User, "hello");
     let json = serde_json::to_string(&msg).unwrap();
     assert!(json.contains("\"user\""));
     assert!(json.contains("\"hello\""));
 }
+
+#[test]
+fn test_tool_result_message_round_trips_call_id() {
+    let mut msg = ChatMessage::new(ChatRole::Tool, "ok");
+    msg.tool_call_id = Some("toolu_01".to_string());
+    let json = serde_json::to_string(&msg).unwrap();
+    let back: ChatMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.tool_call_id.as_deref(), Some("toolu_01"));
+    assert!(back.tool_calls.is_empty());
+}
@@ -1,6 +1,551 @@
-use openduo_agent::react_loop::ReactLoop;
+use openduo_agent::approval::Decision;
+use openduo_agent::prompt::PromptBuilder;
+use openduo_agent::provider::{ChatRole, LlmProvider, ModelResponse, Usage};
+use openduo_agent::react_loop::{AgentEvent, Cancelled, ReactLoop, CANCELLED_NOTE};
+use openduo_agent::testing::{FakeTool, ScriptedApprover, ScriptedProvider, ScriptedTurn};
+use openduo_tools::registry::RiskLevel;
+use openduo_tools::registry::ToolRegistry;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 #[test]
 fn test_react_loop_constructs_with_max_iterations() {
     let _loop_runner = ReactLoop::new(10);
 }
+
+fn run_parts(
+    turns: Vec<ScriptedTurn>,
+    tools: Vec<FakeTool>,
+) -> (Arc<ScriptedProvider>, Arc<dyn LlmProvider>, ToolRegistry) {
+    let scripted = Arc::new(ScriptedProvider::new(turns));
+    let provider: Arc<dyn LlmProvider> = scripted.clone();
+    let mut registry = ToolRegistry::empty();
+    for tool in tools {
+        registry.register(Box::new(tool));
+    }
+    (scripted, provider, registry)
+}
+
+#[tokio::test]
+async fn test_plain_answer_streams_tokens_and_records_history() {
+    let (scripted, provider, registry) =
+        run_parts(vec![ScriptedTurn::text("You have 3 open issues.")], vec![]);
+    let mut history = PromptBuilder::build_initial("https://gitlab.example.com");
+    let tokens = Mutex::new(Vec::new());
+
+    let answer = ReactLoop::new(5)
+        .run(
+            "How many issues?",
+            &mut history,
+            &provider,
+            &registry,
+            |e| {
+                if let AgentEvent::Token(t) = e {
+                    tokens.lock().unwrap().push(t)
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(answer, "You have 3 open issues.");
+    assert_eq!(*tokens.lock().unwrap(), vec!["You have 3 open issues."]);
+    assert_eq!(history.len(), 3);
+    assert!(matches!(history[1].role, ChatRole::User));
+    assert!(matches!(history[2].role, ChatRole::Assistant));
+    assert_eq!(scripted.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn test_multi_step_tool_chain_feeds_observations_back() {
+    let issues = FakeTool::new("list_issues", r#"[{"iid": 1}, {"iid": 2}]"#);
+    let issue_calls = issues.invocations();
+    let close = FakeTool::new("close_issue", r#"{"iid": 2, "state": "closed"}"#);
+    let close_calls = close.invocations();
+    let (scripted, provider, registry) = run_parts(
+        vec![
+            ScriptedTurn::tool_call("list_issues", json!({ "project_id": "g/p" })),
+            ScriptedTurn::tool_call(
+                "close_issue",
+                json!({ "project_id": "g/p", "issue_iid": 2 }),
+            ),
+            ScriptedTurn::text("Closed issue #2."),
+        ],
+        vec![issues, close],
+    );
+    let mut history = PromptBuilder::build_initial("https://gitlab.example.com");
+
+    let answer = ReactLoop::new(5)
+        .run(
+            "Close the second issue",
+            &mut history,
+            &provider,
+            &registry,
+            |_| {},
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(answer, "Closed issue #2.");
+    assert_eq!(
+        *issue_calls.lock().unwrap(),
+        vec![json!({ "project_id": "g/p" })]
+    );
+    assert_eq!(close_calls.lock().unwrap()[0]["issue_iid"], 2);
+
+    // system, user, (assistant call, tool result) x2, final assistant
+    assert_eq!(history.len(), 7);
+    let call_msg = &history[2];
+    assert!(matches!(call_msg.role, ChatRole::Assistant));
+    assert_eq!(call_msg.tool_calls[0].name, "list_issues");
+    assert!(!call_msg.tool_calls[0].id.is_empty());
+    let result_msg = &history[3];
+    assert!(matches!(result_msg.role, ChatRole::Tool));
+    assert_eq!(
+        result_msg.tool_call_id.as_deref(),
+        Some(call_msg.tool_calls[0].id.as_str())
+    );
+
+    // The second request already carries the first observation
+    let requests = scripted.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[1].len(), 4);
+    assert!(requests[1][3].content.contains(r#""iid": 1"#));
+    assert_eq!(scripted.remaining(), 0);
+}
+
+#[tokio::test]
+async fn test_generated_call_ids_differ_across_turns() {
+    let (_, provider, registry) = run_parts(
+        vec![
+            ScriptedTurn::tool_call("list_issues", json!({})),
+            ScriptedTurn::text("One issue."),
+            ScriptedTurn::tool_call("list_issues", json!({})),
+            ScriptedTurn::text("Still one."),
+        ],
+        vec![FakeTool::new("list_issues", "[]")],
+    );
+    let mut history = Vec::new();
+    for message in ["Any issues?", "And now?"] {
+        ReactLoop::new(5)
+            .run(message, &mut history, &provider, &registry, |_| {})
+            .await
+            .unwrap();
+    }
+    let ids: Vec<&str> = history
+        .iter()
+        .flat_map(|m| &m.tool_calls)
+        .map(|c| c.id.as_str())
+        .collect();
+    assert_eq!(ids.len(), 2);
+    assert_ne!(ids[0], ids[1]);
+}
+
+#[tokio::test]
+async fn test_tool_failure_becomes_observation() {
+    let (_, provider, registry) = run_parts(
+        vec![
+            ScriptedTurn::tool_call("get_project", json!({ "project_id": "missing" })),
+            ScriptedTurn::text("That project does not exist."),
+        ],
+        vec![FakeTool::failing("get_project", "404 Not Found")],
+    );
+    let mut history = Vec::new();
+
+    ReactLoop::new(5)
+        .run(
+            "Show project missing",
+            &mut history,
+            &provider,
+            &registry,
+            |_| {},
+        )
+        .await
+        .unwrap();
+
+    let observation = &history[2];
+    assert!(matches!(observation.role, ChatRole::Tool));
+    assert!(observation.content.contains("Tool error: 404 Not Found"));
+}
+
+#[tokio::test]
+async fn test_unknown_tool_is_reported_to_model() {
+    let (_, provider, registry) = run_parts(
+        vec![
+            ScriptedTurn::tool_call("drop_database", json!({})),
+            ScriptedTurn::text("I cannot do that."),
+        ],
+        vec![],
+    );
+    let mut history = Vec::new();
+
+    ReactLoop::new(5)
+        .run("Drop it", &mut history, &provider, &registry, |_| {})
+        .await
+        .unwrap();
+
+    assert!(history[2].content.contains("Unknown tool: drop_database"));
+}
+
+#[tokio::test]
+async fn test_iteration_limit_stops_tool_loop() {
+    let tool = FakeTool::new("list_pipelines", "[]");
+    let calls = tool.invocations();
+    let (scripted, provider, registry) = run_parts(
+        (0..5)
+            .map(|_| ScriptedTurn::tool_call("list_pipelines", json!({ "project_id": "g/p" })))
+            .collect(),
+        vec![tool],
+    );
+    let mut history = Vec::new();
+    let tokens = Mutex::new(String::new());
+
+    let answer = ReactLoop::new(3)
+        .run("Loop forever", &mut history, &provider, &registry, |e| {
+            if let AgentEvent::Token(t) = e {
+                tokens.lock().unwrap().push_str(&t)
+            }
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(calls.lock().unwrap().len(), 3);
+    assert_eq!(scripted.remaining(), 2);
+    assert!(answer.contains("maximum number of reasoning steps"));
+    assert_eq!(*tokens.lock().unwrap(), answer);
+    assert_eq!(history.last().unwrap().content, answer);
+}
+
+#[tokio::test]
+async fn test_events_report_iterations_tool_calls_and_usage() {
+    let (_, provider, registry) = run_parts(
+        vec![
+            ScriptedTurn::tool_calls(vec![
+                ("list_issues".to_string(), json!({ "project_id": "g/p" })),
+                (
+                    "get_project".to_string(),
+                    json!({ "project_id": "missing" }),
+                ),
+            ]),
+            ScriptedTurn::Events(vec![
+                Ok(ModelResponse::Token("Done.".to_string())),
+                Ok(ModelResponse::Usage(Usage {
+                    prompt_tokens: 120,
+                    completion_tokens: 3,
+                })),
+                Ok(ModelResponse::Done),
+            ]),
+        ],
+        vec![
+            FakeTool::new("list_issues", "[]"),
+            FakeTool::failing("get_project", "404 Not Found"),
+        ],
+    );
+    let events = Mutex::new(Vec::new());
+
+    ReactLoop::new(5)
+        .run("Check", &mut Vec::new(), &provider, &registry, |e| {
+            events.lock().unwrap().push(e)
+        })
+        .await
+        .unwrap();
+
+    let events = events.into_inner().unwrap();
+    let kinds: Vec<String> = events
+        .iter()
+        .map(|e| match e {
+            AgentEvent::Iteration(n) => format!("iteration {n}"),
+            AgentEvent::Token(t) => format!("token {t}"),
+            AgentEvent::ToolCallStarted { name, .. } => format!("started {name}"),
+            AgentEvent::ToolCallFinished { name, .. } => format!("finished {name}"),
+            AgentEvent::Usage(u) => format!("usage {}", u.prompt_tokens),
+            AgentEvent::ApprovalRequested { request, .. } => format!("approval {}", request.tool),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            "iteration 1",
+            "started list_issues",
+            "finished list_issues",
+            "started get_project",
+            "finished get_project",
+            "iteration 2",
+            "token Done.",
+            "usage 120",
+        ]
+    );
+    let AgentEvent::ToolCallStarted { id, arguments, .. } = &events[1] else {
+        unreachable!()
+    };
+    assert!(!id.is_empty());
+    assert_eq!(arguments["project_id"], "g/p");
+    let AgentEvent::ToolCallFinished {
+        result_bytes,
+        error,
+        ..
+    } = &events[2]
+    else {
+        unreachable!()
+    };
+    assert_eq!(*result_bytes, 2);
+    assert!(error.is_none());
+    let AgentEvent::ToolCallFinished { error, .. } = &events[4] else {
+        unreachable!()
+    };
+    assert!(error.as_deref().unwrap().contains("404 Not Found"));
+}
+
+type Invocations = Arc<Mutex<Vec<serde_json::Value>>>;
+
+/// A turn that lists issues, closes issue 2 and answers.
+fn close_issue_turn(
+    approver: ScriptedApprover,
+) -> (Invocations, ReactLoop, Vec<ScriptedTurn>, Vec<FakeTool>) {
+    let list = FakeTool::new("list_issues", "[]");
+    let close = FakeTool::new("close_issue", "closed").with_risk(RiskLevel::Destructive);
+    let calls = close.invocations();
+    let turns = vec![
+        ScriptedTurn::tool_calls(vec![
+            ("list_issues".to_string(), json!({ "project_id": "g/p" })),
+            (
+                "close_issue".to_string(),
+                json!({ "project_id": "g/p", "issue_iid": 2 }),
+            ),
+        ]),
+        ScriptedTurn::text("Done."),
+    ];
+    let react_loop = ReactLoop::new(5).with_approver(Arc::new(approver));
+    (calls, react_loop, turns, vec![list, close])
+}
+
+#[tokio::test]
+async fn test_approval_asked_only_for_non_read_tools() {
+    let approver = ScriptedApprover::new(vec![Decision::Approve]);
+    let asked = approver.requests();
+    let (calls, react_loop, turns, tools) = close_issue_turn(approver);
+    let (_, provider, registry) = run_parts(turns, tools);
+    let events = Mutex::new(Vec::new());
+
+    react_loop
+        .run("Close #2", &mut Vec::new(), &provider, &registry, |e| {
+            if let AgentEvent::ApprovalRequested { approval_id, .. } = e {
+                events.lock().unwrap().push(approval_id)
+            }
+        })
+        .await
+        .unwrap();
+
+    let asked = asked.lock().unwrap();
+    assert_eq!(asked.len(), 1);
+    assert_eq!(asked[0].tool, "close_issue");
+    assert_eq!(asked[0].risk, RiskLevel::Destructive);
+    assert_eq!(asked[0].requests[0].url, "fake://close_issue");
+    assert_eq!(*events.lock().unwrap(), vec!["approval-1"]);
+    assert_eq!(calls.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_rejected_call_is_not_run() {
+    let approver = ScriptedApprover::new(vec![Decision::Reject {
+        reason: Some("wrong issue".to_string()),
+    }]);
+    let (calls, react_loop, turns, tools) = close_issue_turn(approver);
+    let (scripted, provider, registry) = run_parts(turns, tools);
+    let mut history = Vec::new();
+    let tool_events = Mutex::new(Vec::new());
+
+    react_loop
+        .run(
+            "Close #2",
+            &mut history,
+            &provider,
+            &registry,
+            |e| match e {
+                AgentEvent::ToolCallStarted { name, .. }
+                | AgentEvent::ToolCallFinished { name, .. } => {
+                    tool_events.lock().unwrap().push(name)
+                }
+                _ => {}
+            },
+        )
+        .await
+        .unwrap();
+
+    assert!(calls.lock().unwrap().is_empty());
+    // Only list_issues ran; the rejected call never started.
+    assert_eq!(*tool_events.lock().unwrap(), ["list_issues", "list_issues"]);
+    let requests = scripted.requests();
+    let observation = &requests[1].last().unwrap().content;
+    assert!(observation.contains("rejected"), "{observation}");
+    assert!(observation.contains("wrong issue"), "{observation}");
+}
+
+#[tokio::test]
+async fn test_edited_arguments_are_run_and_recorded() {
+    let edited = json!({ "project_id": "g/p", "issue_iid": 3 });
+    let approver = ScriptedApprover::new(vec![Decision::Edit(edited.clone())]);
+    let (calls, react_loop, turns, tools) = close_issue_turn(approver);
+    let (_, provider, registry) = run_parts(turns, tools);
+    let mut history = Vec::new();
+
+    react_loop
+        .run("Close #2", &mut history, &provider, &registry, |_| {})
+        .await
+        .unwrap();
+
+    assert_eq!(*calls.lock().unwrap(), vec![edited.clone()]);
+    assert_eq!(history[1].tool_calls[1].arguments, edited);
+}
+
+#[tokio::test]
+async fn test_always_allowed_tool_skips_approval() {
+    let approver = ScriptedApprover::default().always_allowing("close_issue");
+    let asked = approver.requests();
+    let (calls, react_loop, turns, tools) = close_issue_turn(approver);
+    let (_, provider, registry) = run_parts(turns, tools);
+
+    react_loop
+        .run("Close #2", &mut Vec::new(), &provider, &registry, |_| {})
+        .await
+        .unwrap();
+
+    assert!(asked.lock().unwrap().is_empty());
+    assert_eq!(calls.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_provider_errors_propagate() {
+    let (_, provider, registry) = run_parts(
+        vec![ScriptedTurn::Fail("connection refused".to_string())],
+        vec![],
+    );
+    let mut history = Vec::new();
+    let err = ReactLoop::new(3)
+        .run("hello", &mut history, &provider, &registry, |_| {})
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("connection refused"));
+}
+
+#[tokio::test]
+async fn test_stream_errors_propagate() {
+    let (_, provider, registry) = run_parts(
+        vec![ScriptedTurn::Events(vec![
+            Ok(ModelResponse::Token("partial".to_string())),
+            Err("stream reset".to_string()),
+        ])],
+        vec![],
+    );
+    let mut history = Vec::new();
+    let err = ReactLoop::new(3)
+        .run("hello", &mut history, &provider, &registry, |_| {})
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("stream reset"));
+}
+
+/// Cancel `token` once `ready` reports true.
+fn cancel_when(token: &CancellationToken, ready: impl Fn() -> bool + Send + 'static) {
+    let token = token.clone();
+    tokio::spawn(async move {
+        while !ready() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        token.cancel();
+    });
+}
+
+#[tokio::test]
+async fn test_cancel_during_model_stream_keeps_partial_answer() {
+    let (_, provider, registry) = run_parts(
+        vec![ScriptedTurn::Stall(vec![ModelResponse::Token(
+            "There are".to_string(),
+        )])],
+        vec![],
+    );
+    let mut history = Vec::new();
+    let tokens = Arc::new(Mutex::new(Vec::new()));
+    let cancel = CancellationToken::new();
+    let seen = tokens.clone();
+    cancel_when(&cancel, move || !seen.lock().unwrap().is_empty());
+
+    let err = ReactLoop::new(3)
+        .with_cancellation(cancel)
+        .run("How many?", &mut history, &provider, &registry, |e| {
+            if let AgentEvent::Token(t) = e {
+                tokens.lock().unwrap().push(t)
+            }
+        })
+        .await
+        .unwrap_err();
+
+    assert!(err.is::<Cancelled>());
+    assert_eq!(history.len(), 2);
+    assert!(matches!(history[1].role, ChatRole::Assistant));
+    assert_eq!(
+        history[1].content,
+        format!("There are\n\n{}", CANCELLED_NOTE)
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_during_tool_call_answers_every_call() {
+    let slow = FakeTool::hanging("list_jobs");
+    let started = slow.invocations();
+    let never = FakeTool::new("retry_job", "{}");
+    let never_calls = never.invocations();
+    let (scripted, provider, registry) = run_parts(
+        vec![
+            ScriptedTurn::tool_calls(vec![
+                ("list_jobs".to_string(), json!({})),
+                ("retry_job".to_string(), json!({ "job_id": 1 })),
+            ]),
+            ScriptedTurn::text("unused"),
+        ],
+        vec![slow, never],
+    );
+    let mut history = Vec::new();
+    let cancel = CancellationToken::new();
+    cancel_when(&cancel, move || !started.lock().unwrap().is_empty());
+
+    let err = ReactLoop::new(3)
+        .with_cancellation(cancel)
+        .run("Retry the job", &mut history, &provider, &registry, |_| {})
+        .await
+        .unwrap_err();
+
+    assert!(err.is::<Cancelled>());
+    assert!(never_calls.lock().unwrap().is_empty());
+    assert_eq!(scripted.remaining(), 1);
+    // user, assistant tool calls, two tool results, closing assistant note
+    assert_eq!(history.len(), 5);
+    assert_eq!(history[1].tool_calls.len(), 2);
+    for (msg, call) in history[2..4].iter().zip(&history[1].tool_calls) {
+        assert!(matches!(msg.role, ChatRole::Tool));
+        assert_eq!(msg.tool_call_id.as_deref(), Some(call.id.as_str()));
+        assert!(msg.content.contains("Cancelled"));
+    }
+    // list_jobs was already sent; retry_job never was.
+    assert!(history[2].content.contains("may already have been applied"));
+    assert!(history[3].content.contains("not run"));
+    assert_eq!(history[4].content, CANCELLED_NOTE);
+}
+
+#[tokio::test]
+async fn test_cancelled_token_stops_before_calling_the_model() {
+    let (scripted, provider, registry) = run_parts(vec![ScriptedTurn::text("hi")], vec![]);
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    let mut history = Vec::new();
+    let err = ReactLoop::new(3)
+        .with_cancellation(cancel)
+        .run("hello", &mut history, &provider, &registry, |_| {})
+        .await
+        .unwrap_err();
+    assert!(err.is::<Cancelled>());
+    assert!(scripted.requests().is_empty());
+    assert_eq!(history.len(), 2);
+}
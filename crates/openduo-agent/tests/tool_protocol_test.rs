@@ -0,0 +1,86 @@
+use futures::StreamExt;
+use openduo_agent::provider::{ModelResponse, TokenStream, ToolDefinition};
+use openduo_agent::tool_protocol::{extract_tool_calls, render_tool_instructions, ToolCallParser};
+use serde_json::json;
+
+fn collect(events: Vec<ModelResponse>) -> (String, Vec<(String, serde_json::Value)>) {
+    let mut text = String::new();
+    let mut calls = Vec::new();
+    for e in events {
+        match e {
+            ModelResponse::Token(t) => text.push_str(&t),
+            ModelResponse::ToolCall(tc) => calls.push((tc.name, tc.arguments)),
+            ModelResponse::Usage(_) | ModelResponse::Done => {}
+        }
+    }
+    (text, calls)
+}
+
+#[test]
+fn test_parser_extracts_call_split_across_chunks() {
+    let mut parser = ToolCallParser::new();
+    let mut events = Vec::new();
+    for chunk in [
+        "Let me check. <tool",
+        "_call>{\"name\": \"list_issues\", ",
+        "\"arguments\": {\"project_id\": \"g/p\"}}</tool_",
+        "call>",
+    ] {
+        events.extend(parser.push(chunk));
+    }
+    events.extend(parser.finish());
+    let (text, calls) = collect(events);
+    assert_eq!(text, "Let me check. ");
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].0, "list_issues");
+    assert_eq!(calls[0].1, json!({ "project_id": "g/p" }));
+}
+
+#[test]
+fn test_parser_passes_plain_text_through() {
+    let mut parser = ToolCallParser::new();
+    let mut events = parser.push("a < b and <tool");
+    events.extend(parser.push(" is not a tag"));
+    events.extend(parser.finish());
+    let (text, calls) = collect(events);
+    assert_eq!(text, "a < b and <tool is not a tag");
+    assert!(calls.is_empty());
+}
+
+#[test]
+fn test_parser_surfaces_malformed_block_as_text() {
+    let mut parser = ToolCallParser::new();
+    let events = parser.push("<tool_call>not json</tool_call>");
+    let (text, calls) = collect(events);
+    assert!(calls.is_empty());
+    assert!(text.contains("not json"));
+}
+
+#[tokio::test]
+async fn test_extract_tool_calls_wraps_stream() {
+    let inner: TokenStream = Box::pin(futures::stream::iter(vec![
+        Ok(ModelResponse::Token(
+            "<tool_call>{\"name\": \"get_current_user\"}".into(),
+        )),
+        Ok(ModelResponse::Token("</tool_call>".into())),
+        Ok(ModelResponse::Done),
+    ]));
+    let events: Vec<ModelResponse> = extract_tool_calls(inner)
+        .map(|e| e.unwrap())
+        .collect()
+        .await;
+    assert!(matches!(&events[0], ModelResponse::ToolCall(tc) if tc.name == "get_current_user"));
+    assert!(matches!(events[1], ModelResponse::Done));
+}
+
+#[test]
+fn test_render_tool_instructions_lists_tools() {
+    let tools = vec![ToolDefinition {
+        name: "get_issue".into(),
+        description: "Get a specific issue by IID.".into(),
+        parameters: json!({ "type": "object" }),
+    }];
+    let rendered = render_tool_instructions(&tools);
+    assert!(rendered.contains("<tool_call>"));
+    assert!(rendered.contains("- get_issue: Get a specific issue by IID."));
+}
@@ -0,0 +1,257 @@
+//! Optional in-memory cache for `GitLabClient::get`. Entries are fresh for a
+//! per-resource TTL, then revalidated with `If-None-Match` when GitLab sent
+//! an `ETag`. Writes to a project drop that project's entries.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Resource kinds that `cache.ttls` may name, with their default TTL in
+/// seconds. Paths matching none use `CacheSettings::default_ttl`.
+pub const CACHE_RESOURCES: &[(&str, u64)] = &[
+    ("projects", 300),
+    ("users", 300),
+    ("files", 60),
+    ("issues", 30),
+    ("merge_requests", 30),
+    ("pipelines", 10),
+];
+
+#[derive(Debug, Clone)]
+pub struct CacheSettings {
+    pub enabled: bool,
+    pub max_entries: usize,
+    pub default_ttl: Duration,
+    /// TTL per `CACHE_RESOURCES` kind.
+    pub ttls: BTreeMap<String, Duration>,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_entries: 1000,
+            default_ttl: Duration::from_secs(30),
+            ttls: CACHE_RESOURCES
+                .iter()
+                .map(|(kind, secs)| (kind.to_string(), Duration::from_secs(*secs)))
+                .collect(),
+        }
+    }
+}
+
+impl CacheSettings {
+    fn ttl_for(&self, path: &str) -> Duration {
+        resource_kind(path)
+            .and_then(|kind| self.ttls.get(kind))
+            .copied()
+            .unwrap_or(self.default_ttl)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct CacheStats {
+    pub entries: usize,
+    /// Served without a request.
+    pub hits: u64,
+    /// Needed a request, including revalidations.
+    pub misses: u64,
+    /// Stale entries GitLab confirmed unchanged with a 304.
+    pub revalidations: u64,
+    /// Entries dropped because a write touched their project.
+    pub invalidations: u64,
+}
+
+/// What `ResponseCache::lookup` found for a path.
+pub enum Lookup {
+    Fresh(Vec<u8>),
+    /// Expired, but GitLab can confirm it is unchanged via this `ETag`.
+    Stale {
+        etag: String,
+    },
+    Miss,
+}
+
+struct Entry {
+    body: Vec<u8>,
+    etag: Option<String>,
+    project: Option<String>,
+    stored_at: Instant,
+    fresh_until: Instant,
+}
+
+pub struct ResponseCache {
+    settings: CacheSettings,
+    entries: Mutex<HashMap<String, Entry>>,
+    /// Project path → numeric id, learned from `projects/:id` responses, so
+    /// a write by path invalidates entries cached by id and vice versa.
+    project_ids: Mutex<HashMap<String, String>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    revalidations: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl ResponseCache {
+    pub fn new(settings: CacheSettings) -> Self {
+        Self {
+            settings,
+            entries: Mutex::new(HashMap::new()),
+            project_ids: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            revalidations: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+        }
+    }
+
+    pub fn lookup(&self, path: &str) -> Lookup {
+        let entries = self.entries.lock().unwrap();
+        match entries.get(path) {
+            Some(entry) if entry.fresh_until > Instant::now() => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Lookup::Fresh(entry.body.clone())
+            }
+            Some(Entry {
+                etag: Some(etag), ..
+            }) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Lookup::Stale { etag: etag.clone() }
+            }
+            _ => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Lookup::Miss
+            }
+        }
+    }
+
+    /// GitLab answered 304 for a stale entry: extend it and return its body.
+    pub fn revalidated(&self, path: &str) -> Option<Vec<u8>> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get_mut(path)?;
+        entry.fresh_until = Instant::now() + self.settings.ttl_for(path);
+        self.revalidations.fetch_add(1, Ordering::Relaxed);
+        Some(entry.body.clone())
+    }
+
+    pub fn store(&self, path: &str, body: Vec<u8>, etag: Option<String>) {
+        if is_project_path(path) {
+            self.learn_project_id(&body);
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.settings.max_entries && !entries.contains_key(path) {
+            entries.retain(|_, e| e.fresh_until > now || e.etag.is_some());
+            if entries.len() >= self.settings.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, e)| e.stored_at)
+                    .map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(
+            path.to_string(),
+            Entry {
+                body,
+                etag,
+                project: project_segment(path),
+                stored_at: now,
+                fresh_until: now + self.settings.ttl_for(path),
+            },
+        );
+    }
+
+    /// Drop every entry belonging to the project `path` writes to.
+    pub fn invalidate_for(&self, path: &str) {
+        let Some(project) = project_segment(path) else {
+            return;
+        };
+        let project = self.canonical_project(&project);
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|_, e| {
+            e.project
+                .as_deref()
+                .is_none_or(|p| self.canonical_project(p) != project)
+        });
+        self.invalidations
+            .fetch_add((before - entries.len()) as u64, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        self.invalidations
+            .fetch_add(entries.len() as u64, Ordering::Relaxed);
+        entries.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.lock().unwrap().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            revalidations: self.revalidations.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+        }
+    }
+
+    fn learn_project_id(&self, body: &[u8]) {
+        let Ok(json) = serde_json::from_slice::<serde_json::Value>(body) else {
+            return;
+        };
+        if let (Some(id), Some(path)) = (json["id"].as_u64(), json["path_with_namespace"].as_str())
+        {
+            self.project_ids
+                .lock()
+                .unwrap()
+                .insert(path.to_lowercase(), id.to_string());
+        }
+    }
+
+    fn canonical_project(&self, segment: &str) -> String {
+        let decoded = segment
+            .replace("%2F", "/")
+            .replace("%2f", "/")
+            .to_lowercase();
+        self.project_ids
+            .lock()
+            .unwrap()
+            .get(&decoded)
+            .cloned()
+            .unwrap_or(decoded)
+    }
+}
+
+fn segments(path: &str) -> Vec<&str> {
+    let path = path.split('?').next().unwrap_or(path);
+    path.trim_matches('/').split('/').collect()
+}
+
+/// The `:id` in `projects/:id/...`, still percent-encoded.
+fn project_segment(path: &str) -> Option<String> {
+    match segments(path).as_slice() {
+        ["projects", project, ..] => Some(project.to_string()),
+        _ => None,
+    }
+}
+
+fn is_project_path(path: &str) -> bool {
+    matches!(segments(path).as_slice(), ["projects", _])
+}
+
+fn resource_kind(path: &str) -> Option<&'static str> {
+    match segments(path).as_slice() {
+        ["user"] | ["users", ..] => Some("users"),
+        ["projects", _] => Some("projects"),
+        ["projects", _, "repository", ..] => Some("files"),
+        ["projects", _, "issues", ..] => Some("issues"),
+        ["projects", _, "merge_requests", ..] => Some("merge_requests"),
+        ["projects", _, "pipelines" | "jobs", ..] => Some("pipelines"),
+        _ => None,
+    }
+}
@@ -0,0 +1,160 @@
+//! What the connected GitLab supports, probed at startup so tools that need
+//! a newer release or a paid tier are hidden rather than failing with 404s.
+
+use crate::error::{GitLabError, GitLabResult};
+use crate::gitlab_client::GitLabClient;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A tier-gated feature a tool may depend on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Group epics (Premium).
+    Epics,
+    /// Merge trains (Premium).
+    MergeTrains,
+    /// The vulnerability report (Ultimate).
+    Vulnerabilities,
+    /// GitLab Duo Chat, used by the `gitlab` model backend.
+    DuoChat,
+}
+
+impl Capability {
+    pub const ALL: &'static [Capability] = &[
+        Capability::Epics,
+        Capability::MergeTrains,
+        Capability::Vulnerabilities,
+        Capability::DuoChat,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Capability::Epics => "epics",
+            Capability::MergeTrains => "merge_trains",
+            Capability::Vulnerabilities => "vulnerabilities",
+            Capability::DuoChat => "duo_chat",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Capabilities {
+    /// e.g. `17.5.1-ee`.
+    pub version: String,
+    pub revision: Option<String>,
+    /// Enterprise Edition; Community Edition has none of the tiered features.
+    pub enterprise: bool,
+    pub features: BTreeSet<Capability>,
+    /// Features that could not be checked because the user belongs to no
+    /// group or project to probe; their tools stay hidden.
+    pub unknown: BTreeSet<Capability>,
+}
+
+impl Capabilities {
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.features.contains(&capability)
+    }
+
+    /// `(major, minor)` parsed from `version`.
+    pub fn version_number(&self) -> Option<(u32, u32)> {
+        let mut parts = self.version.split(['.', '-']);
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        Some((major, minor))
+    }
+}
+
+/// Read the version and edition, then check each tiered feature with a
+/// cheap request. A feature GitLab answers 403 or 404 for is unavailable;
+/// any other failure aborts the probe so callers can treat it as unknown.
+pub async fn probe(client: &GitLabClient) -> GitLabResult<Capabilities> {
+    // `/metadata` arrived in 15.2; older releases only have `/version`.
+    let meta: Value = match client.get("metadata").await {
+        Ok(meta) => meta,
+        Err(GitLabError::NotFound { .. }) => client.get("version").await?,
+        Err(e) => return Err(e),
+    };
+    let version = meta["version"].as_str().unwrap_or_default().to_string();
+    let enterprise = meta["enterprise"]
+        .as_bool()
+        .unwrap_or_else(|| version.ends_with("-ee"));
+    let mut caps = Capabilities {
+        revision: meta["revision"].as_str().map(str::to_string),
+        version,
+        enterprise,
+        features: BTreeSet::new(),
+        unknown: BTreeSet::new(),
+    };
+    if !enterprise {
+        return Ok(caps);
+    }
+
+    match first_id(client, "groups?min_access_level=10&per_page=1").await? {
+        Some(group) => {
+            if available(client, &format!("groups/{}/epics?per_page=1", group)).await? {
+                caps.features.insert(Capability::Epics);
+            }
+        }
+        None => {
+            caps.unknown.insert(Capability::Epics);
+        }
+    }
+    match first_id(client, "projects?membership=true&simple=true&per_page=1").await? {
+        Some(project) => {
+            if available(
+                client,
+                &format!("projects/{}/merge_trains?per_page=1", project),
+            )
+            .await?
+            {
+                caps.features.insert(Capability::MergeTrains);
+            }
+            if available(
+                client,
+                &format!("projects/{}/vulnerabilities?per_page=1", project),
+            )
+            .await?
+            {
+                caps.features.insert(Capability::Vulnerabilities);
+            }
+        }
+        None => {
+            caps.unknown
+                .extend([Capability::MergeTrains, Capability::Vulnerabilities]);
+        }
+    }
+    let duo: GitLabResult<Value> = client
+        .graphql("query { currentUser { duoChatAvailable } }", Value::Null)
+        .await;
+    match duo {
+        Ok(data) if data["currentUser"]["duoChatAvailable"] == true => {
+            caps.features.insert(Capability::DuoChat);
+        }
+        // Releases before 16.8 reject the field.
+        Ok(_) | Err(GitLabError::GraphQl { .. }) => {}
+        Err(e) => return Err(e),
+    }
+    Ok(caps)
+}
+
+async fn first_id(client: &GitLabClient, path: &str) -> GitLabResult<Option<u64>> {
+    let items: Vec<Value> = client.get(path).await?;
+    Ok(items.first().and_then(|item| item["id"].as_u64()))
+}
+
+async fn available(client: &GitLabClient, path: &str) -> GitLabResult<bool> {
+    match client.get::<Value>(path).await {
+        Ok(_) => Ok(true),
+        Err(GitLabError::Forbidden { .. } | GitLabError::NotFound { .. }) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
@@ -1,26 +1,536 @@
+use crate::cache::{CacheSettings, CACHE_RESOURCES};
+use crate::config_file::{find_workspace_config, home_dir, user_config_path, ConfigFile};
+use crate::http::{ClientIdentity, HttpSettings};
+use crate::redact::Redactor;
 use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Tool groups that `tools.enabled` / `OPENDUO_TOOLS` may name.
+pub const TOOL_GROUPS: &[&str] = &[
+    "cicd",
+    "epics",
+    "issues",
+    "labels",
+    "merge_requests",
+    "milestones",
+    "pipelines",
+    "projects",
+    "repositories",
+    "security",
+    "users",
+];
+
+/// Profile name of the instance given by `GITLAB_URL`/`GITLAB_PAT`.
+pub const DEFAULT_INSTANCE: &str = "default";
+
+const DEFAULT_PORT: u16 = 8745;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_MAX_ITERATIONS: usize = 15;
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+const DEFAULT_SESSION_IDLE_MINUTES: u64 = 60;
+const MAX_ITERATIONS_LIMIT: usize = 100;
+
+/// Which LLM backend the agent talks to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProviderKind {
+    /// GitLab Duo Chat via `/api/v4/chat/completions`.
+    #[default]
+    GitLab,
+    /// Any OpenAI-compatible `/v1/chat/completions` endpoint.
+    OpenAi,
+    /// An Anthropic Messages API (`/v1/messages`) endpoint or proxy.
+    Anthropic,
+    /// A local Ollama server (`/api/chat`).
+    Ollama,
+}
+
+impl FromStr for ProviderKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "gitlab" => Ok(Self::GitLab),
+            "openai" => Ok(Self::OpenAi),
+            "anthropic" => Ok(Self::Anthropic),
+            "ollama" => Ok(Self::Ollama),
+            other => Err(anyhow!(
+                "OPENDUO_PROVIDER must be one of: gitlab, openai, anthropic, ollama (got '{}')",
+                other
+            )),
+        }
+    }
+}
+
+/// Where chat conversations are kept between server restarts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistoryStoreKind {
+    /// One JSON-lines file per conversation under `history_dir`.
+    #[default]
+    JsonLines,
+    /// Memory only; conversations end with the process.
+    None,
+}
+
+impl FromStr for HistoryStoreKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "jsonl" => Ok(Self::JsonLines),
+            "none" => Ok(Self::None),
+            other => Err(anyhow!(
+                "history.store (OPENDUO_HISTORY_STORE) must be one of: jsonl, none (got '{}')",
+                other
+            )),
+        }
+    }
+}
+
+/// One GitLab instance the server can call: where it is and how to
+/// authenticate and trust it.
+#[derive(Debug, Clone)]
+pub struct InstanceProfile {
+    pub name: String,
+    pub gitlab_url: String,
+    pub pat: String,
+    pub ca_cert: Option<PathBuf>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub gitlab_url: String,
     pub pat: String,
     pub server_port: u16,
+    pub provider: ProviderKind,
+    /// Base URL of a non-GitLab model endpoint, e.g. `http://localhost:8000/v1`.
+    pub llm_url: Option<String>,
+    pub llm_api_key: Option<String>,
+    pub llm_model: Option<String>,
+    /// Retries for failed GitLab API requests (`OPENDUO_GITLAB_MAX_RETRIES`).
+    pub gitlab_max_retries: u32,
+    /// Reasoning steps allowed per chat turn.
+    pub max_iterations: usize,
+    /// Messages kept in history besides the system prompt.
+    pub history_limit: usize,
+    /// Chat sessions idle this long are dropped; `0` keeps them forever.
+    pub session_idle_minutes: u64,
+    /// Ask the user before running tools that change GitLab.
+    pub require_approval: bool,
+    pub history_store: HistoryStoreKind,
+    /// Directory for stored conversations; `~/.openduo/history` by default.
+    pub history_dir: Option<PathBuf>,
+    /// Base64 AES-256 key (`OPENDUO_HISTORY_KEY`); stored conversations are
+    /// encrypted when set.
+    pub history_key: Option<String>,
+    /// Tool groups to register; `None` registers all of them.
+    pub enabled_tool_groups: Option<Vec<String>>,
+    /// Project used when a tool call omits `project_id`.
+    pub default_project: Option<String>,
+    pub http: HttpSettings,
+    /// CA bundle for the primary instance (`gitlab.ca_cert`).
+    pub gitlab_ca_cert: Option<PathBuf>,
+    /// Profiles besides the primary one, from `[instances.<name>]`.
+    pub extra_instances: Vec<InstanceProfile>,
+    /// Profile used when a tool call names no instance.
+    pub default_instance: String,
+    /// Regexes redacted on top of the built-in secret patterns.
+    pub redact_patterns: Vec<String>,
+    pub cache: CacheSettings,
+}
+
+impl Default for Config {
+    /// Built-in defaults with no GitLab URL or token; mostly useful for tests
+    /// via struct update syntax.
+    fn default() -> Self {
+        Self {
+            gitlab_url: String::new(),
+            pat: String::new(),
+            server_port: DEFAULT_PORT,
+            provider: ProviderKind::default(),
+            llm_url: None,
+            llm_api_key: None,
+            llm_model: None,
+            gitlab_max_retries: DEFAULT_MAX_RETRIES,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            session_idle_minutes: DEFAULT_SESSION_IDLE_MINUTES,
+            require_approval: true,
+            history_store: HistoryStoreKind::default(),
+            history_dir: None,
+            history_key: None,
+            enabled_tool_groups: None,
+            default_project: None,
+            http: HttpSettings::default(),
+            gitlab_ca_cert: None,
+            extra_instances: Vec::new(),
+            default_instance: DEFAULT_INSTANCE.to_string(),
+            redact_patterns: Vec::new(),
+            cache: CacheSettings::default(),
+        }
+    }
 }
 
 impl Config {
+    /// Defaults, then `~/.openduo/config.toml` (or `OPENDUO_CONFIG`), then
+    /// the allowed keys of the nearest `.openduo.toml` above
+    /// `OPENDUO_WORKSPACE` or the current directory, then environment
+    /// variables.
+    pub fn load() -> Result<Self> {
+        let mut file = ConfigFile::default();
+        if let Some(path) = user_config_path() {
+            match ConfigFile::read(&path)? {
+                Some(user) => file = file.merge(user),
+                None if std::env::var_os("OPENDUO_CONFIG").is_some() => {
+                    return Err(anyhow!("OPENDUO_CONFIG: {} does not exist", path.display()));
+                }
+                None => {}
+            }
+        }
+        let workspace = match std::env::var_os("OPENDUO_WORKSPACE") {
+            Some(dir) => dir.into(),
+            None => std::env::current_dir()?,
+        };
+        if let Some(path) = find_workspace_config(&workspace) {
+            if let Some(ws) = ConfigFile::read(&path)? {
+                tracing::info!(path = %path.display(), "Loaded workspace config");
+                let (ws, ignored) = ws.workspace_layer();
+                if !ignored.is_empty() {
+                    tracing::warn!(
+                        path = %path.display(),
+                        keys = %ignored.join(", "),
+                        "Ignoring workspace config keys; set them in the user config instead"
+                    );
+                }
+                file = file.merge(ws);
+            }
+        }
+        Self::from_file_and_env(file)
+    }
+
+    /// Environment variables over built-in defaults, ignoring config files.
     pub fn from_env() -> Result<Self> {
-        let gitlab_url = std::env::var("GITLAB_URL")
-            .map_err(|_| anyhow!("GITLAB_URL environment variable not set"))?;
+        Self::from_file_and_env(ConfigFile::default())
+    }
+
+    /// Resolve `file` with environment overrides and validate the result.
+    pub fn from_file_and_env(file: ConfigFile) -> Result<Self> {
+        let defaults = Self::default();
+        let gitlab_url = non_empty_env("GITLAB_URL")
+            .or(file.gitlab.url)
+            .ok_or_else(|| {
+                anyhow!("GITLAB_URL environment variable not set (or gitlab.url in a config file)")
+            })?;
         let pat = std::env::var("GITLAB_PAT")
             .map_err(|_| anyhow!("GITLAB_PAT environment variable not set"))?;
-        let server_port = std::env::var("OPENDUO_PORT")
-            .unwrap_or_else(|_| "8745".to_string())
-            .parse::<u16>()
-            .map_err(|_| anyhow!("OPENDUO_PORT must be a valid port number"))?;
-        Ok(Self {
+        let server_port = match std::env::var("OPENDUO_PORT") {
+            Ok(v) => v
+                .parse::<u16>()
+                .map_err(|_| anyhow!("OPENDUO_PORT must be a valid port number"))?,
+            Err(_) => file.server.port.unwrap_or(defaults.server_port),
+        };
+        let provider = match non_empty_env("OPENDUO_PROVIDER") {
+            Some(v) => v.parse()?,
+            None => match file.llm.provider {
+                Some(v) => v.parse().map_err(|e| anyhow!("llm.provider: {}", e))?,
+                None => ProviderKind::default(),
+            },
+        };
+        let enabled_tool_groups = match non_empty_env("OPENDUO_TOOLS") {
+            Some(v) => Some(
+                v.split(',')
+                    .map(|g| g.trim().to_string())
+                    .filter(|g| !g.is_empty())
+                    .collect(),
+            ),
+            None => file.tools.enabled,
+        };
+        let extra_instances = file
+            .instances
+            .into_iter()
+            .map(|(name, section)| {
+                let pat = std::env::var(&section.pat_env).map_err(|_| {
+                    anyhow!(
+                        "instances.{}.pat_env: environment variable {} is not set",
+                        name,
+                        section.pat_env
+                    )
+                })?;
+                Ok(InstanceProfile {
+                    name,
+                    gitlab_url: section.url,
+                    pat,
+                    ca_cert: section.ca_cert,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let secs = |env: &str, file: Option<u64>| -> Result<Option<Duration>> {
+            Ok(env_parse::<u64>(env)?.or(file).map(Duration::from_secs))
+        };
+
+        let config = Self {
             gitlab_url,
             pat,
             server_port,
-        })
+            provider,
+            llm_url: non_empty_env("OPENDUO_LLM_URL").or(file.llm.url),
+            llm_api_key: non_empty_env("OPENDUO_LLM_API_KEY"),
+            llm_model: non_empty_env("OPENDUO_LLM_MODEL").or(file.llm.model),
+            gitlab_max_retries: env_parse("OPENDUO_GITLAB_MAX_RETRIES")?
+                .or(file.gitlab.max_retries)
+                .unwrap_or(defaults.gitlab_max_retries),
+            max_iterations: env_parse("OPENDUO_MAX_ITERATIONS")?
+                .or(file.agent.max_iterations)
+                .unwrap_or(defaults.max_iterations),
+            history_limit: env_parse("OPENDUO_HISTORY_LIMIT")?
+                .or(file.agent.history_limit)
+                .unwrap_or(defaults.history_limit),
+            session_idle_minutes: env_parse("OPENDUO_SESSION_IDLE_MINUTES")?
+                .or(file.agent.session_idle_minutes)
+                .unwrap_or(defaults.session_idle_minutes),
+            require_approval: env_parse("OPENDUO_REQUIRE_APPROVAL")?
+                .or(file.agent.require_approval)
+                .unwrap_or(defaults.require_approval),
+            history_store: match non_empty_env("OPENDUO_HISTORY_STORE").or(file.history.store) {
+                Some(v) => v.parse()?,
+                None => defaults.history_store,
+            },
+            history_dir: non_empty_env("OPENDUO_HISTORY_DIR")
+                .map(PathBuf::from)
+                .or(file.history.dir)
+                .or_else(|| home_dir().map(|home| home.join(".openduo").join("history"))),
+            history_key: non_empty_env("OPENDUO_HISTORY_KEY"),
+            enabled_tool_groups,
+            default_project: non_empty_env("OPENDUO_DEFAULT_PROJECT")
+                .or(file.gitlab.default_project),
+            http: HttpSettings {
+                proxy: non_empty_env("OPENDUO_PROXY").or(file.http.proxy),
+                https_proxy: non_empty_env("HTTPS_PROXY").or_else(|| non_empty_env("https_proxy")),
+                http_proxy: non_empty_env("HTTP_PROXY").or_else(|| non_empty_env("http_proxy")),
+                no_proxy: non_empty_env("OPENDUO_NO_PROXY")
+                    .or(file.http.no_proxy.map(|hosts| hosts.join(",")))
+                    .or_else(|| non_empty_env("NO_PROXY"))
+                    .or_else(|| non_empty_env("no_proxy")),
+                connect_timeout: secs(
+                    "OPENDUO_CONNECT_TIMEOUT_SECS",
+                    file.http.connect_timeout_secs,
+                )?,
+                read_timeout: secs("OPENDUO_READ_TIMEOUT_SECS", file.http.read_timeout_secs)?,
+                extra_ca_certs: non_empty_env("OPENDUO_CA_BUNDLE")
+                    .map(PathBuf::from)
+                    .or(file.http.ca_bundle)
+                    .into_iter()
+                    .collect(),
+                client_identity: match (
+                    non_empty_env("OPENDUO_CLIENT_CERT")
+                        .map(PathBuf::from)
+                        .or(file.http.client_cert),
+                    non_empty_env("OPENDUO_CLIENT_KEY")
+                        .map(PathBuf::from)
+                        .or(file.http.client_key),
+                ) {
+                    (Some(cert), Some(key)) => Some(ClientIdentity { cert, key }),
+                    (None, None) => None,
+                    _ => {
+                        return Err(anyhow!(
+                            "http.client_cert (OPENDUO_CLIENT_CERT) and http.client_key \
+                             (OPENDUO_CLIENT_KEY) must be set together"
+                        ))
+                    }
+                },
+            },
+            gitlab_ca_cert: file.gitlab.ca_cert,
+            extra_instances,
+            default_instance: non_empty_env("OPENDUO_INSTANCE")
+                .or(file.gitlab.default_instance)
+                .unwrap_or_else(|| DEFAULT_INSTANCE.to_string()),
+            redact_patterns: file.redaction.patterns.unwrap_or_default(),
+            cache: {
+                let mut cache = defaults.cache.clone();
+                cache.enabled = env_parse("OPENDUO_CACHE")?
+                    .or(file.cache.enabled)
+                    .unwrap_or(cache.enabled);
+                cache.max_entries = file.cache.max_entries.unwrap_or(cache.max_entries);
+                if let Some(secs) = file.cache.ttl_secs {
+                    cache.default_ttl = Duration::from_secs(secs);
+                }
+                for (kind, secs) in file.cache.ttls {
+                    if !CACHE_RESOURCES.iter().any(|(k, _)| *k == kind) {
+                        return Err(anyhow!(
+                            "cache.ttls: unknown resource '{}' (expected one of: {})",
+                            kind,
+                            CACHE_RESOURCES
+                                .iter()
+                                .map(|(k, _)| *k)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ));
+                    }
+                    cache.ttls.insert(kind, Duration::from_secs(secs));
+                }
+                cache
+            },
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check resolved values, naming the config key (and env var) at fault.
+    pub fn validate(&self) -> Result<()> {
+        let is_http = |url: &str| url.starts_with("http://") || url.starts_with("https://");
+        if !is_http(&self.gitlab_url) {
+            return Err(anyhow!(
+                "gitlab.url (GITLAB_URL) must start with http:// or https:// (got '{}')",
+                self.gitlab_url
+            ));
+        }
+        if let Some(url) = self.llm_url.as_deref().filter(|u| !is_http(u)) {
+            return Err(anyhow!(
+                "llm.url (OPENDUO_LLM_URL) must start with http:// or https:// (got '{}')",
+                url
+            ));
+        }
+        if !(1..=MAX_ITERATIONS_LIMIT).contains(&self.max_iterations) {
+            return Err(anyhow!(
+                "agent.max_iterations (OPENDUO_MAX_ITERATIONS) must be between 1 and {} (got {})",
+                MAX_ITERATIONS_LIMIT,
+                self.max_iterations
+            ));
+        }
+        if self.history_limit == 0 {
+            return Err(anyhow!(
+                "agent.history_limit (OPENDUO_HISTORY_LIMIT) must be at least 1"
+            ));
+        }
+        if let Some(groups) = &self.enabled_tool_groups {
+            if let Some(bad) = groups.iter().find(|g| !TOOL_GROUPS.contains(&g.as_str())) {
+                return Err(anyhow!(
+                    "tools.enabled (OPENDUO_TOOLS): unknown tool group '{}' (expected one of: {})",
+                    bad,
+                    TOOL_GROUPS.join(", ")
+                ));
+            }
+        }
+        for profile in &self.extra_instances {
+            if profile.name == DEFAULT_INSTANCE {
+                return Err(anyhow!(
+                    "instances.{}: the name '{}' is reserved for GITLAB_URL",
+                    profile.name,
+                    DEFAULT_INSTANCE
+                ));
+            }
+            if !is_http(&profile.gitlab_url) {
+                return Err(anyhow!(
+                    "instances.{}.url must start with http:// or https:// (got '{}')",
+                    profile.name,
+                    profile.gitlab_url
+                ));
+            }
+        }
+        let cas = std::iter::once(("gitlab.ca_cert".to_string(), &self.gitlab_ca_cert)).chain(
+            self.extra_instances
+                .iter()
+                .map(|p| (format!("instances.{}.ca_cert", p.name), &p.ca_cert)),
+        );
+        for (key, path) in cas {
+            if let Some(path) = path.as_ref().filter(|p| !p.is_file()) {
+                return Err(anyhow!("{}: {} does not exist", key, path.display()));
+            }
+        }
+        if !self
+            .instances()
+            .iter()
+            .any(|p| p.name == self.default_instance)
+        {
+            return Err(anyhow!(
+                "gitlab.default_instance (OPENDUO_INSTANCE): no instance named '{}'",
+                self.default_instance
+            ));
+        }
+        for (key, proxy) in [
+            ("http.proxy (OPENDUO_PROXY)", &self.http.proxy),
+            ("HTTPS_PROXY", &self.http.https_proxy),
+            ("HTTP_PROXY", &self.http.http_proxy),
+        ] {
+            if let Some(proxy) = proxy {
+                reqwest::Proxy::all(proxy).map_err(|e| anyhow!("{}: {}", key, e))?;
+            }
+        }
+        let files = self
+            .http
+            .extra_ca_certs
+            .iter()
+            .map(|path| ("http.ca_bundle (OPENDUO_CA_BUNDLE)", path))
+            .chain(self.http.client_identity.iter().flat_map(|id| {
+                [
+                    ("http.client_cert (OPENDUO_CLIENT_CERT)", &id.cert),
+                    ("http.client_key (OPENDUO_CLIENT_KEY)", &id.key),
+                ]
+            }));
+        for (key, path) in files {
+            if !path.is_file() {
+                return Err(anyhow!("{}: {} does not exist", key, path.display()));
+            }
+        }
+        if let Some(identity) = &self.http.client_identity {
+            identity
+                .load()
+                .map_err(|e| anyhow!("http.client_cert (OPENDUO_CLIENT_CERT): {}", e))?;
+        }
+        if self.cache.max_entries == 0 {
+            return Err(anyhow!("cache.max_entries must be at least 1"));
+        }
+        Redactor::new(&self.redact_patterns).map_err(|e| anyhow!("redaction.patterns: {}", e))?;
+        for (key, timeout) in [
+            (
+                "http.connect_timeout_secs (OPENDUO_CONNECT_TIMEOUT_SECS)",
+                self.http.connect_timeout,
+            ),
+            (
+                "http.read_timeout_secs (OPENDUO_READ_TIMEOUT_SECS)",
+                self.http.read_timeout,
+            ),
+        ] {
+            if timeout == Some(Duration::ZERO) {
+                return Err(anyhow!("{} must be at least 1", key));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every configured instance, the primary (`GITLAB_URL`) first.
+    pub fn instances(&self) -> Vec<InstanceProfile> {
+        let primary = InstanceProfile {
+            name: DEFAULT_INSTANCE.to_string(),
+            gitlab_url: self.gitlab_url.clone(),
+            pat: self.pat.clone(),
+            ca_cert: self.gitlab_ca_cert.clone(),
+        };
+        std::iter::once(primary)
+            .chain(self.extra_instances.iter().cloned())
+            .collect()
+    }
+
+    /// Whether tools in `group` should be registered.
+    pub fn tool_group_enabled(&self, group: &str) -> bool {
+        self.enabled_tool_groups
+            .as_ref()
+            .is_none_or(|groups| groups.iter().any(|g| g == group))
+    }
+}
+
+fn non_empty_env(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn env_parse<T: FromStr>(key: &str) -> Result<Option<T>> {
+    match non_empty_env(key) {
+        Some(v) => v
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| anyhow!("{} has an invalid value '{}'", key, v)),
+        None => Ok(None),
     }
 }
@@ -0,0 +1,337 @@
+//! On-disk configuration: a user-level `~/.openduo/config.toml` and a
+//! workspace-level `.openduo.toml`, layered under environment variables by
+//! `Config::load`.
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// File name searched for in the workspace directory and its parents.
+pub const WORKSPACE_CONFIG_FILE: &str = ".openduo.toml";
+
+/// The contents of one config file. Every key is optional so files can be
+/// layered; unknown keys are rejected so typos do not go unnoticed.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub gitlab: GitLabSection,
+    #[serde(default)]
+    pub server: ServerSection,
+    #[serde(default)]
+    pub llm: LlmSection,
+    #[serde(default)]
+    pub agent: AgentSection,
+    #[serde(default)]
+    pub tools: ToolsSection,
+    #[serde(default)]
+    pub http: HttpSection,
+    #[serde(default)]
+    pub redaction: RedactionSection,
+    #[serde(default)]
+    pub cache: CacheSection,
+    #[serde(default)]
+    pub history: HistorySection,
+    /// Additional GitLab instances, keyed by profile name.
+    #[serde(default)]
+    pub instances: BTreeMap<String, InstanceSection>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GitLabSection {
+    pub url: Option<String>,
+    pub default_project: Option<String>,
+    pub max_retries: Option<u32>,
+    /// PEM bundle trusted in addition to the system roots for this instance.
+    pub ca_cert: Option<PathBuf>,
+    /// Instance profile tools use when a call names none.
+    pub default_instance: Option<String>,
+    /// Only present to reject it with a pointer to `GITLAB_PAT`.
+    pat: Option<toml::Value>,
+}
+
+/// `[instances.<name>]`: another GitLab the same server can talk to.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstanceSection {
+    pub url: String,
+    /// Environment variable holding this instance's PAT.
+    pub pat_env: String,
+    pub ca_cert: Option<PathBuf>,
+    /// Only present to reject it with a pointer to `pat_env`.
+    pat: Option<toml::Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerSection {
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LlmSection {
+    pub provider: Option<String>,
+    pub url: Option<String>,
+    pub model: Option<String>,
+    /// Only present to reject it with a pointer to `OPENDUO_LLM_API_KEY`.
+    api_key: Option<toml::Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentSection {
+    pub max_iterations: Option<usize>,
+    pub history_limit: Option<usize>,
+    pub session_idle_minutes: Option<u64>,
+    pub require_approval: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolsSection {
+    pub enabled: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpSection {
+    pub proxy: Option<String>,
+    pub no_proxy: Option<Vec<String>>,
+    pub connect_timeout_secs: Option<u64>,
+    pub read_timeout_secs: Option<u64>,
+    /// PEM bundle trusted in addition to the system roots, for every host.
+    pub ca_bundle: Option<PathBuf>,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedactionSection {
+    /// Extra regexes scrubbed from logs and tool output.
+    pub patterns: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheSection {
+    pub enabled: Option<bool>,
+    pub max_entries: Option<usize>,
+    /// TTL for paths that match no resource in `ttls`.
+    pub ttl_secs: Option<u64>,
+    /// TTL in seconds per resource kind, e.g. `files = 120`.
+    #[serde(default)]
+    pub ttls: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HistorySection {
+    /// `jsonl` or `none`.
+    pub store: Option<String>,
+    pub dir: Option<PathBuf>,
+}
+
+impl ConfigFile {
+    /// Parse TOML text; `source` names the file in error messages.
+    pub fn parse(text: &str, source: &Path) -> Result<Self> {
+        let file: Self =
+            toml::from_str(text).map_err(|e| anyhow!("{}: {}", source.display(), e))?;
+        if file.gitlab.pat.is_some() {
+            return Err(anyhow!(
+                "{}: gitlab.pat is not read from config files; set GITLAB_PAT instead",
+                source.display()
+            ));
+        }
+        if let Some(name) = file
+            .instances
+            .iter()
+            .find_map(|(n, i)| i.pat.as_ref().map(|_| n))
+        {
+            return Err(anyhow!(
+                "{}: instances.{}.pat is not read from config files; name an environment \
+                 variable with pat_env instead",
+                source.display(),
+                name
+            ));
+        }
+        if file.llm.api_key.is_some() {
+            return Err(anyhow!(
+                "{}: llm.api_key is not read from config files; set OPENDUO_LLM_API_KEY instead",
+                source.display()
+            ));
+        }
+        Ok(file)
+    }
+
+    /// Read and parse `path`, or `None` if it does not exist.
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text, path).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    /// Keep only the keys a workspace file may set, since it comes from
+    /// whatever repository is open. Returns the names of the dropped keys.
+    pub fn workspace_layer(self) -> (Self, Vec<&'static str>) {
+        let set = [
+            ("gitlab.url", self.gitlab.url.is_some()),
+            ("gitlab.max_retries", self.gitlab.max_retries.is_some()),
+            ("gitlab.ca_cert", self.gitlab.ca_cert.is_some()),
+            (
+                "gitlab.default_instance",
+                self.gitlab.default_instance.is_some(),
+            ),
+            ("server.port", self.server.port.is_some()),
+            ("llm.provider", self.llm.provider.is_some()),
+            ("llm.url", self.llm.url.is_some()),
+            ("llm.model", self.llm.model.is_some()),
+            (
+                "agent.session_idle_minutes",
+                self.agent.session_idle_minutes.is_some(),
+            ),
+            // It may turn approval on, but only the user may turn it off.
+            (
+                "agent.require_approval",
+                self.agent.require_approval == Some(false),
+            ),
+            ("tools.enabled", self.tools.enabled.is_some()),
+            ("http.proxy", self.http.proxy.is_some()),
+            ("http.no_proxy", self.http.no_proxy.is_some()),
+            (
+                "http.connect_timeout_secs",
+                self.http.connect_timeout_secs.is_some(),
+            ),
+            (
+                "http.read_timeout_secs",
+                self.http.read_timeout_secs.is_some(),
+            ),
+            ("http.ca_bundle", self.http.ca_bundle.is_some()),
+            ("http.client_cert", self.http.client_cert.is_some()),
+            ("http.client_key", self.http.client_key.is_some()),
+            ("redaction.patterns", self.redaction.patterns.is_some()),
+            ("cache.enabled", self.cache.enabled.is_some()),
+            ("cache.max_entries", self.cache.max_entries.is_some()),
+            ("cache.ttl_secs", self.cache.ttl_secs.is_some()),
+            ("cache.ttls", !self.cache.ttls.is_empty()),
+            ("history.store", self.history.store.is_some()),
+            ("history.dir", self.history.dir.is_some()),
+            ("instances", !self.instances.is_empty()),
+        ];
+        let ignored = set.iter().filter(|(_, s)| *s).map(|(k, _)| *k).collect();
+        let kept = Self {
+            gitlab: GitLabSection {
+                default_project: self.gitlab.default_project,
+                ..Default::default()
+            },
+            agent: AgentSection {
+                max_iterations: self.agent.max_iterations,
+                history_limit: self.agent.history_limit,
+                require_approval: self.agent.require_approval.filter(|on| *on),
+                session_idle_minutes: None,
+            },
+            ..Default::default()
+        };
+        (kept, ignored)
+    }
+
+    /// Overlay `other` on `self`; keys set in `other` win.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            gitlab: GitLabSection {
+                url: other.gitlab.url.or(self.gitlab.url),
+                default_project: other.gitlab.default_project.or(self.gitlab.default_project),
+                max_retries: other.gitlab.max_retries.or(self.gitlab.max_retries),
+                ca_cert: other.gitlab.ca_cert.or(self.gitlab.ca_cert),
+                default_instance: other
+                    .gitlab
+                    .default_instance
+                    .or(self.gitlab.default_instance),
+                pat: None,
+            },
+            server: ServerSection {
+                port: other.server.port.or(self.server.port),
+            },
+            llm: LlmSection {
+                provider: other.llm.provider.or(self.llm.provider),
+                url: other.llm.url.or(self.llm.url),
+                model: other.llm.model.or(self.llm.model),
+                api_key: None,
+            },
+            agent: AgentSection {
+                max_iterations: other.agent.max_iterations.or(self.agent.max_iterations),
+                history_limit: other.agent.history_limit.or(self.agent.history_limit),
+                session_idle_minutes: other
+                    .agent
+                    .session_idle_minutes
+                    .or(self.agent.session_idle_minutes),
+                require_approval: other.agent.require_approval.or(self.agent.require_approval),
+            },
+            tools: ToolsSection {
+                enabled: other.tools.enabled.or(self.tools.enabled),
+            },
+            http: HttpSection {
+                proxy: other.http.proxy.or(self.http.proxy),
+                no_proxy: other.http.no_proxy.or(self.http.no_proxy),
+                connect_timeout_secs: other
+                    .http
+                    .connect_timeout_secs
+                    .or(self.http.connect_timeout_secs),
+                read_timeout_secs: other.http.read_timeout_secs.or(self.http.read_timeout_secs),
+                ca_bundle: other.http.ca_bundle.or(self.http.ca_bundle),
+                client_cert: other.http.client_cert.or(self.http.client_cert),
+                client_key: other.http.client_key.or(self.http.client_key),
+            },
+            redaction: RedactionSection {
+                patterns: other.redaction.patterns.or(self.redaction.patterns),
+            },
+            cache: CacheSection {
+                enabled: other.cache.enabled.or(self.cache.enabled),
+                max_entries: other.cache.max_entries.or(self.cache.max_entries),
+                ttl_secs: other.cache.ttl_secs.or(self.cache.ttl_secs),
+                ttls: {
+                    let mut ttls = self.cache.ttls;
+                    ttls.extend(other.cache.ttls);
+                    ttls
+                },
+            },
+            history: HistorySection {
+                store: other.history.store.or(self.history.store),
+                dir: other.history.dir.or(self.history.dir),
+            },
+            instances: {
+                let mut instances = self.instances;
+                instances.extend(other.instances);
+                instances
+            },
+        }
+    }
+}
+
+/// `OPENDUO_CONFIG` if set, otherwise `~/.openduo/config.toml`.
+pub fn user_config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("OPENDUO_CONFIG").filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    home_dir().map(|home| home.join(".openduo").join("config.toml"))
+}
+
+/// The nearest `.openduo.toml` in `start` or one of its parents.
+pub fn find_workspace_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(WORKSPACE_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+}
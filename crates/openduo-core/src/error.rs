@@ -0,0 +1,245 @@
+use crate::retry::server_delay;
+use reqwest::header::{HeaderMap, WWW_AUTHENTICATE};
+use reqwest::{Response, StatusCode};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+pub type GitLabResult<T> = std::result::Result<T, GitLabError>;
+
+/// A failed GitLab API call, classified so callers (and the model) can tell
+/// a missing resource from a missing permission.
+#[derive(Debug)]
+pub enum GitLabError {
+    /// 401: the token is missing, revoked or expired.
+    Unauthorized { message: String },
+    /// 403: the token lacks a scope, or the user lacks a role.
+    Forbidden {
+        message: String,
+        required_scope: Option<String>,
+    },
+    /// 404: GitLab also answers 404 for resources the user cannot see.
+    NotFound { message: String },
+    /// 400, 409 or 422, with GitLab's per-field messages when it sent them.
+    ValidationFailed {
+        status: StatusCode,
+        message: String,
+        fields: BTreeMap<String, Vec<String>>,
+    },
+    /// 429 after retries were exhausted.
+    RateLimited { retry_after: Option<Duration> },
+    /// 5xx after retries were exhausted.
+    Server { status: StatusCode, message: String },
+    /// Any other unsuccessful status, e.g. 405 for an action the resource's
+    /// current state does not allow.
+    Status { status: StatusCode, message: String },
+    /// No response at all: DNS, TLS, connection reset or timeout.
+    Transport(reqwest::Error),
+    /// `/api/graphql` answered with an `errors` array.
+    GraphQl { messages: Vec<String> },
+    /// A response arrived but was not what the caller expected.
+    Decode(String),
+    /// The request could not be built, e.g. a token that is not a valid header.
+    InvalidRequest(String),
+    /// A write held back by `preview_writes` instead of being sent.
+    Withheld,
+}
+
+impl GitLabError {
+    /// Read an unsuccessful response and classify it.
+    pub async fn from_response(resp: Response) -> Self {
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let body = resp.text().await.unwrap_or_default();
+        Self::from_parts(status, &headers, &body)
+    }
+
+    pub fn from_parts(status: StatusCode, headers: &HeaderMap, body: &str) -> Self {
+        let json: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+        let mut fields = BTreeMap::new();
+        let message = match &json["message"] {
+            Value::String(s) => s.clone(),
+            Value::Object(map) => {
+                for (field, errors) in map {
+                    let errors = match errors {
+                        Value::Array(items) => items.iter().map(value_text).collect(),
+                        other => vec![value_text(other)],
+                    };
+                    fields.insert(field.clone(), errors);
+                }
+                String::new()
+            }
+            Value::Array(items) => items.iter().map(value_text).collect::<Vec<_>>().join("; "),
+            _ => json["error_description"]
+                .as_str()
+                .or_else(|| json["error"].as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| body.trim().chars().take(200).collect()),
+        };
+        let message = if message.is_empty() && fields.is_empty() {
+            status.canonical_reason().unwrap_or("").to_string()
+        } else {
+            message
+        };
+
+        match status.as_u16() {
+            401 => Self::Unauthorized { message },
+            403 => Self::Forbidden {
+                message,
+                required_scope: json["scope"]
+                    .as_str()
+                    .map(str::to_string)
+                    .or_else(|| scope_from_www_authenticate(headers)),
+            },
+            404 => Self::NotFound { message },
+            400 | 409 | 422 => Self::ValidationFailed {
+                status,
+                message,
+                fields,
+            },
+            429 => Self::RateLimited {
+                retry_after: server_delay(headers),
+            },
+            500..=599 => Self::Server { status, message },
+            _ => Self::Status { status, message },
+        }
+    }
+
+    /// HTTP status behind the error, when there was a response.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Unauthorized { .. } => Some(StatusCode::UNAUTHORIZED),
+            Self::Forbidden { .. } => Some(StatusCode::FORBIDDEN),
+            Self::NotFound { .. } => Some(StatusCode::NOT_FOUND),
+            Self::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS),
+            Self::ValidationFailed { status, .. }
+            | Self::Server { status, .. }
+            | Self::Status { status, .. } => Some(*status),
+            Self::Transport(e) => e.status(),
+            Self::GraphQl { .. } | Self::Decode(_) | Self::InvalidRequest(_) | Self::Withheld => {
+                None
+            }
+        }
+    }
+
+    /// What the model (or user) should do about this error.
+    pub fn remediation(&self) -> Option<String> {
+        let hint = match self {
+            Self::Unauthorized { .. } => "The GitLab personal access token is missing, revoked or \
+                expired. Retrying will not help; ask the user to create a new token and \
+                reconfigure OpenDuo."
+                .to_string(),
+            Self::Forbidden {
+                required_scope: Some(scope),
+                ..
+            } => format!(
+                "The access token lacks the `{}` scope. Ask the user to issue a token with that \
+                 scope; retrying will not help.",
+                scope
+            ),
+            Self::Forbidden { .. } => "The user does not have permission for this action in \
+                this project (it may need the Developer or Maintainer role). Do not retry with \
+                the same arguments; tell the user what access is missing."
+                .to_string(),
+            Self::NotFound { .. } => "Check the project ID or path and the IID/ID you used; \
+                list the parent resource to find valid values. GitLab also returns 404 for \
+                private resources the user cannot see."
+                .to_string(),
+            Self::ValidationFailed { .. } => {
+                "Fix the rejected arguments and call the tool again.".to_string()
+            }
+            Self::RateLimited { .. } => "GitLab is throttling requests. Avoid further tool \
+                calls for now and ask for fewer items when you retry."
+                .to_string(),
+            Self::Server { .. } => "The GitLab instance failed to handle the request and \
+                retries did not help. Tell the user it may be temporarily unavailable."
+                .to_string(),
+            Self::Status { status, .. } if *status == StatusCode::METHOD_NOT_ALLOWED => {
+                "The resource's current state does not allow this action (for example merging \
+                 a closed or conflicting merge request). Fetch it to check its state first."
+                    .to_string()
+            }
+            Self::GraphQl { .. } => "Check the GraphQL query's field names and arguments \
+                against the GitLab schema for this instance's version."
+                .to_string(),
+            Self::Transport(_) => "Could not reach GitLab. Check GITLAB_URL and the network or \
+                proxy settings."
+                .to_string(),
+            Self::Status { .. } | Self::Decode(_) | Self::InvalidRequest(_) | Self::Withheld => {
+                return None
+            }
+        };
+        Some(hint)
+    }
+}
+
+impl fmt::Display for GitLabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unauthorized { message } => write!(f, "401 Unauthorized: {}", message),
+            Self::Forbidden { message, .. } => write!(f, "403 Forbidden: {}", message),
+            Self::NotFound { message } => write!(f, "404 Not Found: {}", message),
+            Self::ValidationFailed {
+                status,
+                message,
+                fields,
+            } => {
+                write!(f, "{} validation failed", status.as_u16())?;
+                if !message.is_empty() {
+                    write!(f, ": {}", message)?;
+                }
+                for (field, errors) in fields {
+                    write!(f, "; {} {}", field, errors.join(", "))?;
+                }
+                Ok(())
+            }
+            Self::RateLimited {
+                retry_after: Some(wait),
+            } => write!(f, "429 Too Many Requests: retry after {}s", wait.as_secs()),
+            Self::RateLimited { retry_after: None } => write!(f, "429 Too Many Requests"),
+            Self::Server { status, message } | Self::Status { status, message } => {
+                write!(f, "{}: {}", status, message)
+            }
+            Self::Transport(e) => write!(f, "request to GitLab failed: {}", e),
+            Self::GraphQl { messages } => write!(f, "GraphQL error: {}", messages.join("; ")),
+            Self::Decode(msg) => write!(f, "unexpected response from GitLab: {}", msg),
+            Self::InvalidRequest(msg) => write!(f, "invalid GitLab request: {}", msg),
+            Self::Withheld => write!(f, "request withheld for preview"),
+        }
+    }
+}
+
+impl std::error::Error for GitLabError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for GitLabError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            Self::Decode(e.to_string())
+        } else {
+            Self::Transport(e)
+        }
+    }
+}
+
+fn value_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// `WWW-Authenticate: Bearer realm="...", error="insufficient_scope", scope="api"`
+fn scope_from_www_authenticate(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(WWW_AUTHENTICATE)?.to_str().ok()?;
+    let start = value.find("scope=\"")? + "scope=\"".len();
+    let end = value[start..].find('"')? + start;
+    Some(value[start..end].to_string())
+}
@@ -1,30 +1,197 @@
 use crate::auth::AuthHeaders;
-use crate::config::Config;
+use crate::cache::{CacheStats, Lookup, ResponseCache};
+use crate::config::{Config, InstanceProfile};
+use crate::error::{GitLabError, GitLabResult};
+use crate::retry::{is_retryable_status, server_delay, RateLimit, RetryPolicy};
 use anyhow::Result;
-use reqwest::Client;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
 use serde::de::DeserializeOwned;
-use tracing::instrument;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::Arc;
+use tracing::{debug, instrument, warn};
+
+/// Largest page size the GitLab REST API accepts.
+const MAX_PER_PAGE: usize = 100;
+
+/// A write `preview_writes` recorded instead of sending.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlannedRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<serde_json::Value>,
+}
+
+tokio::task_local! {
+    static PREVIEW: RefCell<Vec<PlannedRequest>>;
+}
+
+/// Run `f`, recording each write a `GitLabClient` is asked to make instead
+/// of sending it; the write fails with `GitLabError::Withheld`. Reads and
+/// GraphQL queries go through as usual.
+pub async fn preview_writes<F: Future>(f: F) -> (F::Output, Vec<PlannedRequest>) {
+    PREVIEW
+        .scope(RefCell::new(Vec::new()), async move {
+            let output = f.await;
+            (output, PREVIEW.with(RefCell::take))
+        })
+        .await
+}
+
+/// `Err(Withheld)` after recording the write, when inside `preview_writes`.
+fn withhold(method: &str, url: &str, body: Option<serde_json::Value>) -> GitLabResult<()> {
+    let planned = PREVIEW.try_with(|planned| {
+        planned.borrow_mut().push(PlannedRequest {
+            method: method.to_string(),
+            url: url.to_string(),
+            body,
+        })
+    });
+    match planned {
+        Ok(()) => Err(GitLabError::Withheld),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Whether every operation in a GraphQL document is a query. Comments,
+/// commas and fragment definitions are skipped; anything else, including
+/// a document this cannot follow, counts as a write.
+fn is_read_only(document: &str) -> bool {
+    let mut rest = document;
+    let mut operations = 0;
+    loop {
+        rest = skip_ignored(rest);
+        if rest.is_empty() {
+            return operations > 0;
+        }
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        match &rest[..end] {
+            "" if rest.starts_with('{') => operations += 1,
+            "query" => operations += 1,
+            "fragment" => {}
+            _ => return false,
+        }
+        match skip_definition(&rest[end..]) {
+            Some(after) => rest = after,
+            None => return false,
+        }
+    }
+}
+
+/// `text` without leading whitespace, commas and `#` comments.
+fn skip_ignored(mut text: &str) -> &str {
+    loop {
+        text = text.trim_start_matches(|c: char| c.is_whitespace() || c == ',' || c == '\u{feff}');
+        match text.strip_prefix('#') {
+            Some(comment) => text = comment.find('\n').map_or("", |i| &comment[i..]),
+            None => return text,
+        }
+    }
+}
+
+/// What follows the selection set closing the definition that `text` is
+/// in, skipping strings, comments and braces in variable defaults.
+fn skip_definition(text: &str) -> Option<&str> {
+    let (mut braces, mut parens) = (0usize, 0usize);
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        rest = match c {
+            '#' => after.find('\n').map_or("", |i| &after[i..]),
+            '"' if after.starts_with("\"\"") => {
+                let body = &after[2..];
+                &body[body.find("\"\"\"")? + 3..]
+            }
+            '"' => {
+                let mut escaped = false;
+                let end = after.find(|c| {
+                    let close = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    close
+                })?;
+                &after[end + 1..]
+            }
+            '(' => {
+                parens += 1;
+                after
+            }
+            ')' => {
+                parens = parens.checked_sub(1)?;
+                after
+            }
+            '{' => {
+                braces += 1;
+                after
+            }
+            '}' => {
+                braces = braces.checked_sub(1)?;
+                if braces == 0 && parens == 0 {
+                    return Some(after);
+                }
+                after
+            }
+            _ => after,
+        };
+    }
+    None
+}
 
 #[derive(Clone)]
 pub struct GitLabClient {
     client: Client,
     base_url: String,
     pat: String,
+    retry: RetryPolicy,
+    /// Shared by clones, so every tool bound to this instance benefits.
+    cache: Option<Arc<ResponseCache>>,
 }
 
 impl GitLabClient {
+    /// Client for the primary instance (`GITLAB_URL`/`GITLAB_PAT`).
     pub fn new(config: Config) -> Result<Self> {
-        let client = Client::builder()
-            .use_native_tls()
-            .build()
-            .map_err(|e| anyhow::anyhow!("Failed to build reqwest client: {}", e))?;
+        let primary = config.instances().remove(0);
+        Self::for_instance(&primary, &config)
+    }
+
+    /// Client for one instance profile, sharing the rest of `config`.
+    pub fn for_instance(profile: &InstanceProfile, config: &Config) -> Result<Self> {
+        let mut http = config.http.clone();
+        http.extra_ca_certs.extend(profile.ca_cert.clone());
         Ok(Self {
-            client,
-            base_url: config.gitlab_url.trim_end_matches('/').to_string(),
-            pat: config.pat,
+            client: http.build_client()?,
+            base_url: profile.gitlab_url.trim_end_matches('/').to_string(),
+            pat: profile.pat.clone(),
+            retry: RetryPolicy::with_max_retries(config.gitlab_max_retries),
+            cache: config
+                .cache
+                .enabled
+                .then(|| Arc::new(ResponseCache::new(config.cache.clone()))),
         })
     }
 
+    /// Counters for the response cache, if it is enabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|c| c.stats())
+    }
+
+    /// Replace the retry policy derived from `Config`.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    fn auth_headers(&self) -> GitLabResult<HeaderMap> {
+        AuthHeaders::new(&self.pat)
+            .to_header_map()
+            .map_err(|e| GitLabError::InvalidRequest(e.to_string()))
+    }
+
     pub fn base_url(&self) -> &str {
         &self.base_url
     }
@@ -34,16 +201,262 @@ impl GitLabClient {
     }
 
     #[instrument(skip(self))]
-    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
-        let headers = AuthHeaders::new(&self.pat).to_header_map()?;
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> GitLabResult<T> {
+        let mut headers = self.auth_headers()?;
+        let Some(cache) = &self.cache else {
+            let resp = self
+                .send(self.client.get(self.api_url(path)).headers(headers), true)
+                .await?;
+            return Ok(resp.json::<T>().await?);
+        };
+
+        let key = path.trim_start_matches('/');
+        match cache.lookup(key) {
+            Lookup::Fresh(body) => return decode(&body),
+            Lookup::Stale { etag } => {
+                if let Ok(value) = HeaderValue::from_str(&etag) {
+                    headers.insert(IF_NONE_MATCH, value);
+                }
+            }
+            Lookup::Miss => {}
+        }
+        let mut resp = self
+            .send(
+                self.client.get(self.api_url(path)).headers(headers.clone()),
+                true,
+            )
+            .await?;
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            if let Some(body) = cache.revalidated(key) {
+                return decode(&body);
+            }
+            // Evicted since the lookup, so there is nothing to reuse.
+            headers.remove(IF_NONE_MATCH);
+            resp = self
+                .send(self.client.get(self.api_url(path)).headers(headers), true)
+                .await?;
+        }
+        let etag = resp
+            .headers()
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let body = resp.bytes().await?.to_vec();
+        let value = decode(&body)?;
+        cache.store(key, body, etag);
+        Ok(value)
+    }
+
+    /// Stream up to `max_items` items from a list endpoint, fetching further
+    /// pages on demand. Follows the `Link: rel="next"` header, which covers
+    /// both offset and keyset (`pagination=keyset`) pagination, and falls
+    /// back to `X-Next-Page`. A `per_page` is added unless `path` sets one.
+    pub fn paginate<T>(&self, path: &str, max_items: usize) -> BoxStream<'static, GitLabResult<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        if max_items == 0 {
+            return stream::empty().boxed();
+        }
+        let first = match Url::parse(&self.api_url(path)) {
+            Ok(mut url) => {
+                if !url.query_pairs().any(|(k, _)| k == "per_page") {
+                    let per_page = max_items.min(MAX_PER_PAGE);
+                    url.query_pairs_mut()
+                        .append_pair("per_page", &per_page.to_string());
+                }
+                url
+            }
+            Err(e) => {
+                let err = GitLabError::InvalidRequest(e.to_string());
+                return stream::once(async move { Err(err) }).boxed();
+            }
+        };
+        let client = self.clone();
+        stream::try_unfold(Some(first), move |next| {
+            let client = client.clone();
+            async move {
+                let Some(url) = next else {
+                    return Ok::<_, GitLabError>(None);
+                };
+                let resp = client.get_raw(url.as_str()).await?;
+                let next = client.next_page_url(&url, resp.headers());
+                let items: Vec<T> = resp.json().await?;
+                Ok(Some((stream::iter(items.into_iter().map(Ok)), next)))
+            }
+        })
+        .try_flatten()
+        .take(max_items)
+        .boxed()
+    }
+
+    /// Collect up to `max_items` items from a list endpoint across pages.
+    #[instrument(skip(self))]
+    pub async fn get_paginated<T>(&self, path: &str, max_items: usize) -> GitLabResult<Vec<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        self.paginate(path, max_items).try_collect().await
+    }
+
+    /// `POST /api/graphql`, decoding `data` as `T`. Any entry in `errors`
+    /// fails the call, since partial data is easy to misread.
+    #[instrument(skip(self, query, variables))]
+    pub async fn graphql<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> GitLabResult<T> {
+        let headers = AuthHeaders::new(&self.pat)
+            .to_bearer_header_map()
+            .map_err(|e| GitLabError::InvalidRequest(e.to_string()))?;
+        let body = serde_json::json!({ "query": query, "variables": variables });
+        let idempotent = is_read_only(query);
+        if !idempotent {
+            withhold(
+                "POST",
+                &format!("{}/api/graphql", self.base_url),
+                Some(body.clone()),
+            )?;
+        }
+        let clear = || {
+            if let (false, Some(cache)) = (idempotent, &self.cache) {
+                cache.clear();
+            }
+        };
+        clear();
         let resp = self
-            .client
-            .get(self.api_url(path))
-            .headers(headers)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(resp.json::<T>().await?)
+            .send(
+                self.client
+                    .post(format!("{}/api/graphql", self.base_url))
+                    .headers(headers)
+                    .json(&body),
+                idempotent,
+            )
+            .await;
+        // Again once it landed, in case a concurrent read cached the old state.
+        clear();
+        let resp = resp?;
+        let mut body: serde_json::Value = resp.json().await?;
+        if let Some(errors) = body["errors"].as_array().filter(|e| !e.is_empty()) {
+            let messages = errors
+                .iter()
+                .map(|e| match e["message"].as_str() {
+                    Some(m) => m.to_string(),
+                    None => e.to_string(),
+                })
+                .collect();
+            return Err(GitLabError::GraphQl { messages });
+        }
+        serde_json::from_value(body["data"].take()).map_err(|e| GitLabError::Decode(e.to_string()))
+    }
+
+    /// Collect up to `max_items` nodes from a cursor-paginated connection.
+    /// `connection` is the field path from `data` to the connection, e.g.
+    /// `["project", "workItems"]`. The query must declare `$after: String`
+    /// and select `nodes` and `pageInfo { hasNextPage endCursor }`.
+    /// `variables` must be an object or `null`.
+    #[instrument(skip(self, query, variables))]
+    pub async fn graphql_paginated<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+        connection: &[&str],
+        max_items: usize,
+    ) -> GitLabResult<Vec<T>> {
+        let mut variables = match variables {
+            serde_json::Value::Null => serde_json::json!({}),
+            v @ serde_json::Value::Object(_) => v,
+            other => {
+                return Err(GitLabError::InvalidRequest(format!(
+                    "GraphQL variables must be an object, got {}",
+                    other
+                )))
+            }
+        };
+        let mut items = Vec::new();
+        while items.len() < max_items {
+            let data: serde_json::Value = self.graphql(query, variables.clone()).await?;
+            let conn = connection.iter().fold(&data, |v, field| &v[*field]);
+            if conn.is_null() {
+                return Err(GitLabError::NotFound {
+                    message: format!("`{}` is null in the GraphQL response", connection.join(".")),
+                });
+            }
+            let nodes = conn["nodes"].as_array().cloned().unwrap_or_default();
+            for node in nodes.into_iter().take(max_items - items.len()) {
+                items.push(
+                    serde_json::from_value(node).map_err(|e| GitLabError::Decode(e.to_string()))?,
+                );
+            }
+            match (
+                conn["pageInfo"]["hasNextPage"].as_bool(),
+                conn["pageInfo"]["endCursor"].as_str(),
+            ) {
+                (Some(true), Some(cursor)) => variables["after"] = cursor.into(),
+                _ => break,
+            }
+        }
+        Ok(items)
+    }
+
+    /// `url` has this instance's scheme, host and port, no credentials, and
+    /// a path under its base path. A string prefix check would also accept
+    /// `https://gitlab.example.com.evil.net` or `...@evil.net`.
+    fn is_own_url(&self, url: &Url) -> bool {
+        let Ok(base) = Url::parse(&self.base_url) else {
+            return false;
+        };
+        let base_path = base.path().trim_end_matches('/');
+        url.scheme() == base.scheme()
+            && url.host_str() == base.host_str()
+            && url.port_or_known_default() == base.port_or_known_default()
+            && url.username().is_empty()
+            && url.password().is_none()
+            && url
+                .path()
+                .strip_prefix(base_path)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// Work out the next page from GitLab's pagination headers. `Link`
+    /// targets outside this instance are ignored so the token never leaves it.
+    fn next_page_url(&self, current: &Url, headers: &HeaderMap) -> Option<Url> {
+        let link = headers
+            .get_all(reqwest::header::LINK)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .find_map(|part| {
+                let (target, params) = part.split_once(';')?;
+                params
+                    .split(';')
+                    .any(|p| matches!(p.trim(), "rel=\"next\"" | "rel=next"))
+                    .then(|| target.trim().trim_start_matches('<').trim_end_matches('>'))
+            });
+        if let Some(url) = link.and_then(|u| Url::parse(u).ok()) {
+            if self.is_own_url(&url) {
+                return Some(url);
+            }
+            debug!(url = %url, "Ignoring a next-page link outside this instance");
+        }
+
+        let page = headers
+            .get("x-next-page")
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())?;
+        let mut next = current.clone();
+        let pairs: Vec<(String, String)> = current
+            .query_pairs()
+            .filter(|(k, _)| k != "page")
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        next.query_pairs_mut()
+            .clear()
+            .extend_pairs(pairs)
+            .append_pair("page", page);
+        Some(next)
     }
 
     #[instrument(skip(self, body))]
@@ -51,57 +464,332 @@ impl GitLabClient {
         &self,
         path: &str,
         body: serde_json::Value,
-    ) -> Result<T> {
-        let headers = AuthHeaders::new(&self.pat).to_header_map()?;
+    ) -> GitLabResult<T> {
+        let headers = self.auth_headers()?;
+        withhold("POST", &self.api_url(path), Some(body.clone()))?;
+        let resp = self
+            .send_write(
+                path,
+                self.client
+                    .post(self.api_url(path))
+                    .headers(headers)
+                    .json(&body),
+                false,
+            )
+            .await?;
+        Ok(resp.json::<T>().await?)
+    }
+
+    #[instrument(skip(self, body))]
+    pub async fn put<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> GitLabResult<T> {
+        let headers = self.auth_headers()?;
+        withhold("PUT", &self.api_url(path), Some(body.clone()))?;
         let resp = self
-            .client
-            .post(self.api_url(path))
-            .headers(headers)
-            .json(&body)
-            .send()
-            .await?
-            .error_for_status()?;
+            .send_write(
+                path,
+                self.client
+                    .put(self.api_url(path))
+                    .headers(headers)
+                    .json(&body),
+                true,
+            )
+            .await?;
         Ok(resp.json::<T>().await?)
     }
 
     #[instrument(skip(self, body))]
-    pub async fn put<T: DeserializeOwned>(&self, path: &str, body: serde_json::Value) -> Result<T> {
-        let headers = AuthHeaders::new(&self.pat).to_header_map()?;
+    pub async fn patch<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> GitLabResult<T> {
+        let headers = self.auth_headers()?;
+        withhold("PATCH", &self.api_url(path), Some(body.clone()))?;
         let resp = self
-            .client
-            .put(self.api_url(path))
-            .headers(headers)
-            .json(&body)
-            .send()
-            .await?
-            .error_for_status()?;
+            .send_write(
+                path,
+                self.client
+                    .patch(self.api_url(path))
+                    .headers(headers)
+                    .json(&body),
+                false,
+            )
+            .await?;
         Ok(resp.json::<T>().await?)
     }
 
-    pub async fn get_raw(&self, url: &str) -> Result<reqwest::Response> {
-        let headers = AuthHeaders::new(&self.pat).to_header_map()?;
-        Ok(self
-            .client
-            .get(url)
-            .headers(headers)
-            .send()
-            .await?
-            .error_for_status()?)
+    /// `DELETE`; GitLab answers most deletes with an empty 204. Only
+    /// retried after a 429: once a delete may have landed, a repeat would
+    /// answer 404 and report a success as a failure.
+    #[instrument(skip(self))]
+    pub async fn delete(&self, path: &str) -> GitLabResult<()> {
+        let headers = self.auth_headers()?;
+        withhold("DELETE", &self.api_url(path), None)?;
+        self.send_write(
+            path,
+            self.client.delete(self.api_url(path)).headers(headers),
+            false,
+        )
+        .await?;
+        Ok(())
     }
 
-    pub async fn post_stream(
+    /// `multipart/form-data` POST with `file` plus plain text `fields`, as
+    /// `/projects/:id/uploads` expects.
+    #[instrument(skip(self, file, fields), fields(filename = %file.filename, size = file.data.len()))]
+    pub async fn upload<T: DeserializeOwned>(
         &self,
-        url: &str,
-        body: serde_json::Value,
-    ) -> Result<reqwest::Response> {
-        let headers = AuthHeaders::new(&self.pat).to_header_map()?;
-        Ok(self
-            .client
-            .post(url)
-            .headers(headers)
-            .json(&body)
-            .send()
-            .await?
-            .error_for_status()?)
+        path: &str,
+        file: &UploadFile,
+        fields: &[(&str, &str)],
+    ) -> GitLabResult<T> {
+        let mut headers = self.auth_headers()?;
+        let boundary = format!("openduo-{:016x}", fastrand::u64(..));
+        let content_type = format!("multipart/form-data; boundary={}", boundary);
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_str(&content_type)
+                .map_err(|e| GitLabError::InvalidRequest(e.to_string()))?,
+        );
+        let mut summary = serde_json::json!({
+            "file": file.filename,
+            "size": file.data.len(),
+        });
+        for (name, value) in fields {
+            summary[*name] = (*value).into();
+        }
+        let body = multipart_body(&boundary, file, fields)?;
+        withhold("POST", &self.api_url(path), Some(summary))?;
+        let resp = self
+            .send_write(
+                path,
+                self.client
+                    .post(self.api_url(path))
+                    .headers(headers)
+                    .body(body),
+                false,
+            )
+            .await?;
+        Ok(resp.json::<T>().await?)
+    }
+
+    /// `PUT` a raw file body, as the generic package registry
+    /// (`/projects/:id/packages/generic/:name/:version/:file`) expects.
+    #[instrument(skip(self, file), fields(filename = %file.filename, size = file.data.len()))]
+    pub async fn put_file<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        file: &UploadFile,
+    ) -> GitLabResult<T> {
+        let mut headers = self.auth_headers()?;
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_str(&file.content_type)
+                .map_err(|e| GitLabError::InvalidRequest(e.to_string()))?,
+        );
+        let summary = serde_json::json!({
+            "file": file.filename,
+            "size": file.data.len(),
+            "content_type": file.content_type,
+        });
+        withhold("PUT", &self.api_url(path), Some(summary))?;
+        let resp = self
+            .send_write(
+                path,
+                self.client
+                    .put(self.api_url(path))
+                    .headers(headers)
+                    .body(file.data.clone()),
+                true,
+            )
+            .await?;
+        Ok(resp.json::<T>().await?)
+    }
+
+    pub async fn get_raw(&self, url: &str) -> GitLabResult<Response> {
+        let headers = self.auth_headers()?;
+        self.send(self.client.get(url).headers(headers), true).await
+    }
+
+    pub async fn post_stream(&self, url: &str, body: serde_json::Value) -> GitLabResult<Response> {
+        let headers = self.auth_headers()?;
+        self.send(self.client.post(url).headers(headers).json(&body), false)
+            .await
+    }
+
+    /// Send a write, dropping cached reads for its project before and after:
+    /// a read racing the write could otherwise cache the old state again.
+    async fn send_write(
+        &self,
+        path: &str,
+        request: RequestBuilder,
+        idempotent: bool,
+    ) -> GitLabResult<Response> {
+        self.invalidate(path);
+        let resp = self.send(request, idempotent).await;
+        self.invalidate(path);
+        resp
+    }
+
+    /// Drop cached reads for the project a write to `path` touches.
+    fn invalidate(&self, path: &str) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate_for(path.trim_start_matches('/'));
+        }
+    }
+
+    /// Send a request, retrying per `self.retry`. Idempotent requests are
+    /// retried on connection errors and transient statuses; anything else
+    /// only on 429, which GitLab returns before doing any work.
+    async fn send(&self, request: RequestBuilder, idempotent: bool) -> GitLabResult<Response> {
+        let mut attempt = 0;
+        loop {
+            let current = request.try_clone().ok_or_else(|| {
+                GitLabError::InvalidRequest("request body cannot be retried".into())
+            })?;
+            let can_retry = attempt < self.retry.max_retries;
+            let delay = match current.send().await {
+                Ok(resp) => {
+                    log_rate_limit(resp.headers());
+                    let status = resp.status();
+                    let retryable = status == StatusCode::TOO_MANY_REQUESTS
+                        || (idempotent && is_retryable_status(status));
+                    // 304 only comes back for our own `If-None-Match`.
+                    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+                        return Ok(resp);
+                    }
+                    if !retryable || !can_retry {
+                        return Err(GitLabError::from_response(resp).await);
+                    }
+                    let delay = match status {
+                        StatusCode::TOO_MANY_REQUESTS => server_delay(resp.headers())
+                            .unwrap_or_else(|| self.retry.backoff(attempt)),
+                        _ => self.retry.backoff(attempt),
+                    };
+                    if delay > self.retry.max_delay {
+                        warn!(
+                            status = status.as_u16(),
+                            wait_secs = delay.as_secs(),
+                            "GitLab asked for a longer pause than the retry policy allows"
+                        );
+                        return Err(GitLabError::from_response(resp).await);
+                    }
+                    warn!(
+                        status = status.as_u16(),
+                        attempt = attempt + 1,
+                        delay_ms = delay.as_millis() as u64,
+                        "retrying GitLab request"
+                    );
+                    delay
+                }
+                Err(e) if idempotent && can_retry && (e.is_connect() || e.is_timeout()) => {
+                    let delay = self.retry.backoff(attempt);
+                    warn!(
+                        error = %e,
+                        attempt = attempt + 1,
+                        delay_ms = delay.as_millis() as u64,
+                        "retrying GitLab request"
+                    );
+                    delay
+                }
+                Err(e) => return Err(e.into()),
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// A file to send with `upload` or `put_file`.
+#[derive(Debug, Clone)]
+pub struct UploadFile {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+impl UploadFile {
+    pub fn new(filename: impl Into<String>, data: Vec<u8>) -> Self {
+        Self {
+            filename: filename.into(),
+            content_type: "application/octet-stream".to_string(),
+            data,
+        }
+    }
+
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+}
+
+/// Built by hand rather than with `reqwest::multipart` so the body stays
+/// cloneable and `send` can retry it after a 429. The file name and content
+/// type may come from the model, so anything that could end a part header
+/// early is refused.
+fn multipart_body(
+    boundary: &str,
+    file: &UploadFile,
+    fields: &[(&str, &str)],
+) -> GitLabResult<Vec<u8>> {
+    if file.filename.chars().any(char::is_control) {
+        return Err(GitLabError::InvalidRequest(
+            "file name contains control characters".into(),
+        ));
+    }
+    HeaderValue::from_str(&file.content_type).map_err(|_| {
+        GitLabError::InvalidRequest(format!("invalid content type: {:?}", file.content_type))
+    })?;
+    let quote = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                boundary,
+                quote(name),
+                value
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+             Content-Type: {}\r\n\r\n",
+            boundary,
+            quote(&file.filename),
+            file.content_type
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(&file.data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    Ok(body)
+}
+
+fn decode<T: DeserializeOwned>(body: &[u8]) -> GitLabResult<T> {
+    serde_json::from_slice(body).map_err(|e| GitLabError::Decode(e.to_string()))
+}
+
+fn log_rate_limit(headers: &HeaderMap) {
+    if let Some(rl) = RateLimit::from_headers(headers) {
+        if rl.is_low() {
+            warn!(
+                remaining = rl.remaining,
+                limit = rl.limit,
+                "GitLab rate limit nearly exhausted"
+            );
+        } else {
+            debug!(
+                remaining = rl.remaining,
+                limit = rl.limit,
+                "GitLab rate limit"
+            );
+        }
     }
 }
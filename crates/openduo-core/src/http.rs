@@ -0,0 +1,102 @@
+//! The one place outbound `reqwest::Client`s are built, so the GitLab client
+//! and every model provider share the same TLS, proxy and timeout settings.
+
+use anyhow::{anyhow, Result};
+use reqwest::{Certificate, Client, Identity, NoProxy, Proxy};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Outbound HTTP settings shared by the GitLab client and model providers.
+#[derive(Debug, Clone, Default)]
+pub struct HttpSettings {
+    /// Proxy for all outbound traffic (`OPENDUO_PROXY`).
+    pub proxy: Option<String>,
+    /// Proxy for `https://` targets when `proxy` is unset (`HTTPS_PROXY`).
+    pub https_proxy: Option<String>,
+    /// Proxy for `http://` targets when `proxy` is unset (`HTTP_PROXY`).
+    pub http_proxy: Option<String>,
+    /// Comma-separated hosts, domains and CIDRs that bypass the proxy.
+    pub no_proxy: Option<String>,
+    pub connect_timeout: Option<Duration>,
+    /// Longest gap between reads; bounds stalled streams without capping
+    /// how long a streamed answer may take overall.
+    pub read_timeout: Option<Duration>,
+    /// PEM bundles trusted in addition to the system roots.
+    pub extra_ca_certs: Vec<PathBuf>,
+    /// Client certificate for servers behind mutual TLS.
+    pub client_identity: Option<ClientIdentity>,
+}
+
+/// A PEM certificate chain and its PKCS#8 PEM private key.
+#[derive(Debug, Clone)]
+pub struct ClientIdentity {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl HttpSettings {
+    /// Build a client from these settings. Only the proxies named here are
+    /// used; reqwest's own environment lookup is turned off so what
+    /// `Config` resolved is what runs.
+    pub fn build_client(&self) -> Result<Client> {
+        let mut builder = Client::builder().use_native_tls().no_proxy();
+        for proxy in self.proxies()? {
+            builder = builder.proxy(proxy);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        for path in &self.extra_ca_certs {
+            let pem = read(path, "CA bundle")?;
+            for cert in Certificate::from_pem_bundle(&pem)
+                .map_err(|e| anyhow!("invalid CA bundle {}: {}", path.display(), e))?
+            {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if let Some(identity) = &self.client_identity {
+            builder = builder.identity(identity.load()?);
+        }
+        builder
+            .build()
+            .map_err(|e| anyhow!("Failed to build reqwest client: {}", e))
+    }
+
+    fn proxies(&self) -> Result<Vec<Proxy>> {
+        let no_proxy = || self.no_proxy.as_deref().and_then(NoProxy::from_string);
+        let mut proxies = Vec::new();
+        if let Some(url) = &self.proxy {
+            proxies.push(Proxy::all(url)?.no_proxy(no_proxy()));
+        } else {
+            if let Some(url) = &self.https_proxy {
+                proxies.push(Proxy::https(url)?.no_proxy(no_proxy()));
+            }
+            if let Some(url) = &self.http_proxy {
+                proxies.push(Proxy::http(url)?.no_proxy(no_proxy()));
+            }
+        }
+        Ok(proxies)
+    }
+}
+
+impl ClientIdentity {
+    pub fn load(&self) -> Result<Identity> {
+        let cert = read(&self.cert, "client certificate")?;
+        let key = read(&self.key, "client key")?;
+        Identity::from_pkcs8_pem(&cert, &key).map_err(|e| {
+            anyhow!(
+                "invalid client certificate {} / key {} (the key must be PKCS#8 PEM): {}",
+                self.cert.display(),
+                self.key.display(),
+                e
+            )
+        })
+    }
+}
+
+fn read(path: &Path, what: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| anyhow!("cannot read {} {}: {}", what, path.display(), e))
+}
@@ -1,4 +1,12 @@
 pub mod auth;
+pub mod cache;
+pub mod capabilities;
 pub mod config;
+pub mod config_file;
+pub mod error;
 pub mod gitlab_client;
+pub mod http;
+pub mod redact;
+pub mod retry;
+pub mod token;
 pub mod types;
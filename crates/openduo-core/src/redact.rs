@@ -0,0 +1,104 @@
+//! Scrubs credentials from text before it is logged or handed to the model.
+//! Job logs in particular leak tokens that masked CI variables missed.
+
+use anyhow::{anyhow, Result};
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+
+/// What a redacted secret is replaced with.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Built-in patterns. Where a pattern has a `secret` group only that group
+/// is replaced, so the surrounding key or header name stays readable.
+const BUILTIN_PATTERNS: &[&str] = &[
+    // GitLab personal, deploy, runner, trigger, CI job, feed, OAuth and
+    // agent tokens.
+    r"\b(?:glpat|gldt|glrt|glptt|glcbt|glft|glimt|glagent|gloas|glsoat|glffct)-[0-9A-Za-z_.\-]{16,}",
+    // Legacy runner registration tokens.
+    r"\bGR1348941[0-9A-Za-z_\-]{20,}",
+    // AWS access key ids and secret access keys.
+    r"\b(?:AKIA|ASIA|ABIA|ACCA)[0-9A-Z]{16}\b",
+    r#"(?i)aws_?secret_?access_?key["']?\s*[:=]\s*["']?(?P<secret>[A-Za-z0-9/+=]{40})"#,
+    // Google API keys and OAuth access tokens.
+    r"\bAIza[0-9A-Za-z_\-]{35}",
+    r"\bya29\.[0-9A-Za-z_\-]{20,}",
+    // PEM private keys, including the `\n`-escaped form in JSON key files.
+    r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----",
+    // Authorization headers and GitLab's token headers. The scheme must
+    // follow a header name so prose like "basic implementation" is kept.
+    r#"(?i:authorization)["']?[ \t]*[:=][ \t]*["']?(?:Bearer|Basic)[ \t]+(?P<secret>[A-Za-z0-9\-._~+/]{12,}=*)"#,
+    r"(?i)\b(?:private-token|job-token|deploy-token|x-api-key)\s*[:=]\s*(?P<secret>[^\s,;]+)",
+    // Credentials embedded in URLs.
+    r"(?i)\b[a-z][a-z0-9+.\-]*://[^:/\s@]+:(?P<secret>[^@\s/]+)@",
+    // `password = ...`, `"client_secret": "..."` and similar assignments on
+    // one line. The key must end in the keyword, so `password_expires_at`
+    // and `secret_detection:` are not secrets; `true`, `false` and `null`
+    // are left alone too (see `replace_secret`).
+    r#"(?i)\b[\w\-]*(?:password|passwd|secret)["']?[ \t]*[:=][ \t]*["']?(?P<secret>[^\s"',;]{4,})"#,
+];
+
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    /// The built-in patterns plus `extra`, e.g. from `redaction.patterns`.
+    pub fn new(extra: &[String]) -> Result<Self> {
+        let mut patterns: Vec<Regex> = BUILTIN_PATTERNS
+            .iter()
+            .map(|p| Regex::new(p).expect("built-in redaction pattern"))
+            .collect();
+        for pattern in extra {
+            patterns.push(
+                Regex::new(pattern)
+                    .map_err(|e| anyhow!("invalid redaction pattern '{}': {}", pattern, e))?,
+            );
+        }
+        Ok(Self { patterns })
+    }
+
+    /// `text` with every match replaced by `[REDACTED]`; borrowed when
+    /// nothing matched.
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for pattern in &self.patterns {
+            if let Cow::Owned(replaced) = pattern.replace_all(&text, replace_secret) {
+                // Matches `replace_secret` kept as they were change nothing.
+                if replaced != *text {
+                    text = Cow::Owned(replaced);
+                }
+            }
+        }
+        text
+    }
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new(&[]).expect("built-in redaction patterns")
+    }
+}
+
+fn replace_secret(caps: &Captures) -> String {
+    let whole = caps.get(0).expect("group 0 always matches");
+    match caps.name("secret") {
+        // A JSON or YAML literal, not a credential; the match runs on into
+        // a closing bracket when the value ends an object or array.
+        Some(secret)
+            if matches!(
+                secret.as_str().trim_end_matches(['}', ']']),
+                "true" | "false" | "null"
+            ) =>
+        {
+            whole.as_str().to_string()
+        }
+        Some(secret) => {
+            let text = whole.as_str();
+            let start = secret.start() - whole.start();
+            let end = secret.end() - whole.start();
+            format!("{}{}{}", &text[..start], REDACTED, &text[end..])
+        }
+        None => REDACTED.to_string(),
+    }
+}
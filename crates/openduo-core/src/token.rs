@@ -0,0 +1,90 @@
+//! What the configured PAT is allowed to do, read from GitLab at startup so
+//! problems surface on `/health` rather than at the first failing tool call.
+
+use crate::error::{GitLabError, GitLabResult};
+use crate::gitlab_client::GitLabClient;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Scopes the README asks for: `api` for tools, `read_user` for `/user`,
+/// `ai_features` for Duo Chat.
+pub const REQUIRED_SCOPES: &[&str] = &["api", "read_user", "ai_features"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenStatus {
+    pub username: String,
+    pub token_name: Option<String>,
+    /// `None` when this GitLab predates `/personal_access_tokens/self`.
+    pub scopes: Option<Vec<String>>,
+    /// `YYYY-MM-DD`, or `None` for a token without expiry.
+    pub expires_at: Option<String>,
+    pub expires_in_days: Option<i64>,
+    pub missing_scopes: Vec<String>,
+    /// The token has `read_api` but not `api`, so write tools cannot work.
+    pub read_only: bool,
+}
+
+/// Look up the token behind `client` and the user it belongs to.
+pub async fn introspect(client: &GitLabClient) -> GitLabResult<TokenStatus> {
+    let token = match client.get::<Value>("personal_access_tokens/self").await {
+        Ok(token) => Some(token),
+        Err(GitLabError::NotFound { .. }) => None,
+        Err(e) => return Err(e),
+    };
+    let user: Value = client.get("user").await?;
+
+    let scopes: Option<Vec<String>> = token.as_ref().and_then(|t| {
+        t["scopes"].as_array().map(|scopes| {
+            scopes
+                .iter()
+                .filter_map(|s| s.as_str().map(str::to_string))
+                .collect()
+        })
+    });
+    let has = |scope: &str| scopes.iter().flatten().any(|s| s == scope);
+    let expires_at = token
+        .as_ref()
+        .and_then(|t| t["expires_at"].as_str())
+        .map(str::to_string);
+
+    Ok(TokenStatus {
+        username: user["username"].as_str().unwrap_or_default().to_string(),
+        token_name: token
+            .as_ref()
+            .and_then(|t| t["name"].as_str())
+            .map(str::to_string),
+        missing_scopes: match &scopes {
+            Some(_) => REQUIRED_SCOPES
+                .iter()
+                .filter(|s| !has(s))
+                .map(|s| s.to_string())
+                .collect(),
+            None => Vec::new(),
+        },
+        read_only: has("read_api") && !has("api"),
+        expires_in_days: expires_at.as_deref().and_then(days_until),
+        expires_at,
+        scopes,
+    })
+}
+
+/// Days from today (UTC) until a `YYYY-MM-DD` date; negative once it passed.
+pub fn days_until(date: &str) -> Option<i64> {
+    let mut parts = date.get(..10)?.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    let today = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() as i64 / 86_400;
+    Some(days_from_civil(year, month, day) - today)
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
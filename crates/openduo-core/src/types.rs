@@ -1,4 +1,5 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolDefinition {
@@ -6,3 +7,396 @@ pub struct ToolDefinition {
     pub description: String,
     pub parameters: serde_json::Value,
 }
+
+// GitLab resource models. Deserialization is lenient so that responses from
+// older or newer GitLab releases still parse: missing fields and `null` take
+// their default, unknown fields are ignored, ids may arrive as strings or
+// GraphQL global ids, and `labels` may be names or label objects. Only the
+// fields tools and the UI use are kept, and empty optionals are omitted when
+// serializing, so a typed value is also a compact summary of the response.
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct User {
+    #[serde(deserialize_with = "lenient_id")]
+    pub id: u64,
+    #[serde(deserialize_with = "null_default")]
+    pub username: String,
+    #[serde(deserialize_with = "null_default")]
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Project {
+    #[serde(deserialize_with = "lenient_id")]
+    pub id: u64,
+    #[serde(deserialize_with = "null_default")]
+    pub name: String,
+    #[serde(deserialize_with = "null_default")]
+    pub path_with_namespace: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// `None` for an empty repository.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<Namespace>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_url_to_repo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssh_url_to_repo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_activity_at: Option<String>,
+}
+
+/// The group or user a project belongs to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Namespace {
+    #[serde(deserialize_with = "lenient_id")]
+    pub id: u64,
+    #[serde(deserialize_with = "null_default")]
+    pub name: String,
+    #[serde(deserialize_with = "null_default")]
+    pub full_path: String,
+    /// `group` or `user`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Label {
+    #[serde(deserialize_with = "lenient_id")]
+    pub id: u64,
+    #[serde(deserialize_with = "null_default")]
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Milestone {
+    #[serde(deserialize_with = "lenient_id")]
+    pub id: u64,
+    #[serde(deserialize_with = "lenient_id")]
+    pub iid: u64,
+    #[serde(deserialize_with = "null_default")]
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(deserialize_with = "null_default")]
+    pub state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Issue {
+    #[serde(deserialize_with = "lenient_id")]
+    pub id: u64,
+    #[serde(deserialize_with = "lenient_id")]
+    pub iid: u64,
+    #[serde(deserialize_with = "lenient_id")]
+    pub project_id: u64,
+    #[serde(deserialize_with = "null_default")]
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(deserialize_with = "null_default")]
+    pub state: String,
+    #[serde(
+        deserialize_with = "label_names",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub labels: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<User>,
+    #[serde(
+        deserialize_with = "null_default",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub assignees: Vec<User>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub milestone: Option<Milestone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MergeRequest {
+    #[serde(deserialize_with = "lenient_id")]
+    pub id: u64,
+    #[serde(deserialize_with = "lenient_id")]
+    pub iid: u64,
+    #[serde(deserialize_with = "lenient_id")]
+    pub project_id: u64,
+    #[serde(deserialize_with = "null_default")]
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(deserialize_with = "null_default")]
+    pub state: String,
+    #[serde(deserialize_with = "null_default")]
+    pub source_branch: String,
+    #[serde(deserialize_with = "null_default")]
+    pub target_branch: String,
+    #[serde(deserialize_with = "null_default")]
+    pub draft: bool,
+    /// What GitLab 13 and older send instead of `draft`; see `is_draft`.
+    #[serde(deserialize_with = "null_default", skip_serializing_if = "is_false")]
+    pub work_in_progress: bool,
+    #[serde(
+        deserialize_with = "label_names",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub labels: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<User>,
+    #[serde(
+        deserialize_with = "null_default",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub assignees: Vec<User>,
+    #[serde(
+        deserialize_with = "null_default",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub reviewers: Vec<User>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub milestone: Option<Milestone>,
+    /// Deprecated by GitLab in favour of `detailed_merge_status`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detailed_merge_status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_conflicts: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocking_discussions_resolved: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_when_pipeline_succeeds: Option<bool>,
+    /// Only on single merge request responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head_pipeline: Option<Pipeline>,
+    /// Only on single merge request responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_refs: Option<DiffRefs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merged_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_url: Option<String>,
+}
+
+impl MergeRequest {
+    pub fn is_draft(&self) -> bool {
+        self.draft || self.work_in_progress
+    }
+}
+
+/// The commits a merge request's diff is computed between.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiffRefs {
+    #[serde(deserialize_with = "null_default")]
+    pub base_sha: String,
+    #[serde(deserialize_with = "null_default")]
+    pub head_sha: String,
+    #[serde(deserialize_with = "null_default")]
+    pub start_sha: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pipeline {
+    #[serde(deserialize_with = "lenient_id")]
+    pub id: u64,
+    /// Project-scoped number, sent since GitLab 12.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iid: Option<u64>,
+    #[serde(deserialize_with = "lenient_id")]
+    pub project_id: u64,
+    #[serde(deserialize_with = "null_default")]
+    pub status: String,
+    #[serde(rename = "ref", deserialize_with = "null_default")]
+    pub git_ref: String,
+    #[serde(deserialize_with = "null_default")]
+    pub sha: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Seconds; only on single-pipeline responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    /// Percentage as a decimal string, e.g. `"87.5"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<User>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Job {
+    #[serde(deserialize_with = "lenient_id")]
+    pub id: u64,
+    #[serde(deserialize_with = "null_default")]
+    pub name: String,
+    #[serde(deserialize_with = "null_default")]
+    pub stage: String,
+    #[serde(deserialize_with = "null_default")]
+    pub status: String,
+    #[serde(rename = "ref", deserialize_with = "null_default")]
+    pub git_ref: String,
+    /// GitLab nests the pipeline; only its id is kept.
+    #[serde(
+        rename = "pipeline",
+        deserialize_with = "nested_id",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub pipeline_id: Option<u64>,
+    #[serde(deserialize_with = "null_default")]
+    pub allow_failure: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Commit {
+    /// The full SHA.
+    #[serde(deserialize_with = "null_default")]
+    pub id: String,
+    #[serde(deserialize_with = "null_default")]
+    pub short_id: String,
+    #[serde(deserialize_with = "null_default")]
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(deserialize_with = "null_default")]
+    pub author_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(
+        deserialize_with = "null_default",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub parent_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Note {
+    #[serde(deserialize_with = "lenient_id")]
+    pub id: u64,
+    #[serde(deserialize_with = "null_default")]
+    pub body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<User>,
+    /// Generated by GitLab, e.g. "changed the description".
+    #[serde(deserialize_with = "null_default")]
+    pub system: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noteable_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noteable_iid: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// `null` as the type's default.
+fn null_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// A numeric id sent as a number, a numeric string or a GraphQL global id
+/// like `gid://gitlab/Issue/12`. Anything else is 0.
+fn lenient_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    Ok(id_from(&Value::deserialize(deserializer)?).unwrap_or_default())
+}
+
+/// The `id` of a nested object such as a job's `pipeline`.
+fn nested_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    Ok(id_from(value.get("id").unwrap_or(&value)))
+}
+
+fn id_from(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.rsplit('/').next()?.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Label names from either `["bug"]` or, with `with_labels_details=true`,
+/// `[{"name": "bug", ...}]`.
+fn label_names<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let labels = Option::<Vec<Value>>::deserialize(deserializer)?.unwrap_or_default();
+    Ok(labels
+        .iter()
+        .filter_map(|label| match label {
+            Value::String(name) => Some(name.clone()),
+            other => other["name"].as_str().map(str::to_string),
+        })
+        .collect())
+}
@@ -0,0 +1,105 @@
+use openduo_core::cache::{CacheSettings, CacheStats};
+use openduo_core::config::Config;
+use openduo_core::gitlab_client::GitLabClient;
+use openduo_test_support::fixtures::{PROJECT_ID, PROJECT_PATH};
+use openduo_test_support::MockGitLab;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+fn cached_client(mock: &MockGitLab, cache: CacheSettings) -> GitLabClient {
+    GitLabClient::new(Config {
+        cache,
+        ..mock.config()
+    })
+    .unwrap()
+}
+
+fn enabled() -> CacheSettings {
+    CacheSettings {
+        enabled: true,
+        ..CacheSettings::default()
+    }
+}
+
+#[tokio::test]
+async fn test_cache_is_off_by_default() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let _: Value = client.get("projects/42").await.unwrap();
+    let _: Value = client.get("projects/42").await.unwrap();
+    assert_eq!(mock.requests().len(), 2);
+    assert!(client.cache_stats().is_none());
+}
+
+#[tokio::test]
+async fn test_repeated_reads_are_served_from_cache() {
+    let mock = MockGitLab::start().await;
+    let client = cached_client(&mock, enabled());
+    let first: Value = client.get("projects/42").await.unwrap();
+    let second: Value = client.clone().get("projects/42").await.unwrap();
+    assert_eq!(first, second);
+    assert_eq!(mock.requests().len(), 1);
+    assert_eq!(
+        client.cache_stats().unwrap(),
+        CacheStats {
+            entries: 1,
+            hits: 1,
+            misses: 1,
+            ..CacheStats::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_expired_entries_are_revalidated_with_etag() {
+    let mock = MockGitLab::start().await;
+    let mut cache = enabled();
+    cache.ttls.insert("projects".into(), Duration::ZERO);
+    let client = cached_client(&mock, cache);
+
+    let _: Value = client.get("projects/42").await.unwrap();
+    let again: Value = client.get("projects/42").await.unwrap();
+    assert_eq!(again["id"], PROJECT_ID);
+    assert_eq!(mock.requests().len(), 2);
+    assert_eq!(client.cache_stats().unwrap().revalidations, 1);
+
+    mock.with_data(|d| d.projects[0]["description"] = json!("Changed"));
+    let changed: Value = client.get("projects/42").await.unwrap();
+    assert_eq!(changed["description"], "Changed");
+    assert_eq!(client.cache_stats().unwrap().revalidations, 1);
+}
+
+#[tokio::test]
+async fn test_writes_invalidate_the_project_by_id_or_path() {
+    let mock = MockGitLab::start().await;
+    let client = cached_client(&mock, enabled());
+    let _: Value = client.get("projects/42").await.unwrap();
+    let _: Value = client.get("projects/42/issues/1").await.unwrap();
+    let _: Value = client.get("projects/43").await.unwrap();
+    let _: Value = client.get("user").await.unwrap();
+
+    let path = format!("projects/{}/issues", PROJECT_PATH.replace('/', "%2F"));
+    let _: Value = client
+        .post(&path, json!({ "title": "Cache buster" }))
+        .await
+        .unwrap();
+    let stats = client.cache_stats().unwrap();
+    assert_eq!(stats.invalidations, 2);
+    assert_eq!(stats.entries, 2);
+
+    let requests = mock.requests().len();
+    let _: Value = client.get("projects/42/issues/1").await.unwrap();
+    let _: Value = client.get("projects/43").await.unwrap();
+    assert_eq!(mock.requests().len(), requests + 1);
+}
+
+#[tokio::test]
+async fn test_not_modified_without_a_cached_body_refetches() {
+    let mock = MockGitLab::start().await;
+    let client = cached_client(&mock, enabled());
+    // As if the entry were evicted between the lookup and the answer.
+    mock.fail_next(304, &[]);
+    let project: Value = client.get("projects/42").await.unwrap();
+    assert_eq!(project["id"], PROJECT_ID);
+    assert_eq!(mock.requests().len(), 2);
+}
@@ -0,0 +1,80 @@
+use openduo_core::capabilities::{probe, Capability};
+use openduo_core::gitlab_client::GitLabClient;
+use openduo_test_support::MockGitLab;
+use serde_json::json;
+
+#[tokio::test]
+async fn test_probe_detects_licensed_features() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let caps = probe(&client).await.unwrap();
+    assert_eq!(caps.version, "17.5.0-ee");
+    assert_eq!(caps.version_number(), Some((17, 5)));
+    assert!(caps.enterprise);
+    for capability in Capability::ALL {
+        assert!(caps.supports(*capability), "{capability}");
+    }
+}
+
+#[tokio::test]
+async fn test_probe_community_edition_has_no_tiered_features() {
+    let mock = MockGitLab::start().await;
+    mock.with_data(|d| {
+        d.metadata = json!({ "version": "17.5.0", "revision": "abc", "enterprise": false })
+    });
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let caps = probe(&client).await.unwrap();
+    assert!(!caps.enterprise);
+    assert!(caps.features.is_empty());
+    // Community Edition is not probed for features.
+    assert!(!mock.requests().iter().any(|r| r.path.contains("epics")));
+}
+
+#[tokio::test]
+async fn test_probe_treats_forbidden_feature_as_unavailable() {
+    let mock = MockGitLab::start().await;
+    mock.with_data(|d| d.licensed = vec![Capability::Epics, Capability::MergeTrains]);
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let caps = probe(&client).await.unwrap();
+    assert!(caps.supports(Capability::Epics));
+    assert!(caps.supports(Capability::MergeTrains));
+    assert!(!caps.supports(Capability::Vulnerabilities));
+    assert!(!caps.supports(Capability::DuoChat));
+    assert!(caps.unknown.is_empty());
+}
+
+#[tokio::test]
+async fn test_probe_marks_features_unknown_without_a_group_or_project() {
+    let mock = MockGitLab::start().await;
+    mock.with_data(|d| {
+        d.groups.clear();
+        d.projects.clear();
+    });
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let caps = probe(&client).await.unwrap();
+    assert_eq!(
+        caps.unknown.iter().copied().collect::<Vec<_>>(),
+        [
+            Capability::Epics,
+            Capability::MergeTrains,
+            Capability::Vulnerabilities
+        ]
+    );
+    assert!(!caps.supports(Capability::Epics));
+    assert!(caps.supports(Capability::DuoChat));
+}
+
+#[tokio::test]
+async fn test_probe_falls_back_to_version_endpoint() {
+    let mock = MockGitLab::start().await;
+    mock.with_data(|d| {
+        d.metadata_missing = true;
+        d.metadata = json!({ "version": "14.10.5-ee", "revision": "def" });
+    });
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let caps = probe(&client).await.unwrap();
+    assert_eq!(caps.version, "14.10.5-ee");
+    assert_eq!(caps.revision.as_deref(), Some("def"));
+    assert!(caps.enterprise);
+    assert!(mock.requests().iter().any(|r| r.path == "version"));
+}
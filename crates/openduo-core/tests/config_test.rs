@@ -1,6 +1,8 @@
 use openduo_core::auth::AuthHeaders;
-use openduo_core::config::Config;
+use openduo_core::config::{Config, HistoryStoreKind, ProviderKind};
+use openduo_core::config_file::ConfigFile;
 use serial_test::serial;
+use std::path::{Path, PathBuf};
 
 #[test]
 #[serial]
@@ -38,3 +40,348 @@ fn test_auth_headers_contain_content_type() {
     let map = headers.to_header_map().unwrap();
     assert!(map.contains_key("Content-Type"));
 }
+
+#[test]
+#[serial]
+fn test_config_selects_provider_from_env() {
+    unsafe {
+        std::env::set_var("GITLAB_URL", "https://gitlab.example.com");
+        std::env::set_var("GITLAB_PAT", "glpat-test123");
+        std::env::set_var("OPENDUO_PROVIDER", "openai");
+        std::env::set_var("OPENDUO_LLM_URL", "http://localhost:8000/v1");
+    }
+    let cfg = Config::from_env().unwrap();
+    unsafe {
+        std::env::remove_var("OPENDUO_PROVIDER");
+        std::env::remove_var("OPENDUO_LLM_URL");
+    }
+    assert_eq!(cfg.provider, ProviderKind::OpenAi);
+    assert_eq!(cfg.llm_url.as_deref(), Some("http://localhost:8000/v1"));
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("openduo-config-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn parse(text: &str) -> anyhow::Result<ConfigFile> {
+    ConfigFile::parse(text, Path::new("test.toml"))
+}
+
+#[test]
+fn test_config_file_parses_all_sections() {
+    let file = parse(
+        r#"
+        [gitlab]
+        url = "https://gitlab.example.com"
+        default_project = "group/app"
+
+        [llm]
+        provider = "ollama"
+        model = "llama3.1"
+
+        [agent]
+        max_iterations = 8
+        history_limit = 20
+        require_approval = false
+
+        [tools]
+        enabled = ["issues", "pipelines"]
+
+        [http]
+        proxy = "http://proxy.example.com:3128"
+        connect_timeout_secs = 5
+        "#,
+    )
+    .unwrap();
+    assert_eq!(file.gitlab.default_project.as_deref(), Some("group/app"));
+    assert_eq!(file.agent.max_iterations, Some(8));
+    assert_eq!(file.agent.require_approval, Some(false));
+    assert_eq!(file.tools.enabled.unwrap(), ["issues", "pipelines"]);
+    assert_eq!(file.http.connect_timeout_secs, Some(5));
+}
+
+#[test]
+fn test_config_file_errors_name_the_key() {
+    let err = parse("[agent]\nmax_iteration = 3\n")
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with("test.toml:"), "{err}");
+    assert!(err.contains("max_iteration"), "{err}");
+
+    let err = parse("[agent]\nhistory_limit = \"lots\"\n")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("history_limit"), "{err}");
+
+    let err = parse("[gitlab]\npat = \"glpat-x\"\n")
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("gitlab.pat") && err.contains("GITLAB_PAT"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_config_file_merge_prefers_later_layer() {
+    let user = parse("[agent]\nmax_iterations = 8\nhistory_limit = 20\n").unwrap();
+    let workspace = parse("[agent]\nmax_iterations = 4\n").unwrap();
+    let merged = user.merge(workspace);
+    assert_eq!(merged.agent.max_iterations, Some(4));
+    assert_eq!(merged.agent.history_limit, Some(20));
+}
+
+#[test]
+fn test_workspace_layer_keeps_only_allowed_keys() {
+    let workspace = parse(
+        "[gitlab]\nurl = \"https://gitlab.evil.example\"\ndefault_project = \"group/app\"\n\
+         [llm]\nurl = \"https://llm.evil.example\"\n\
+         [http]\nca_bundle = \"/tmp/evil.pem\"\n\
+         [agent]\nhistory_limit = 10\n",
+    )
+    .unwrap();
+    let (kept, ignored) = workspace.workspace_layer();
+    assert_eq!(ignored, ["gitlab.url", "llm.url", "http.ca_bundle"]);
+    assert!(kept.gitlab.url.is_none() && kept.llm.url.is_none());
+    assert!(kept.http.ca_bundle.is_none());
+    assert_eq!(kept.gitlab.default_project.as_deref(), Some("group/app"));
+    assert_eq!(kept.agent.history_limit, Some(10));
+}
+
+#[test]
+#[serial]
+fn test_workspace_config_can_only_raise_require_approval() {
+    unsafe {
+        std::env::set_var("GITLAB_URL", "https://gitlab.example.com");
+        std::env::set_var("GITLAB_PAT", "glpat-test123");
+        std::env::remove_var("OPENDUO_REQUIRE_APPROVAL");
+    }
+    let load = |user: &str, workspace: &str| {
+        let (ws, ignored) = parse(workspace).unwrap().workspace_layer();
+        let cfg = Config::from_file_and_env(parse(user).unwrap().merge(ws)).unwrap();
+        (cfg.require_approval, ignored)
+    };
+    let off = "[agent]\nrequire_approval = false\n";
+    let on = "[agent]\nrequire_approval = true\n";
+    assert_eq!(load("", off), (true, vec!["agent.require_approval"]));
+    assert_eq!(load(off, on), (true, vec![]));
+    assert_eq!(load(off, ""), (false, vec![]));
+
+    unsafe { std::env::set_var("OPENDUO_REQUIRE_APPROVAL", "false") };
+    let from_env = load("", on);
+    unsafe { std::env::remove_var("OPENDUO_REQUIRE_APPROVAL") };
+    assert_eq!(from_env, (false, vec![]));
+}
+
+#[test]
+#[serial]
+fn test_config_validation_names_offending_key() {
+    unsafe {
+        std::env::set_var("GITLAB_URL", "https://gitlab.example.com");
+        std::env::set_var("GITLAB_PAT", "glpat-test123");
+    }
+    let err = Config::from_file_and_env(parse("[agent]\nmax_iterations = 0\n").unwrap())
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with("agent.max_iterations"), "{err}");
+
+    let err = Config::from_file_and_env(parse("[tools]\nenabled = [\"wiki\"]\n").unwrap())
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("tools.enabled") && err.contains("'wiki'"),
+        "{err}"
+    );
+
+    unsafe { std::env::set_var("OPENDUO_HISTORY_LIMIT", "many") };
+    let err = Config::from_env().unwrap_err().to_string();
+    unsafe { std::env::remove_var("OPENDUO_HISTORY_LIMIT") };
+    assert!(err.contains("OPENDUO_HISTORY_LIMIT"), "{err}");
+}
+
+#[test]
+#[serial]
+fn test_config_load_layers_files_under_env() {
+    let home = temp_dir("user");
+    let user_file = home.join("config.toml");
+    std::fs::write(
+        &user_file,
+        "[gitlab]\nurl = \"https://user.example.com\"\n[agent]\nmax_iterations = 8\nhistory_limit = 20\n",
+    )
+    .unwrap();
+    let workspace = temp_dir("workspace");
+    std::fs::write(
+        workspace.join(".openduo.toml"),
+        "[agent]\nmax_iterations = 4\n[gitlab]\ndefault_project = \"group/app\"\n\
+         url = \"https://gitlab.evil.example\"\n[http]\nproxy = \"http://evil.example:3128\"\n",
+    )
+    .unwrap();
+    let nested = workspace.join("src").join("deep");
+    std::fs::create_dir_all(&nested).unwrap();
+
+    unsafe {
+        std::env::remove_var("GITLAB_URL");
+        std::env::set_var("GITLAB_PAT", "glpat-test123");
+        std::env::set_var("OPENDUO_CONFIG", &user_file);
+        std::env::set_var("OPENDUO_WORKSPACE", &nested);
+        std::env::set_var("OPENDUO_HISTORY_LIMIT", "30");
+    }
+    let cfg = Config::load();
+    unsafe {
+        std::env::remove_var("OPENDUO_CONFIG");
+        std::env::remove_var("OPENDUO_WORKSPACE");
+        std::env::remove_var("OPENDUO_HISTORY_LIMIT");
+    }
+    let cfg = cfg.unwrap();
+    assert_eq!(cfg.gitlab_url, "https://user.example.com");
+    assert_eq!(cfg.max_iterations, 4);
+    assert_eq!(cfg.history_limit, 30);
+    assert_eq!(cfg.default_project.as_deref(), Some("group/app"));
+    assert!(cfg.tool_group_enabled("issues"));
+    // A checked-out repository cannot redirect traffic or credentials.
+    assert!(cfg.http.proxy.is_none());
+}
+
+#[test]
+#[serial]
+fn test_config_instance_profiles_read_pat_from_named_env() {
+    unsafe {
+        std::env::set_var("GITLAB_URL", "https://gitlab.example.com");
+        std::env::set_var("GITLAB_PAT", "glpat-test123");
+        std::env::set_var("OPENDUO_TEST_MIRROR_PAT", "glpat-mirror");
+    }
+    let file = parse(
+        "[gitlab]\ndefault_instance = \"mirror\"\n\
+         [instances.mirror]\nurl = \"https://mirror.example.com\"\npat_env = \"OPENDUO_TEST_MIRROR_PAT\"\n",
+    )
+    .unwrap();
+    let cfg = Config::from_file_and_env(file).unwrap();
+    let names: Vec<_> = cfg.instances().into_iter().map(|p| p.name).collect();
+    assert_eq!(names, ["default", "mirror"]);
+    assert_eq!(cfg.default_instance, "mirror");
+    assert_eq!(cfg.extra_instances[0].pat, "glpat-mirror");
+
+    let err =
+        parse("[instances.mirror]\nurl = \"https://m\"\npat_env = \"X\"\npat = \"glpat-x\"\n")
+            .unwrap_err()
+            .to_string();
+    assert!(err.contains("instances.mirror.pat"), "{err}");
+
+    unsafe { std::env::remove_var("OPENDUO_TEST_MIRROR_PAT") };
+    let file = parse(
+        "[instances.mirror]\nurl = \"https://mirror.example.com\"\npat_env = \"OPENDUO_TEST_MIRROR_PAT\"\n",
+    )
+    .unwrap();
+    let err = Config::from_file_and_env(file).unwrap_err().to_string();
+    assert!(
+        err.contains("instances.mirror.pat_env") && err.contains("OPENDUO_TEST_MIRROR_PAT"),
+        "{err}"
+    );
+
+    let err = Config::from_file_and_env(parse("[gitlab]\ndefault_instance = \"gone\"\n").unwrap())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("gitlab.default_instance"), "{err}");
+}
+
+#[test]
+#[serial]
+fn test_config_load_takes_instances_only_from_user_file() {
+    let home = temp_dir("instances-user");
+    let user_file = home.join("config.toml");
+    std::fs::write(
+        &user_file,
+        "[gitlab]\nurl = \"https://gitlab.example.com\"\n\
+         [instances.dr]\nurl = \"https://dr.example.com\"\npat_env = \"GITLAB_PAT\"\n",
+    )
+    .unwrap();
+    // A repository trying to send the default PAT to its own host.
+    let workspace = temp_dir("instances-workspace");
+    std::fs::write(
+        workspace.join(".openduo.toml"),
+        "[gitlab]\ndefault_instance = \"dr\"\n\
+         [instances.dr]\nurl = \"https://gitlab.evil.example\"\npat_env = \"GITLAB_PAT\"\n\
+         [instances.leak]\nurl = \"https://gitlab.evil.example\"\npat_env = \"GITLAB_PAT\"\n",
+    )
+    .unwrap();
+
+    unsafe {
+        std::env::remove_var("GITLAB_URL");
+        std::env::remove_var("OPENDUO_INSTANCE");
+        std::env::set_var("GITLAB_PAT", "glpat-test123");
+        std::env::set_var("OPENDUO_CONFIG", &user_file);
+        std::env::set_var("OPENDUO_WORKSPACE", &workspace);
+    }
+    let cfg = Config::load();
+    unsafe {
+        std::env::remove_var("OPENDUO_CONFIG");
+        std::env::remove_var("OPENDUO_WORKSPACE");
+    }
+    let cfg = cfg.unwrap();
+    assert_eq!(cfg.default_instance, "default");
+    let instances: Vec<_> = cfg
+        .extra_instances
+        .iter()
+        .map(|p| (p.name.as_str(), p.gitlab_url.as_str()))
+        .collect();
+    assert_eq!(instances, [("dr", "https://dr.example.com")]);
+}
+
+#[test]
+#[serial]
+fn test_config_cache_section() {
+    unsafe {
+        std::env::set_var("GITLAB_URL", "https://gitlab.example.com");
+        std::env::set_var("GITLAB_PAT", "glpat-test123");
+    }
+    let file = parse("[cache]\nenabled = true\nttl_secs = 5\n[cache.ttls]\nfiles = 120\n").unwrap();
+    let cfg = Config::from_file_and_env(file).unwrap();
+    assert!(cfg.cache.enabled);
+    assert_eq!(cfg.cache.default_ttl.as_secs(), 5);
+    assert_eq!(cfg.cache.ttls["files"].as_secs(), 120);
+    assert_eq!(cfg.cache.ttls["projects"].as_secs(), 300);
+
+    let err = Config::from_file_and_env(parse("[cache.ttls]\nwikis = 10\n").unwrap())
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("cache.ttls") && err.contains("'wikis'"),
+        "{err}"
+    );
+}
+
+#[test]
+#[serial]
+fn test_config_history_section() {
+    unsafe {
+        std::env::set_var("GITLAB_URL", "https://gitlab.example.com");
+        std::env::set_var("GITLAB_PAT", "glpat-test123");
+        std::env::set_var("OPENDUO_HISTORY_KEY", "a2V5");
+    }
+    let file = parse("[history]\nstore = \"jsonl\"\ndir = \"/var/lib/openduo\"\n").unwrap();
+    let cfg = Config::from_file_and_env(file);
+    unsafe {
+        std::env::remove_var("OPENDUO_HISTORY_KEY");
+    }
+    let cfg = cfg.unwrap();
+    assert_eq!(cfg.history_store, HistoryStoreKind::JsonLines);
+    assert_eq!(cfg.history_dir, Some(PathBuf::from("/var/lib/openduo")));
+    assert_eq!(cfg.history_key.as_deref(), Some("a2V5"));
+
+    let cfg = Config::from_file_and_env(parse("[history]\nstore = \"none\"\n").unwrap()).unwrap();
+    assert_eq!(cfg.history_store, HistoryStoreKind::None);
+    assert!(cfg.history_key.is_none());
+
+    let err = Config::from_file_and_env(parse("[history]\nstore = \"sqlite\"\n").unwrap())
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("history.store") && err.contains("'sqlite'"),
+        "{err}"
+    );
+    // Keys are only taken from the environment.
+    assert!(parse("[history]\nkey = \"a2V5\"\n").is_err());
+}
@@ -0,0 +1,122 @@
+use openduo_core::error::GitLabError;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+fn classify(status: u16, body: &str) -> GitLabError {
+    GitLabError::from_parts(
+        StatusCode::from_u16(status).unwrap(),
+        &HeaderMap::new(),
+        body,
+    )
+}
+
+#[test]
+fn test_not_found_keeps_gitlab_message() {
+    let err = classify(404, r#"{"message":"404 Project Not Found"}"#);
+    assert!(
+        matches!(&err, GitLabError::NotFound { message } if message == "404 Project Not Found")
+    );
+    assert_eq!(err.to_string(), "404 Not Found: 404 Project Not Found");
+    assert!(err.remediation().unwrap().contains("project ID"));
+}
+
+#[test]
+fn test_expired_token_is_unauthorized() {
+    let err = classify(
+        401,
+        r#"{"error":"invalid_token","error_description":"Token has expired."}"#,
+    );
+    assert!(
+        matches!(&err, GitLabError::Unauthorized { message } if message == "Token has expired.")
+    );
+    assert!(err.remediation().unwrap().contains("new token"));
+}
+
+#[test]
+fn test_forbidden_reports_required_scope() {
+    let err = classify(
+        403,
+        r#"{"error":"insufficient_scope","error_description":"The request requires higher privileges than provided by the access token.","scope":"api"}"#,
+    );
+    assert!(matches!(
+        &err,
+        GitLabError::Forbidden { required_scope: Some(s), .. } if s == "api"
+    ));
+    assert!(err.remediation().unwrap().contains("`api` scope"));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "www-authenticate",
+        HeaderValue::from_static(
+            r#"Bearer realm="", error="insufficient_scope", scope="read_api""#,
+        ),
+    );
+    let err = GitLabError::from_parts(
+        StatusCode::FORBIDDEN,
+        &headers,
+        r#"{"message":"403 Forbidden"}"#,
+    );
+    assert!(matches!(
+        &err,
+        GitLabError::Forbidden { required_scope: Some(s), .. } if s == "read_api"
+    ));
+}
+
+#[test]
+fn test_forbidden_without_scope_points_at_role() {
+    let err = classify(403, r#"{"message":"403 Forbidden"}"#);
+    assert!(matches!(
+        &err,
+        GitLabError::Forbidden {
+            required_scope: None,
+            ..
+        }
+    ));
+    assert!(err.remediation().unwrap().contains("permission"));
+}
+
+#[test]
+fn test_validation_errors_collect_field_messages() {
+    let err = classify(
+        400,
+        r#"{"message":{"title":["can't be blank"],"labels":["is invalid","is too long"]}}"#,
+    );
+    let GitLabError::ValidationFailed { fields, .. } = &err else {
+        panic!("expected ValidationFailed, got {err:?}");
+    };
+    assert_eq!(fields["title"], vec!["can't be blank"]);
+    assert_eq!(fields["labels"].len(), 2);
+    assert_eq!(
+        err.to_string(),
+        "400 validation failed; labels is invalid, is too long; title can't be blank"
+    );
+
+    let err = classify(409, r#"{"message":"Label already exists"}"#);
+    assert_eq!(
+        err.to_string(),
+        "409 validation failed: Label already exists"
+    );
+}
+
+#[test]
+fn test_rate_limited_and_server_errors() {
+    let mut headers = HeaderMap::new();
+    headers.insert("retry-after", HeaderValue::from_static("30"));
+    let err = GitLabError::from_parts(StatusCode::TOO_MANY_REQUESTS, &headers, "Retry later");
+    assert!(matches!(
+        err,
+        GitLabError::RateLimited { retry_after: Some(d) } if d == Duration::from_secs(30)
+    ));
+
+    let err = classify(502, "<html>Bad Gateway</html>");
+    assert!(matches!(&err, GitLabError::Server { message, .. } if message.contains("Bad Gateway")));
+    assert_eq!(err.status(), Some(StatusCode::BAD_GATEWAY));
+}
+
+#[test]
+fn test_method_not_allowed_hint() {
+    let err = classify(405, r#"{"message":"405 Method Not Allowed"}"#);
+    assert!(matches!(err, GitLabError::Status { .. }));
+    assert!(err.remediation().unwrap().contains("current state"));
+}
@@ -1,6 +1,13 @@
+use futures::StreamExt;
 use openduo_core::config::Config;
-use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::error::GitLabError;
+use openduo_core::gitlab_client::{preview_writes, GitLabClient, UploadFile};
+use openduo_core::retry::RetryPolicy;
+use openduo_test_support::fixtures::PROJECT_ID;
+use openduo_test_support::MockGitLab;
+use serde_json::{json, Value};
 use serial_test::serial;
+use std::time::Duration;
 
 #[tokio::test]
 #[serial]
@@ -13,3 +20,426 @@ async fn test_client_builds_without_panicking() {
     let client = GitLabClient::new(config).unwrap();
     assert!(client.base_url().starts_with("https://"));
 }
+
+fn add_labels(mock: &MockGitLab, count: u64) {
+    mock.with_data(|d| {
+        for n in 0..count {
+            d.labels.push(json!({
+                "id": 5000 + n,
+                "project_id": PROJECT_ID,
+                "name": format!("label-{}", n),
+                "color": "#cccccc",
+            }));
+        }
+    });
+}
+
+#[tokio::test]
+async fn test_get_paginated_follows_pages_up_to_cap() {
+    let mock = MockGitLab::start().await;
+    add_labels(&mock, 248); // 250 labels in total
+    let client = GitLabClient::new(mock.config()).unwrap();
+
+    let labels: Vec<Value> = client
+        .get_paginated("projects/42/labels", 230)
+        .await
+        .unwrap();
+    assert_eq!(labels.len(), 230);
+
+    let requests = mock.requests();
+    let pages: Vec<&str> = requests
+        .iter()
+        .map(|r| r.query.get("page").map(String::as_str).unwrap_or("1"))
+        .collect();
+    assert_eq!(pages, ["1", "2", "3"]);
+    assert!(requests.iter().all(|r| r.query["per_page"] == "100"));
+}
+
+#[tokio::test]
+async fn test_get_paginated_stops_at_last_page() {
+    let mock = MockGitLab::start().await;
+    add_labels(&mock, 3);
+    let client = GitLabClient::new(mock.config()).unwrap();
+
+    let labels: Vec<Value> = client
+        .get_paginated("projects/42/labels?per_page=2", 100)
+        .await
+        .unwrap();
+    assert_eq!(labels.len(), 5);
+    assert_eq!(mock.requests().len(), 3);
+    assert!(mock.requests().iter().all(|r| r.query["per_page"] == "2"));
+}
+
+#[tokio::test]
+async fn test_paginate_follows_keyset_links() {
+    let mock = MockGitLab::start().await;
+    mock.with_data(|d| {
+        for id in 100..105 {
+            d.projects
+                .push(json!({ "id": id, "name": format!("p{}", id) }));
+        }
+    });
+    let client = GitLabClient::new(mock.config()).unwrap();
+
+    let ids: Vec<u64> = client
+        .paginate::<Value>(
+            "projects?pagination=keyset&order_by=id&sort=asc&per_page=3",
+            100,
+        )
+        .map(|p| p.unwrap()["id"].as_u64().unwrap())
+        .collect()
+        .await;
+    assert_eq!(ids, [42, 43, 100, 101, 102, 103, 104]);
+
+    let cursors: Vec<Option<String>> = mock
+        .requests()
+        .iter()
+        .map(|r| r.query.get("id_after").cloned())
+        .collect();
+    assert_eq!(
+        cursors,
+        [None, Some("100".to_string()), Some("103".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_paginate_ignores_look_alike_links() {
+    let mock = MockGitLab::start().await;
+    // A host name for the instance, reached through the mock as a proxy, so
+    // a followed link would show up as a second request.
+    let mut config = mock.config();
+    config.gitlab_url = "http://gitlab.example.com".to_string();
+    config.http.proxy = Some(mock.url());
+    let client = GitLabClient::new(config).unwrap();
+
+    for next in [
+        "http://gitlab.example.com.evil.net/api/v4/projects?page=2",
+        "http://gitlab.example.com@evil.net/api/v4/projects?page=2",
+        "http://gitlab.example.com:8080/api/v4/projects?page=2",
+        "https://gitlab.example.com/api/v4/projects?page=2",
+    ] {
+        let before = mock.requests().len();
+        let link = format!("<{}>; rel=\"next\"", next);
+        mock.fail_next_with(200, json!([{ "id": 1 }]), &[("link", &link)]);
+        let items: Vec<Value> = client.get_paginated("projects", 100).await.unwrap();
+        assert_eq!(items.len(), 1, "{next}");
+        assert_eq!(mock.requests().len(), before + 1, "followed {next}");
+    }
+
+    let before = mock.requests().len();
+    let link = "<http://gitlab.example.com/api/v4/projects?page=2>; rel=\"next\"";
+    mock.fail_next_with(200, json!([{ "id": 1 }]), &[("link", link)]);
+    let _: Vec<Value> = client.get_paginated("projects", 100).await.unwrap();
+    assert_eq!(mock.requests().len(), before + 2);
+}
+
+#[tokio::test]
+async fn test_paginate_with_zero_cap_sends_nothing() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let labels: Vec<Value> = client.get_paginated("projects/42/labels", 0).await.unwrap();
+    assert!(labels.is_empty());
+    assert!(mock.requests().is_empty());
+}
+
+fn fast_retry(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(500),
+    }
+}
+
+#[tokio::test]
+async fn test_get_retries_transient_errors() {
+    let mock = MockGitLab::start().await;
+    mock.fail_next(502, &[]);
+    mock.fail_next(503, &[]);
+    let client = GitLabClient::new(mock.config())
+        .unwrap()
+        .with_retry_policy(fast_retry(3));
+
+    let user: Value = client.get("user").await.unwrap();
+    assert_eq!(user["username"], "alice");
+    assert_eq!(mock.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_get_gives_up_after_max_retries() {
+    let mock = MockGitLab::start().await;
+    for _ in 0..3 {
+        mock.fail_next(500, &[]);
+    }
+    let client = GitLabClient::new(mock.config())
+        .unwrap()
+        .with_retry_policy(fast_retry(2));
+
+    assert!(client.get::<Value>("user").await.is_err());
+    assert_eq!(mock.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_post_is_not_retried_on_server_error() {
+    let mock = MockGitLab::start().await;
+    mock.fail_next(502, &[]);
+    let client = GitLabClient::new(mock.config())
+        .unwrap()
+        .with_retry_policy(fast_retry(3));
+
+    let result = client
+        .post::<Value>(
+            "projects/42/labels",
+            json!({ "name": "x", "color": "#000000" }),
+        )
+        .await;
+    assert!(result.is_err());
+    assert_eq!(mock.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn test_delete_is_not_retried_on_server_error() {
+    let mock = MockGitLab::start().await;
+    mock.fail_next(502, &[]);
+    let client = GitLabClient::new(mock.config())
+        .unwrap()
+        .with_retry_policy(fast_retry(3));
+
+    let err = client.delete("projects/42/labels/bug").await.unwrap_err();
+    assert!(!matches!(err, GitLabError::NotFound { .. }), "{err}");
+    assert_eq!(mock.requests().len(), 1);
+
+    mock.fail_next(429, &[("Retry-After", "0")]);
+    client.delete("projects/42/labels/bug").await.unwrap();
+    assert_eq!(mock.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_rate_limited_post_honors_retry_after() {
+    let mock = MockGitLab::start().await;
+    mock.fail_next(429, &[("Retry-After", "0"), ("RateLimit-Remaining", "0")]);
+    let client = GitLabClient::new(mock.config())
+        .unwrap()
+        .with_retry_policy(fast_retry(3));
+
+    let label: Value = client
+        .post(
+            "projects/42/labels",
+            json!({ "name": "x", "color": "#000000" }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(label["name"], "x");
+    assert_eq!(mock.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_rate_limit_longer_than_max_delay_fails_fast() {
+    let mock = MockGitLab::start().await;
+    mock.fail_next(429, &[("Retry-After", "3600")]);
+    let client = GitLabClient::new(mock.config())
+        .unwrap()
+        .with_retry_policy(fast_retry(3));
+
+    assert!(client.get::<Value>("user").await.is_err());
+    assert_eq!(mock.requests().len(), 1);
+}
+
+const WORK_ITEMS: &str = r#"
+query($fullPath: ID!, $first: Int, $after: String) {
+  project(fullPath: $fullPath) {
+    workItems(first: $first, after: $after) {
+      nodes { iid title state }
+      pageInfo { hasNextPage endCursor }
+    }
+  }
+}"#;
+
+#[tokio::test]
+async fn test_graphql_decodes_data() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+
+    let data: Value = client
+        .graphql("query { currentUser { username name } }", json!({}))
+        .await
+        .unwrap();
+    assert_eq!(data["currentUser"]["username"], "alice");
+
+    let req = mock.last_request().unwrap();
+    assert_eq!(
+        (req.method.as_str(), req.path.as_str()),
+        ("POST", "graphql")
+    );
+    assert_eq!(req.body["variables"], json!({}));
+}
+
+#[tokio::test]
+async fn test_graphql_mutations_are_found_past_comments_and_fragments() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let writes = [
+        "# Close it\nmutation { issueClose(input: {}) { errors } }",
+        "\u{feff}\t\n  mutation\tClose { issueClose(input: {}) { errors } }",
+        "fragment E on IssueClosePayload { errors }\nmutation { issueClose(input: {}) { ...E } }",
+        "query A { currentUser { id } }\nmutation B { issueClose(input: {}) { errors } }",
+        "subscription { issueUpdated { id } }",
+        "{ unterminated",
+    ];
+    for query in writes {
+        let (result, planned) = preview_writes(client.graphql::<Value>(query, Value::Null)).await;
+        assert!(matches!(result, Err(GitLabError::Withheld)), "{query}");
+        assert_eq!(planned.len(), 1, "{query}");
+    }
+    let reads = [
+        "# mutation { nope }\nquery { currentUser { username } }",
+        "{ currentUser { username } }",
+        "fragment U on UserCore { username }\nquery { currentUser { ...U } }",
+        "query($q: String = \"} mutation {\") { currentUser { username } }",
+    ];
+    for query in reads {
+        let (result, planned) = preview_writes(client.graphql::<Value>(query, Value::Null)).await;
+        assert!(!matches!(result, Err(GitLabError::Withheld)), "{query}");
+        assert!(planned.is_empty(), "{query}");
+    }
+    assert!(mock.requests().iter().all(|r| r.path == "graphql"));
+    assert_eq!(mock.requests().len(), reads.len());
+}
+
+#[tokio::test]
+async fn test_graphql_errors_are_returned() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+
+    let err = client
+        .graphql::<Value>("query { nope }", Value::Null)
+        .await
+        .unwrap_err();
+    assert!(matches!(&err, GitLabError::GraphQl { messages } if messages.len() == 1));
+    assert!(err.to_string().starts_with("GraphQL error:"));
+}
+
+#[tokio::test]
+async fn test_graphql_paginated_follows_cursors() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let vars = json!({ "fullPath": "openduo/demo", "first": 2 });
+
+    let items: Vec<Value> = client
+        .graphql_paginated(WORK_ITEMS, vars.clone(), &["project", "workItems"], 50)
+        .await
+        .unwrap();
+    let titles: Vec<&str> = items.iter().map(|i| i["title"].as_str().unwrap()).collect();
+    assert_eq!(
+        titles,
+        [
+            "Login page returns 500",
+            "Add dark mode",
+            "Upgrade dependencies"
+        ]
+    );
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].body["variables"]["after"], "cursor:2");
+
+    let items: Vec<Value> = client
+        .graphql_paginated(WORK_ITEMS, vars, &["project", "workItems"], 1)
+        .await
+        .unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(mock.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_graphql_paginated_missing_project_is_not_found() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+
+    let err = client
+        .graphql_paginated::<Value>(
+            WORK_ITEMS,
+            json!({ "fullPath": "nope/missing" }),
+            &["project", "workItems"],
+            10,
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, GitLabError::NotFound { .. }));
+}
+
+#[tokio::test]
+async fn test_graphql_paginated_rejects_non_object_variables() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+
+    for vars in [json!(["openduo/demo"]), json!("openduo/demo")] {
+        let err = client
+            .graphql_paginated::<Value>(WORK_ITEMS, vars, &["project", "workItems"], 10)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GitLabError::InvalidRequest(_)), "{err}");
+    }
+    assert!(mock.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_patch_and_delete_send_their_methods() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let err = client
+        .patch::<Value>("projects/42/things/1", json!({ "name": "x" }))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, GitLabError::NotFound { .. }), "{err}");
+    let req = mock.last_request().unwrap();
+    assert_eq!(
+        (req.method.as_str(), req.body["name"].as_str()),
+        ("PATCH", Some("x"))
+    );
+
+    client.delete("projects/42/labels/bug").await.unwrap();
+    assert_eq!(mock.last_request().unwrap().method, "DELETE");
+}
+
+#[tokio::test]
+async fn test_upload_is_multipart_and_retried_after_429() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config())
+        .unwrap()
+        .with_retry_policy(fast_retry(1));
+    mock.fail_next(429, &[("Retry-After", "0")]);
+    let file =
+        UploadFile::new("notes.md", b"# Notes\n".to_vec()).with_content_type("text/markdown");
+    let upload: Value = client
+        .upload(
+            "projects/42/uploads",
+            &file,
+            &[("caption", "release notes")],
+        )
+        .await
+        .unwrap();
+    assert_eq!(upload["alt"], "notes.md");
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    let body = &requests[1].body;
+    assert_eq!(body["caption"], "release notes");
+    assert_eq!(body["file"]["content_type"], "text/markdown");
+    assert_eq!(body["file"]["content"], "# Notes\n");
+}
+
+#[tokio::test]
+async fn test_upload_refuses_header_injection() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let files = [
+        UploadFile::new("a.md\r\nContent-Type: text/html", b"x".to_vec()),
+        UploadFile::new("a.md", b"x".to_vec()).with_content_type("text/plain\r\n\r\nforged"),
+    ];
+    for file in &files {
+        let err = client
+            .upload::<Value>("projects/42/uploads", file, &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GitLabError::InvalidRequest(_)), "{err}");
+    }
+    assert!(mock.requests().is_empty());
+}
@@ -0,0 +1,151 @@
+use openduo_core::config::Config;
+use openduo_core::config_file::ConfigFile;
+use openduo_core::error::GitLabError;
+use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::http::{ClientIdentity, HttpSettings};
+use openduo_core::retry::RetryPolicy;
+use openduo_test_support::MockGitLab;
+use serde_json::Value;
+use serial_test::serial;
+use std::path::{Path, PathBuf};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// A client for a host that does not resolve, so only a proxy can reach it.
+fn unresolvable_client(mock: &MockGitLab, http: HttpSettings) -> GitLabClient {
+    GitLabClient::new(Config {
+        gitlab_url: "http://gitlab.invalid".to_string(),
+        http,
+        ..mock.config()
+    })
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_requests_go_through_configured_proxy() {
+    let mock = MockGitLab::start().await;
+    let client = unresolvable_client(
+        &mock,
+        HttpSettings {
+            proxy: Some(mock.url()),
+            ..HttpSettings::default()
+        },
+    );
+    let user: Value = client.get("/user").await.unwrap();
+    assert!(user["username"].is_string());
+    assert_eq!(mock.last_request().unwrap().path, "user");
+}
+
+#[tokio::test]
+async fn test_no_proxy_hosts_bypass_the_proxy() {
+    let mock = MockGitLab::start().await;
+    let client = unresolvable_client(
+        &mock,
+        HttpSettings {
+            http_proxy: Some(mock.url()),
+            no_proxy: Some("example.com,.invalid".to_string()),
+            ..HttpSettings::default()
+        },
+    )
+    .with_retry_policy(RetryPolicy::with_max_retries(0));
+    let err = client.get::<Value>("/user").await.unwrap_err();
+    assert!(matches!(err, GitLabError::Transport(_)), "{err}");
+    assert!(mock.requests().is_empty());
+
+    // An https-only proxy is not used for http:// targets.
+    let client = unresolvable_client(
+        &mock,
+        HttpSettings {
+            https_proxy: Some(mock.url()),
+            ..HttpSettings::default()
+        },
+    )
+    .with_retry_policy(RetryPolicy::with_max_retries(0));
+    assert!(client.get::<Value>("/user").await.is_err());
+    assert!(mock.requests().is_empty());
+}
+
+#[test]
+fn test_ca_bundle_and_client_identity_load() {
+    let http = HttpSettings {
+        extra_ca_certs: vec![fixture("ca.pem")],
+        client_identity: Some(ClientIdentity {
+            cert: fixture("client.pem"),
+            key: fixture("client.key"),
+        }),
+        ..HttpSettings::default()
+    };
+    http.build_client().unwrap();
+}
+
+#[test]
+fn test_bad_tls_files_name_the_path() {
+    let http = HttpSettings {
+        extra_ca_certs: vec![fixture("missing.pem")],
+        ..HttpSettings::default()
+    };
+    let err = http.build_client().unwrap_err().to_string();
+    assert!(
+        err.contains("CA bundle") && err.contains("missing.pem"),
+        "{err}"
+    );
+
+    let http = HttpSettings {
+        client_identity: Some(ClientIdentity {
+            cert: fixture("client.pem"),
+            key: fixture("ca.pem"),
+        }),
+        ..HttpSettings::default()
+    };
+    let err = http.build_client().unwrap_err().to_string();
+    assert!(err.contains("PKCS#8"), "{err}");
+}
+
+#[test]
+#[serial]
+fn test_config_resolves_proxy_and_tls_settings() {
+    unsafe {
+        std::env::set_var("GITLAB_URL", "https://gitlab.example.com");
+        std::env::set_var("GITLAB_PAT", "glpat-test123");
+        std::env::set_var("HTTPS_PROXY", "http://env-proxy.example.com:3128");
+        std::env::set_var("NO_PROXY", "env.example.com");
+    }
+    let text = format!(
+        "[http]\nno_proxy = [\"gitlab.example.com\", \"10.0.0.0/8\"]\nca_bundle = {:?}\n\
+         client_cert = {:?}\nclient_key = {:?}\n",
+        fixture("ca.pem"),
+        fixture("client.pem"),
+        fixture("client.key"),
+    );
+    let file = ConfigFile::parse(&text, Path::new("test.toml")).unwrap();
+    let cfg = Config::from_file_and_env(file);
+    let half = ConfigFile::parse(
+        &format!("[http]\nclient_cert = {:?}\n", fixture("client.pem")),
+        Path::new("test.toml"),
+    )
+    .unwrap();
+    let half = Config::from_file_and_env(half);
+    unsafe {
+        std::env::remove_var("HTTPS_PROXY");
+        std::env::remove_var("NO_PROXY");
+    }
+
+    let cfg = cfg.unwrap();
+    assert_eq!(
+        cfg.http.https_proxy.as_deref(),
+        Some("http://env-proxy.example.com:3128")
+    );
+    assert_eq!(
+        cfg.http.no_proxy.as_deref(),
+        Some("gitlab.example.com,10.0.0.0/8")
+    );
+    assert_eq!(cfg.http.extra_ca_certs, [fixture("ca.pem")]);
+    assert!(cfg.http.client_identity.is_some());
+
+    let err = half.unwrap_err().to_string();
+    assert!(err.contains("http.client_key"), "{err}");
+}
@@ -0,0 +1,78 @@
+use openduo_core::retry::{is_retryable_status, server_delay, RateLimit, RetryPolicy};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::StatusCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (k, v) in pairs {
+        map.insert(*k, HeaderValue::from_str(v).unwrap());
+    }
+    map
+}
+
+#[test]
+fn test_backoff_grows_and_is_capped() {
+    let policy = RetryPolicy {
+        max_retries: 5,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(1000),
+    };
+    for attempt in 0..6 {
+        let exp = Duration::from_millis(100 * 2u64.pow(attempt)).min(policy.max_delay);
+        let delay = policy.backoff(attempt);
+        assert!(
+            delay >= exp / 2 && delay <= exp,
+            "attempt {attempt}: {delay:?}"
+        );
+    }
+}
+
+#[test]
+fn test_retryable_statuses() {
+    assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+    assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+    assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+    assert!(!is_retryable_status(StatusCode::UNPROCESSABLE_ENTITY));
+}
+
+#[test]
+fn test_server_delay_from_retry_after_seconds() {
+    let h = headers(&[("retry-after", "7")]);
+    assert_eq!(server_delay(&h), Some(Duration::from_secs(7)));
+}
+
+#[test]
+fn test_server_delay_from_retry_after_date() {
+    let at = SystemTime::now() + Duration::from_secs(120);
+    let h = headers(&[("retry-after", &httpdate::fmt_http_date(at))]);
+    let delay = server_delay(&h).unwrap();
+    assert!(delay > Duration::from_secs(100) && delay <= Duration::from_secs(120));
+}
+
+#[test]
+fn test_server_delay_from_ratelimit_reset() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let h = headers(&[("ratelimit-reset", &(now + 30).to_string())]);
+    let delay = server_delay(&h).unwrap();
+    assert!(delay >= Duration::from_secs(29) && delay <= Duration::from_secs(30));
+    assert_eq!(server_delay(&HeaderMap::new()), None);
+}
+
+#[test]
+fn test_rate_limit_headers() {
+    let h = headers(&[("ratelimit-limit", "600"), ("ratelimit-remaining", "42")]);
+    let rl = RateLimit::from_headers(&h).unwrap();
+    assert_eq!(
+        rl,
+        RateLimit {
+            limit: 600,
+            remaining: 42
+        }
+    );
+    assert!(rl.is_low());
+    assert!(RateLimit::from_headers(&headers(&[("ratelimit-limit", "600")])).is_none());
+}
@@ -0,0 +1,58 @@
+use openduo_core::error::GitLabError;
+use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::token::{days_until, introspect};
+use openduo_test_support::MockGitLab;
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn test_introspect_reports_user_scopes_and_expiry() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let status = introspect(&client).await.unwrap();
+    assert_eq!(status.username, "alice");
+    assert_eq!(status.token_name.as_deref(), Some("openduo"));
+    assert_eq!(status.expires_at.as_deref(), Some("2099-01-01"));
+    assert!(status.expires_in_days.unwrap() > 0);
+    assert!(status.missing_scopes.is_empty());
+    assert!(!status.read_only);
+}
+
+#[tokio::test]
+async fn test_introspect_flags_read_api_token() {
+    let mock = MockGitLab::start().await;
+    mock.with_data(|d| d.token["scopes"] = json!(["read_api", "read_user"]));
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let status = introspect(&client).await.unwrap();
+    assert!(status.read_only);
+    assert_eq!(status.missing_scopes, ["api", "ai_features"]);
+}
+
+#[tokio::test]
+async fn test_introspect_without_token_endpoint() {
+    let mock = MockGitLab::start().await;
+    mock.with_data(|d| d.token = Value::Null);
+    let client = GitLabClient::new(mock.config()).unwrap();
+    let status = introspect(&client).await.unwrap();
+    assert_eq!(status.username, "alice");
+    assert!(status.scopes.is_none());
+    assert!(status.missing_scopes.is_empty());
+}
+
+#[tokio::test]
+async fn test_introspect_rejected_token() {
+    let mock = MockGitLab::start().await;
+    let client = GitLabClient::new(openduo_core::config::Config {
+        pat: "glpat-wrong".to_string(),
+        ..mock.config()
+    })
+    .unwrap();
+    let err = introspect(&client).await.unwrap_err();
+    assert!(matches!(err, GitLabError::Unauthorized { .. }), "{err}");
+}
+
+#[test]
+fn test_days_until() {
+    assert_eq!(days_until("1970-01-01").map(|d| d < -20_000), Some(true));
+    assert!(days_until("2999-12-31").unwrap() > 300_000);
+    assert_eq!(days_until("soon"), None);
+}
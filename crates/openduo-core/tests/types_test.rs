@@ -0,0 +1,163 @@
+use openduo_core::types::{
+    Commit, Issue, Job, Label, MergeRequest, Milestone, Note, Pipeline, Project, User,
+};
+use openduo_test_support::fixtures::Fixtures;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// A response recorded from GitLab, under `tests/fixtures/gitlab`.
+fn recorded<T: DeserializeOwned>(name: &str) -> T {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/gitlab")
+        .join(name);
+    let text = std::fs::read_to_string(&path).unwrap();
+    serde_json::from_str(&text).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+fn parse<T: DeserializeOwned>(value: &Value) -> T {
+    serde_json::from_value(value.clone()).unwrap_or_else(|e| panic!("{e}: {value}"))
+}
+
+#[test]
+fn test_mock_fixtures_match_the_models() {
+    let data = Fixtures::default();
+    for user in data.users.iter().chain([&data.current_user]) {
+        let user: User = parse(user);
+        assert!(user.id > 0 && !user.username.is_empty());
+    }
+    for project in &data.projects {
+        let project: Project = parse(project);
+        assert!(!project.path_with_namespace.is_empty());
+    }
+    for issue in &data.issues {
+        let issue: Issue = parse(issue);
+        assert!(issue.iid > 0 && !issue.title.is_empty() && !issue.labels.is_empty());
+        assert_eq!(issue.author.unwrap().username, "alice");
+    }
+    for mr in &data.merge_requests {
+        let mr: MergeRequest = parse(mr);
+        assert!(!mr.source_branch.is_empty() && !mr.target_branch.is_empty());
+    }
+    for pipeline in &data.pipelines {
+        let pipeline: Pipeline = parse(pipeline);
+        assert!(!pipeline.status.is_empty() && !pipeline.git_ref.is_empty());
+    }
+    for job in &data.jobs {
+        let job: Job = parse(job);
+        assert!(job.pipeline_id.is_some() && !job.stage.is_empty());
+    }
+    for commit in &data.commits {
+        let commit: Commit = parse(commit);
+        assert_eq!(commit.short_id.len(), 8);
+    }
+    for label in &data.labels {
+        let label: Label = parse(label);
+        assert!(label.color.is_some());
+    }
+    for milestone in &data.milestones {
+        let milestone: Milestone = parse(milestone);
+        assert_eq!(milestone.due_date.as_deref(), Some("2026-03-31"));
+    }
+}
+
+#[test]
+fn test_recorded_responses_parse() {
+    let issue: Issue = recorded("issue.json");
+    assert_eq!((issue.id, issue.iid, issue.project_id), (76, 6, 1));
+    assert_eq!(issue.labels, ["bug", "priority::high"]);
+    assert_eq!(issue.assignees[0].username, "root");
+    assert_eq!(issue.milestone.unwrap().title, "v17.1");
+    assert_eq!(issue.closed_at, None);
+
+    let mr: MergeRequest = recorded("merge_request.json");
+    assert_eq!(mr.state, "merged");
+    assert_eq!(mr.reviewers.len(), 1);
+    assert_eq!(mr.detailed_merge_status.as_deref(), Some("not_open"));
+    assert_eq!(mr.has_conflicts, Some(false));
+    assert!(!mr.is_draft());
+    assert_eq!(mr.blocking_discussions_resolved, Some(true));
+    assert_eq!(mr.merge_when_pipeline_succeeds, Some(true));
+    let head = mr.head_pipeline.unwrap();
+    assert_eq!((head.id, head.status.as_str()), (8, "success"));
+    assert_eq!(head.coverage.as_deref(), Some("87.5"));
+    assert_eq!(mr.diff_refs.unwrap().head_sha, head.sha);
+
+    let pipeline: Pipeline = recorded("pipeline.json");
+    assert_eq!((pipeline.id, pipeline.iid), (287, Some(144)));
+    assert_eq!(pipeline.git_ref, "main");
+    assert_eq!(pipeline.duration, Some(34.0));
+    assert_eq!(pipeline.user.unwrap().username, "root");
+    assert!(pipeline.finished_at.is_some());
+
+    let job: Job = recorded("job.json");
+    assert_eq!(job.pipeline_id, Some(6));
+    assert_eq!(job.failure_reason.as_deref(), Some("script_failure"));
+    assert!(job.allow_failure);
+
+    let project: Project = recorded("project.json");
+    assert_eq!(
+        project.path_with_namespace,
+        "diaspora/diaspora-project-site"
+    );
+    assert_eq!(project.default_branch, None);
+    assert!(project.ssh_url_to_repo.unwrap().starts_with("git@"));
+    assert!(project.http_url_to_repo.is_some());
+    let namespace = project.namespace.unwrap();
+    assert_eq!(namespace.full_path, "diaspora");
+    assert_eq!(namespace.kind.as_deref(), Some("group"));
+}
+
+#[test]
+fn test_older_gitlab_merge_request_parses() {
+    // GitLab 13: no `draft` or `detailed_merge_status`, label objects,
+    // `null` for lists and descriptions.
+    let mr: MergeRequest = recorded("merge_request_13.json");
+    assert!(mr.is_draft());
+    assert_eq!(mr.labels, ["backend"]);
+    assert!(mr.assignees.is_empty());
+    assert_eq!(mr.description, None);
+    assert_eq!(mr.detailed_merge_status, None);
+    assert_eq!(mr.merge_status.as_deref(), Some("cannot_be_merged"));
+}
+
+#[test]
+fn test_missing_and_null_fields_take_defaults() {
+    let issue: Issue = parse(&json!({ "iid": 3, "title": null, "labels": null }));
+    assert_eq!(issue.iid, 3);
+    assert_eq!(issue.title, "");
+    assert!(issue.labels.is_empty() && issue.author.is_none());
+
+    let note: Note = parse(&json!({ "id": 1, "body": "LGTM", "system": null }));
+    assert!(!note.system);
+    assert_eq!(parse::<Commit>(&json!({})), Commit::default());
+}
+
+#[test]
+fn test_ids_accept_strings_and_graphql_global_ids() {
+    let issue: Issue = parse(&json!({
+        "id": "gid://gitlab/Issue/501",
+        "iid": "1",
+        "project_id": 42,
+    }));
+    assert_eq!((issue.id, issue.iid, issue.project_id), (501, 1, 42));
+
+    let user: User = parse(&json!({ "id": "gid://gitlab/User/7", "username": "bob" }));
+    assert_eq!(user.id, 7);
+}
+
+#[test]
+fn test_serialized_models_drop_empty_fields() {
+    let issue: Issue = recorded("issue.json");
+    let value = serde_json::to_value(&issue).unwrap();
+    assert!(value.get("closed_at").is_none());
+    assert!(value.get("time_stats").is_none());
+    assert_eq!(value["labels"], json!(["bug", "priority::high"]));
+
+    let job: Job = recorded("job.json");
+    let value = serde_json::to_value(&job).unwrap();
+    assert_eq!(value["pipeline"], 6);
+    assert_eq!(value["ref"], "main");
+    // The compact form parses back to the same model.
+    assert_eq!(parse::<Job>(&value), job);
+}
@@ -0,0 +1,100 @@
+//! Tool calls waiting for the user's approval, by id, answered through
+//! `POST /approvals/:id`.
+
+use crate::sessions::{new_id, Session};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use openduo_agent::approval::{ApprovalRequest, Approver, Decision};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// Observation for calls a client cannot be asked about.
+const NON_INTERACTIVE_REASON: &str = "this chat client cannot ask for approval; tell the user \
+    to update the OpenDuo extension or set require_approval = false";
+
+struct Pending {
+    session: Arc<Session>,
+    tool: String,
+    answer: oneshot::Sender<Decision>,
+}
+
+#[derive(Default)]
+pub struct Approvals {
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl Approvals {
+    /// Answer approval `id`; `always` also allows its tool for the rest of
+    /// the session. `false` if nothing is waiting on `id`.
+    pub fn resolve(&self, id: &str, decision: Decision, always: bool) -> bool {
+        let Some(pending) = self.pending.lock().unwrap().remove(id) else {
+            return false;
+        };
+        if always && !matches!(decision, Decision::Reject { .. }) {
+            pending.session.always_allow(&pending.tool);
+        }
+        // The turn may have been cancelled meanwhile.
+        let _ = pending.answer.send(decision);
+        true
+    }
+
+    fn withdraw(&self, id: &str) {
+        self.pending.lock().unwrap().remove(id);
+    }
+}
+
+/// Removes a request once its decision future is dropped, answered or not.
+struct Withdraw {
+    approvals: Arc<Approvals>,
+    id: String,
+}
+
+impl Drop for Withdraw {
+    fn drop(&mut self) {
+        self.approvals.withdraw(&self.id);
+    }
+}
+
+/// Asks the client streaming a session's turn.
+pub struct SessionApprover {
+    pub approvals: Arc<Approvals>,
+    pub session: Arc<Session>,
+    /// `false` when the client cannot show approval requests (the `text`
+    /// protocol); calls that need one are then rejected.
+    pub interactive: bool,
+}
+
+impl Approver for SessionApprover {
+    fn always_allowed(&self, tool: &str) -> bool {
+        self.session.is_always_allowed(tool)
+    }
+
+    fn request(&self, request: &ApprovalRequest) -> (String, BoxFuture<'static, Decision>) {
+        let id = new_id();
+        if !self.interactive {
+            let decision = Decision::Reject {
+                reason: Some(NON_INTERACTIVE_REASON.to_string()),
+            };
+            return (id, futures::future::ready(decision).boxed());
+        }
+        let (answer, decision) = oneshot::channel();
+        self.approvals.pending.lock().unwrap().insert(
+            id.clone(),
+            Pending {
+                session: self.session.clone(),
+                tool: request.tool.clone(),
+                answer,
+            },
+        );
+        let withdraw = Withdraw {
+            approvals: self.approvals.clone(),
+            id: id.clone(),
+        };
+        let decision = async move {
+            let _withdraw = withdraw;
+            decision.await.unwrap_or(Decision::Reject { reason: None })
+        };
+        (id, decision.boxed())
+    }
+}
@@ -0,0 +1,160 @@
+//! What a chat response streams: typed SSE events, or bare `data:` lines
+//! for clients that predate them.
+
+use axum::response::sse::Event;
+use openduo_agent::provider::Usage;
+use openduo_agent::react_loop::AgentEvent;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// `token`, `iteration`, `tool_call_started`, `tool_call_finished`,
+    /// `approval_required`, `error` and `done` events with JSON payloads.
+    Events,
+    /// Tokens as bare data lines, `[ERROR] ...` for rejected requests and a
+    /// closing `[DONE]`, as the first extension releases expect.
+    Text,
+}
+
+pub enum ChatEvent {
+    Agent(AgentEvent),
+    Error {
+        /// `invalid_request` or `agent_error`.
+        code: &'static str,
+        message: String,
+    },
+    Done(TurnSummary),
+}
+
+impl ChatEvent {
+    /// `None` for events the protocol has no way to express.
+    pub fn into_sse(self, protocol: Protocol) -> Option<Event> {
+        match protocol {
+            Protocol::Events => self.into_typed(),
+            Protocol::Text => self.into_text().map(|data| Event::default().data(data)),
+        }
+    }
+
+    fn into_typed(self) -> Option<Event> {
+        let (name, payload) = match self {
+            ChatEvent::Agent(AgentEvent::Token(text)) => ("token", json!({ "text": text })),
+            ChatEvent::Agent(AgentEvent::Iteration(n)) => ("iteration", json!({ "iteration": n })),
+            ChatEvent::Agent(AgentEvent::ToolCallStarted {
+                id,
+                name,
+                arguments,
+            }) => (
+                "tool_call_started",
+                json!({ "id": id, "name": name, "arguments": arguments }),
+            ),
+            ChatEvent::Agent(AgentEvent::ToolCallFinished {
+                id,
+                name,
+                duration,
+                result_bytes,
+                error,
+            }) => (
+                "tool_call_finished",
+                json!({
+                    "id": id,
+                    "name": name,
+                    "duration_ms": duration.as_millis() as u64,
+                    "result_bytes": result_bytes,
+                    "error": error,
+                }),
+            ),
+            ChatEvent::Agent(AgentEvent::ApprovalRequested {
+                approval_id,
+                request,
+            }) => {
+                let mut payload = json!(request);
+                payload["approval_id"] = approval_id.into();
+                ("approval_required", payload)
+            }
+            // Reported once, summed, in `done`.
+            ChatEvent::Agent(AgentEvent::Usage(_)) => return None,
+            ChatEvent::Error { code, message } => {
+                ("error", json!({ "code": code, "message": message }))
+            }
+            ChatEvent::Done(summary) => ("done", json!(summary)),
+        };
+        Some(Event::default().event(name).data(payload.to_string()))
+    }
+
+    fn into_text(self) -> Option<String> {
+        match self {
+            ChatEvent::Agent(AgentEvent::Token(text)) => Some(text),
+            // Text clients cannot answer, so the call has been rejected.
+            ChatEvent::Agent(AgentEvent::ApprovalRequested { request, .. }) => Some(format!(
+                "[ERROR] {} needs approval, which the text protocol cannot ask for, so it \
+                 was not run. Chat through /sessions/{{id}}/chat with the events protocol \
+                 to approve changes.",
+                request.tool
+            )),
+            ChatEvent::Agent(_) => None,
+            ChatEvent::Error {
+                code: "invalid_request",
+                message,
+            } => Some(format!("[ERROR] {}", message)),
+            ChatEvent::Error { message, .. } => Some(format!("Error: {}", message)),
+            ChatEvent::Done(_) => Some("[DONE]".to_string()),
+        }
+    }
+}
+
+/// Payload of the closing `done` event.
+#[derive(Debug, Serialize)]
+pub struct TurnSummary {
+    pub turn_id: Option<String>,
+    pub cancelled: bool,
+    pub iterations: usize,
+    pub tool_calls: usize,
+    pub duration_ms: u64,
+    /// Summed over the turn's model calls; `null` if the backend reports
+    /// no token counts.
+    pub usage: Option<Usage>,
+}
+
+/// Tallies a turn's events for its `TurnSummary`.
+pub struct TurnStats {
+    started: Instant,
+    iterations: usize,
+    tool_calls: usize,
+    usage: Option<Usage>,
+}
+
+impl Default for TurnStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            iterations: 0,
+            tool_calls: 0,
+            usage: None,
+        }
+    }
+}
+
+impl TurnStats {
+    pub fn record(&mut self, event: &AgentEvent) {
+        match event {
+            AgentEvent::Iteration(n) => self.iterations = *n,
+            AgentEvent::ToolCallFinished { .. } => self.tool_calls += 1,
+            AgentEvent::Usage(usage) => *self.usage.get_or_insert_with(Usage::default) += *usage,
+            _ => {}
+        }
+    }
+
+    pub fn summary(&self, turn_id: Option<String>, cancelled: bool) -> TurnSummary {
+        TurnSummary {
+            turn_id,
+            cancelled,
+            iterations: self.iterations,
+            tool_calls: self.tool_calls,
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            usage: self.usage,
+        }
+    }
+}
@@ -1,15 +1,34 @@
+mod approvals;
+mod events;
+mod redacting_writer;
 mod routes;
+mod sessions;
+mod store;
+mod turns;
 mod validation;
 
 use anyhow::Result;
-use openduo_agent::gitlab_provider::GitLabAiProvider;
 use openduo_agent::prompt::PromptBuilder;
-use openduo_core::config::Config;
+use openduo_agent::provider::provider_from_config;
+use openduo_core::capabilities::{self, Capability};
+use openduo_core::config::{Config, HistoryStoreKind, ProviderKind, DEFAULT_INSTANCE};
+use openduo_core::error::GitLabError;
+use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::redact::Redactor;
+use openduo_core::token::introspect;
 use openduo_tools::registry::ToolRegistry;
+use redacting_writer::RedactingWriter;
 use routes::{build_router, AppState};
+use serde_json::{json, Value};
+use sessions::{keep_recent, SessionStore, DEFAULT_SESSION};
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tracing::info;
+use std::time::Duration;
+use store::{ConversationStore, JsonlStore};
+use tracing::{info, warn};
+
+/// Warn this many days before a token expires.
+const TOKEN_EXPIRY_WARNING_DAYS: i64 = 14;
 use tracing_subscriber::EnvFilter;
 
 async fn shutdown_signal() {
@@ -19,26 +38,191 @@ async fn shutdown_signal() {
     info!("Received shutdown signal, draining connections...");
 }
 
+/// Check each instance's PAT before serving. A rejected or expired token
+/// stops startup; a `read_api`-only token disables write tools.
+async fn check_tokens(
+    config: &Config,
+    tools: &mut ToolRegistry,
+) -> Result<BTreeMap<String, Value>> {
+    let mut report = BTreeMap::new();
+    for profile in config.instances() {
+        let client = GitLabClient::for_instance(&profile, config)?;
+        let status = match introspect(&client).await {
+            Ok(status) => status,
+            Err(e @ GitLabError::Unauthorized { .. }) => anyhow::bail!(
+                "GitLab rejected the token for instance '{}' ({}): {}",
+                profile.name,
+                profile.gitlab_url,
+                e
+            ),
+            Err(e) => {
+                warn!(instance = %profile.name, error = %e, "Could not check GitLab token");
+                report.insert(profile.name, json!({ "error": e.to_string() }));
+                continue;
+            }
+        };
+        if status.expires_in_days.is_some_and(|days| days < 0) {
+            anyhow::bail!(
+                "The GitLab token for instance '{}' expired on {}",
+                profile.name,
+                status.expires_at.as_deref().unwrap_or_default()
+            );
+        }
+        if let Some(days) = status
+            .expires_in_days
+            .filter(|days| *days <= TOKEN_EXPIRY_WARNING_DAYS)
+        {
+            warn!(instance = %profile.name, days, "GitLab token expires soon");
+        }
+        if !status.missing_scopes.is_empty() {
+            warn!(
+                instance = %profile.name,
+                missing = ?status.missing_scopes,
+                "GitLab token lacks required scopes"
+            );
+        }
+        if status.read_only {
+            warn!(instance = %profile.name, "GitLab token only has read_api; write tools disabled");
+            tools.set_read_only(&profile.name);
+        }
+        info!(instance = %profile.name, username = %status.username, "GitLab token verified");
+        report.insert(profile.name, serde_json::to_value(&status)?);
+    }
+    Ok(report)
+}
+
+/// Probe what each instance supports so the registry can hide tools it
+/// cannot serve. An instance that fails the probe keeps every tool.
+async fn probe_capabilities(
+    config: &Config,
+    tools: &mut ToolRegistry,
+    report: &mut BTreeMap<String, Value>,
+) -> Result<()> {
+    for profile in config.instances() {
+        let client = GitLabClient::for_instance(&profile, config)?;
+        let caps = match capabilities::probe(&client).await {
+            Ok(caps) => caps,
+            Err(e) => {
+                warn!(instance = %profile.name, error = %e, "Could not detect GitLab capabilities");
+                continue;
+            }
+        };
+        info!(
+            instance = %profile.name,
+            version = %caps.version,
+            features = ?caps.features,
+            unknown = ?caps.unknown,
+            "GitLab capabilities detected"
+        );
+        if profile.name == DEFAULT_INSTANCE
+            && config.provider == ProviderKind::GitLab
+            && !caps.supports(Capability::DuoChat)
+        {
+            warn!("GitLab Duo Chat is not available on this instance; chat requests will fail");
+        }
+        if let Some(Value::Object(entry)) = report.get_mut(&profile.name) {
+            entry.insert(
+                "capabilities".to_string(),
+                json!({
+                    "version": caps.version,
+                    "edition": if caps.enterprise { "ee" } else { "ce" },
+                    "features": caps.features,
+                    "unknown": caps.unknown,
+                }),
+            );
+        }
+        tools.set_capabilities(&profile.name, caps);
+    }
+    Ok(())
+}
+
+fn open_store(config: &Config) -> Result<Option<Arc<dyn ConversationStore>>> {
+    match (config.history_store, &config.history_dir) {
+        (HistoryStoreKind::None, _) => Ok(None),
+        (HistoryStoreKind::JsonLines, None) => {
+            warn!("No home directory or history.dir; conversations will not be saved");
+            Ok(None)
+        }
+        (HistoryStoreKind::JsonLines, Some(dir)) => {
+            let store = JsonlStore::open(dir.clone(), config.history_key.as_deref())?;
+            info!(
+                dir = %dir.display(),
+                encrypted = config.history_key.is_some(),
+                "Saving conversations"
+            );
+            Ok(Some(Arc::new(store)))
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let config = Config::load()?;
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(RedactingWriter::new(Redactor::new(
+            &config.redact_patterns,
+        )?))
         .init();
 
-    let config = Config::from_env()?;
     let port = config.server_port;
     let gitlab_url = config.gitlab_url.clone();
+    let max_iterations = config.max_iterations;
+    let history_limit = config.history_limit;
 
-    let provider = Arc::new(GitLabAiProvider::new(&config)?);
-    let tools = Arc::new(ToolRegistry::new(config)?);
-    // Initialize conversation history with system prompt
-    let history = Arc::new(Mutex::new(PromptBuilder::build_initial(&gitlab_url)));
+    let provider = provider_from_config(&config)?;
+    let mut tools = ToolRegistry::new(config.clone())?;
+    let mut gitlab_status = check_tokens(&config, &mut tools).await?;
+    probe_capabilities(&config, &mut tools, &mut gitlab_status).await?;
+    let tools = Arc::new(tools);
+    // Every session starts from the system prompt
+    let idle_timeout = (config.session_idle_minutes > 0)
+        .then(|| Duration::from_secs(config.session_idle_minutes * 60));
+    let sessions = Arc::new(SessionStore::new(
+        PromptBuilder::build_initial(&gitlab_url),
+        idle_timeout,
+    ));
+    let store = open_store(&config)?;
+    if let Some(store) = &store {
+        // Pick up the shared `/chat` conversation where it left off.
+        match store.load(DEFAULT_SESSION) {
+            Ok(Some(messages)) => {
+                let mut messages: Vec<_> = messages.into_iter().map(|m| m.message).collect();
+                keep_recent(&mut messages, 0, history_limit);
+                sessions.restore(DEFAULT_SESSION, messages);
+            }
+            Ok(None) => {}
+            Err(e) => warn!(
+                error = %format!("{:#}", e),
+                "Cannot read the saved default conversation; starting it empty"
+            ),
+        }
+    }
+    if idle_timeout.is_some() {
+        let sessions = sessions.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                tick.tick().await;
+                let evicted = sessions.evict_idle();
+                if evicted > 0 {
+                    info!(evicted, "Dropped idle chat sessions");
+                }
+            }
+        });
+    }
 
     let state = AppState {
         provider,
         tools,
-        history,
-        chat_lock: Arc::new(Mutex::new(())),
+        sessions,
+        turns: Arc::default(),
+        approvals: Arc::default(),
+        require_approval: config.require_approval,
+        store,
+        max_iterations,
+        history_limit,
+        gitlab_status: Arc::new(gitlab_status),
     };
     let app = build_router(state);
 
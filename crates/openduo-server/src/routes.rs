@@ -1,27 +1,52 @@
 use axum::{
-    extract::State,
-    response::{Json, Sse},
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response, Sse},
     routing::{get, post},
     Router,
 };
 use futures::StreamExt;
-use openduo_agent::{provider::LlmProvider, react_loop::ReactLoop};
+use openduo_agent::approval::Decision;
+use openduo_agent::provider::{ChatMessage, LlmProvider};
+use openduo_agent::react_loop::{Cancelled, ReactLoop};
+use openduo_core::error::GitLabError;
+use openduo_core::types::{Issue, MergeRequest, Pipeline, Project};
 use openduo_tools::registry::ToolRegistry;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
+use crate::approvals::{Approvals, SessionApprover};
+use crate::events::{ChatEvent, Protocol, TurnStats};
+use crate::sessions::{keep_recent, Session, SessionStore, SessionSummary, DEFAULT_SESSION};
+use crate::store::{to_markdown, ConversationStore, StoredMessage};
+use crate::turns::{Turns, TURN_ID_HEADER};
 use crate::validation::validate_chat_request;
 
 #[derive(Clone)]
 pub struct AppState {
     pub provider: Arc<dyn LlmProvider>,
     pub tools: Arc<ToolRegistry>,
-    pub history: Arc<Mutex<Vec<openduo_agent::provider::ChatMessage>>>,
-    /// Serializes chat requests so only one runs at a time, preventing history races.
-    pub chat_lock: Arc<Mutex<()>>,
+    /// Conversations by session id; `/chat` uses the default session.
+    pub sessions: Arc<SessionStore>,
+    /// Turns in progress, for cancellation.
+    pub turns: Arc<Turns>,
+    /// Tool calls waiting for the user.
+    pub approvals: Arc<Approvals>,
+    /// Ask before running tools that change GitLab.
+    pub require_approval: bool,
+    /// Persists each finished turn; `None` keeps conversations in memory only.
+    pub store: Option<Arc<dyn ConversationStore>>,
+    /// Reasoning steps allowed per chat turn.
+    pub max_iterations: usize,
+    /// Messages kept in history besides the system prompt.
+    pub history_limit: usize,
+    /// Startup token check per GitLab instance, reported on `/health`.
+    pub gitlab_status: Arc<BTreeMap<String, Value>>,
 }
 
 #[derive(Deserialize)]
@@ -29,81 +54,527 @@ pub struct ChatRequest {
     pub message: String,
 }
 
-pub async fn health() -> Json<Value> {
-    Json(json!({ "status": "ok", "service": "openduo-server" }))
+type ErrorResponse = (StatusCode, Json<Value>);
+
+fn internal_error(e: anyhow::Error) -> ErrorResponse {
+    tracing::error!("{:#}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "error": format!("{:#}", e) })),
+    )
+}
+
+fn conversation_not_found(id: &str) -> ErrorResponse {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": format!("Unknown conversation: {}", id) })),
+    )
+}
+
+/// Checked before the store is asked, so a malformed id is the client's
+/// error rather than a 500.
+fn check_conversation_id(id: &str) -> Result<(), ErrorResponse> {
+    if crate::store::valid_id(id) {
+        Ok(())
+    } else {
+        Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Invalid conversation id: {}", id) })),
+        ))
+    }
+}
+
+fn session_not_found(id: &str) -> ErrorResponse {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": format!("Unknown session: {}", id) })),
+    )
+}
+
+pub async fn health(State(state): State<AppState>) -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+        "service": "openduo-server",
+        "gitlab": *state.gitlab_status,
+        "cache_stats": state.tools.cache_stats(),
+        "sessions": state.sessions.len(),
+    }))
 }
 
 pub async fn tools_list(State(state): State<AppState>) -> Json<Value> {
     Json(json!({ "tools": state.tools.definitions() }))
 }
 
+/// Browsers send `Origin`, and listening on 127.0.0.1 does not keep other
+/// web pages out, so of those only VS Code webviews (whose host part is
+/// random) may call the server. Requests without an `Origin`, such as the
+/// extension host's, are not affected.
+fn allowed_origin(origin: &HeaderValue) -> bool {
+    origin.as_bytes().starts_with(b"vscode-webview://")
+}
+
+/// CORS alone only stops pages reading responses; this also stops them
+/// triggering anything with a simple cross-site request.
+async fn reject_foreign_origins(req: Request, next: Next) -> Response {
+    match req.headers().get(header::ORIGIN) {
+        Some(origin) if !allowed_origin(origin) => (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Origin not allowed" })),
+        )
+            .into_response(),
+        _ => next.run(req).await,
+    }
+}
+
 pub fn build_router(state: AppState) -> Router {
-    // Allow any origin: server only listens on 127.0.0.1, and VS Code
-    // webviews use unpredictable vscode-webview:// origins.
     let cors = CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(AllowOrigin::predicate(|origin, _| allowed_origin(origin)))
         .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_headers(Any)
+        .expose_headers([header::HeaderName::from_static(TURN_ID_HEADER)]);
 
     Router::new()
         .route("/health", get(health))
         .route("/tools", get(tools_list))
         .route("/chat", post(chat_handler))
+        .route("/chat/:turn_id/cancel", post(cancel_turn))
+        .route("/approvals/:id", post(answer_approval))
+        .route("/sessions", post(create_session))
+        .route("/sessions/:id", get(get_session).delete(delete_session))
+        .route("/sessions/:id/chat", post(session_chat_handler))
+        .route("/conversations", get(list_conversations))
+        .route(
+            "/conversations/:id",
+            axum::routing::delete(delete_conversation),
+        )
+        .route("/conversations/:id/resume", post(resume_conversation))
+        .route("/conversations/:id/export", get(export_conversation))
+        .route("/gitlab/projects/:project", get(get_project))
+        .route("/gitlab/projects/:project/issues/:iid", get(get_issue))
+        .route(
+            "/gitlab/projects/:project/merge_requests/:iid",
+            get(get_merge_request),
+        )
+        .route("/gitlab/projects/:project/pipelines/:id", get(get_pipeline))
+        .layer(middleware::from_fn(reject_foreign_origins))
         .layer(cors)
         .with_state(state)
 }
 
+pub async fn create_session(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    let session = state.sessions.create();
+    tracing::info!(session = %session.id, "Chat session created");
+    (StatusCode::CREATED, Json(json!({ "id": session.id })))
+}
+
+pub async fn get_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SessionSummary>, ErrorResponse> {
+    let session = state
+        .sessions
+        .get(&id)
+        .ok_or_else(|| session_not_found(&id))?;
+    Ok(Json(session.summary().await))
+}
+
+pub async fn delete_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ErrorResponse> {
+    let session = state
+        .sessions
+        .get(&id)
+        .filter(|_| id != DEFAULT_SESSION)
+        .ok_or_else(|| session_not_found(&id))?;
+    let _history = session.history.lock().await;
+    session.invalidate();
+    state.sessions.remove(&id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_conversations(
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ErrorResponse> {
+    let conversations = match &state.store {
+        Some(store) => store.list().map_err(internal_error)?,
+        None => Vec::new(),
+    };
+    Ok(Json(json!({ "conversations": conversations })))
+}
+
+fn load_conversation(state: &AppState, id: &str) -> Result<Vec<StoredMessage>, ErrorResponse> {
+    check_conversation_id(id)?;
+    let store = state
+        .store
+        .as_ref()
+        .ok_or_else(|| conversation_not_found(id))?;
+    store
+        .load(id)
+        .map_err(internal_error)?
+        .ok_or_else(|| conversation_not_found(id))
+}
+
+/// Make a stored conversation a live session again; chat continues with
+/// `/sessions/:id/chat`.
+pub async fn resume_conversation(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, ErrorResponse> {
+    if state.sessions.get(&id).is_none() {
+        let mut messages: Vec<ChatMessage> = load_conversation(&state, &id)?
+            .into_iter()
+            .map(|m| m.message)
+            .collect();
+        // Only the last `history_limit` messages go back to the model.
+        keep_recent(&mut messages, 0, state.history_limit);
+        state.sessions.restore(&id, messages);
+        tracing::info!(session = %id, "Conversation resumed");
+    }
+    Ok(Json(json!({ "id": id })))
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    /// `json` (default) or `markdown`.
+    pub format: Option<String>,
+}
+
+pub async fn export_conversation(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ErrorResponse> {
+    let messages = load_conversation(&state, &id)?;
+    Ok(match query.format.as_deref().unwrap_or("json") {
+        "json" => Json(json!({ "id": id, "messages": messages })).into_response(),
+        "markdown" | "md" => (
+            [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+            to_markdown(&id, &messages),
+        )
+            .into_response(),
+        other => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(
+                    json!({ "error": format!("Unknown export format: {} (expected json or markdown)", other) }),
+                ),
+            ))
+        }
+    })
+}
+
+/// Delete a stored conversation and end its live session, if any.
+pub async fn delete_conversation(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ErrorResponse> {
+    check_conversation_id(&id)?;
+    // With the history locked no running turn can save between the
+    // invalidation and the delete and bring the conversation back.
+    let session = state.sessions.get(&id);
+    let mut history = match &session {
+        Some(session) => Some(session.history.lock().await),
+        None => None,
+    };
+    if let Some(session) = &session {
+        session.invalidate();
+    }
+    let stored = match &state.store {
+        Some(store) => store.delete(&id).map_err(internal_error)?,
+        None => false,
+    };
+    let live = match history.as_mut() {
+        Some(history) if id == DEFAULT_SESSION => {
+            **history = state.sessions.initial_history().to_vec();
+            true
+        }
+        _ => state.sessions.remove(&id),
+    };
+    if !stored && !live {
+        return Err(conversation_not_found(&id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct InstanceQuery {
+    /// GitLab instance profile; the default one if absent.
+    pub instance: Option<String>,
+}
+
+/// Fetch a GitLab resource as its typed model, for the chat panel to show.
+/// `project` is an id or a full path.
+async fn fetch<T: DeserializeOwned>(
+    state: &AppState,
+    instance: Option<&str>,
+    project: &str,
+    resource: &str,
+) -> Result<Json<T>, ErrorResponse> {
+    let client = state.tools.client(instance).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Unknown GitLab instance: {}", instance.unwrap_or_default()) })),
+        )
+    })?;
+    let path = format!("projects/{}{}", urlencoding::encode(project), resource);
+    client.get(&path).await.map(Json).map_err(|e: GitLabError| {
+        let status = e
+            .status()
+            .and_then(|s| StatusCode::from_u16(s.as_u16()).ok())
+            .unwrap_or(StatusCode::BAD_GATEWAY);
+        (status, Json(json!({ "error": e.to_string() })))
+    })
+}
+
+pub async fn get_project(
+    State(state): State<AppState>,
+    Path(project): Path<String>,
+    Query(query): Query<InstanceQuery>,
+) -> Result<Json<Project>, ErrorResponse> {
+    fetch(&state, query.instance.as_deref(), &project, "").await
+}
+
+pub async fn get_issue(
+    State(state): State<AppState>,
+    Path((project, iid)): Path<(String, u64)>,
+    Query(query): Query<InstanceQuery>,
+) -> Result<Json<Issue>, ErrorResponse> {
+    let resource = format!("/issues/{}", iid);
+    fetch(&state, query.instance.as_deref(), &project, &resource).await
+}
+
+pub async fn get_merge_request(
+    State(state): State<AppState>,
+    Path((project, iid)): Path<(String, u64)>,
+    Query(query): Query<InstanceQuery>,
+) -> Result<Json<MergeRequest>, ErrorResponse> {
+    let resource = format!("/merge_requests/{}", iid);
+    fetch(&state, query.instance.as_deref(), &project, &resource).await
+}
+
+pub async fn get_pipeline(
+    State(state): State<AppState>,
+    Path((project, id)): Path<(String, u64)>,
+    Query(query): Query<InstanceQuery>,
+) -> Result<Json<Pipeline>, ErrorResponse> {
+    let resource = format!("/pipelines/{}", id);
+    fetch(&state, query.instance.as_deref(), &project, &resource).await
+}
+
+/// Keep the system prompt and about the last `limit` messages.
+fn trim_history(history: &mut Vec<ChatMessage>, limit: usize) {
+    keep_recent(history, 1, limit);
+}
+
+#[derive(Deserialize)]
+pub struct ChatQuery {
+    /// `events` or `text`; see `Protocol`.
+    pub protocol: Option<Protocol>,
+}
+
+/// Chat in the default session, shared by every client that does not
+/// create its own. Streams bare text unless `?protocol=events`.
 pub async fn chat_handler(
     State(state): State<AppState>,
+    Query(query): Query<ChatQuery>,
     Json(req): Json<ChatRequest>,
-) -> Sse<impl futures::Stream<Item = Result<axum::response::sse::Event, std::convert::Infallible>>>
-{
-    let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+) -> Response {
+    let session = state
+        .sessions
+        .get(DEFAULT_SESSION)
+        .expect("default session always exists");
+    let protocol = query.protocol.unwrap_or(Protocol::Text);
+    run_turn(state, session, req.message, protocol)
+}
 
-    if let Err(e) = validate_chat_request(&req.message) {
-        let _ = tx.send(format!("[ERROR] {}", e));
-        let _ = tx.send("[DONE]".to_string());
+/// Chat in a session. Streams typed events unless `?protocol=text`.
+pub async fn session_chat_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<ChatQuery>,
+    Json(req): Json<ChatRequest>,
+) -> Result<Response, ErrorResponse> {
+    let session = state
+        .sessions
+        .get(&id)
+        .ok_or_else(|| session_not_found(&id))?;
+    let protocol = query.protocol.unwrap_or(Protocol::Events);
+    Ok(run_turn(state, session, req.message, protocol))
+}
+
+/// Run one chat turn in `session`, streaming its progress as SSE in the
+/// given protocol and always ending with a `done` event. The turn id is
+/// returned in `TURN_ID_HEADER`; the turn is cancelled if the client
+/// disconnects.
+fn run_turn(
+    state: AppState,
+    session: Arc<Session>,
+    message: String,
+    protocol: Protocol,
+) -> Response {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<ChatEvent>();
+    let mut turn_id = None;
+
+    if let Err(e) = validate_chat_request(&message) {
+        let _ = tx.send(ChatEvent::Error {
+            code: "invalid_request",
+            message: e.to_string(),
+        });
+        let _ = tx.send(ChatEvent::Done(TurnStats::default().summary(None, false)));
     } else {
         let provider = state.provider.clone();
         let tools = state.tools.clone();
-        let history = state.history.clone();
-        let chat_lock = state.chat_lock.clone();
-        let message = req.message;
+        let store = state.store.clone();
+        let turns = state.turns.clone();
+        let approver = state.require_approval.then(|| {
+            Arc::new(SessionApprover {
+                approvals: state.approvals.clone(),
+                session: session.clone(),
+                interactive: protocol == Protocol::Events,
+            })
+        });
+        let max_iterations = state.max_iterations;
+        let history_limit = state.history_limit;
+        let (id, cancel) = turns.start();
+        turn_id = Some(id.clone());
+        session.touch();
 
         tokio::spawn(async move {
-            // Serialize chat requests to prevent history race conditions
-            let _guard = chat_lock.lock().await;
-            let react_loop = ReactLoop::new(15);
-            let mut hist = history.lock().await.clone();
-            match react_loop
-                .run(&message, &mut hist, &provider, &tools, |token| {
-                    let _ = tx.send(token);
+            let stats = std::sync::Mutex::new(TurnStats::default());
+            // The receiver goes away when the client disconnects.
+            let watcher = {
+                let tx = tx.clone();
+                let cancel = cancel.clone();
+                tokio::spawn(async move {
+                    tx.closed().await;
+                    cancel.cancel();
                 })
-                .await
-            {
-                Ok(_) => {
-                    // Trim history to prevent unbounded growth (keep system prompt + last 50 messages)
-                    if hist.len() > 51 {
-                        let system = hist[0].clone();
-                        hist = std::iter::once(system)
-                            .chain(hist[hist.len() - 50..].iter().cloned())
-                            .collect();
-                    }
-                    *history.lock().await = hist;
+            };
+            // One turn at a time per session; other sessions are unaffected.
+            let guard = tokio::select! {
+                guard = session.turn_lock.lock() => Some(guard),
+                _ = cancel.cancelled() => None,
+            };
+            let mut cancelled = guard.is_none();
+            if let Some(_guard) = guard {
+                let mut react_loop = ReactLoop::new(max_iterations).with_cancellation(cancel);
+                if let Some(approver) = approver {
+                    react_loop = react_loop.with_approver(approver);
                 }
-                Err(e) => {
-                    tracing::error!("ReactLoop error: {:#}", e);
-                    let _ = tx.send(format!("Error: {}", e));
+                let (mut hist, generation) = {
+                    let history = session.history.lock().await;
+                    (history.clone(), session.generation())
+                };
+                let before = hist.len();
+                let result = react_loop
+                    .run(&message, &mut hist, &provider, &tools, |event| {
+                        stats.lock().unwrap().record(&event);
+                        let _ = tx.send(ChatEvent::Agent(event));
+                    })
+                    .await;
+                // A cancelled turn still leaves a consistent history to keep.
+                let keep = match result {
+                    Ok(_) => true,
+                    Err(e) if e.is::<Cancelled>() => {
+                        tracing::info!(session = %session.id, turn = %id, "Turn cancelled");
+                        cancelled = true;
+                        true
+                    }
+                    Err(e) => {
+                        tracing::error!(session = %session.id, "ReactLoop error: {:#}", e);
+                        let _ = tx.send(ChatEvent::Error {
+                            code: "agent_error",
+                            message: e.to_string(),
+                        });
+                        false
+                    }
+                };
+                let mut history = session.history.lock().await;
+                if keep && session.generation() != generation {
+                    tracing::info!(session = %session.id, turn = %id, "Session deleted during the turn; not saving it");
+                } else if keep {
+                    if let Some(store) = &store {
+                        if let Err(e) = store.append(&session.id, &hist[before..]) {
+                            tracing::warn!(session = %session.id, "Could not save conversation: {:#}", e);
+                        }
+                    }
+                    // Trim history to prevent unbounded growth (keep system prompt + last N messages)
+                    trim_history(&mut hist, history_limit);
+                    *history = hist;
                 }
             }
-            let _ = tx.send("[DONE]".to_string());
+            watcher.abort();
+            turns.finish(&id);
+            session.touch();
+            let summary = stats.lock().unwrap().summary(Some(id), cancelled);
+            let _ = tx.send(ChatEvent::Done(summary));
         });
     }
 
-    let stream = tokio_stream::wrappers::UnboundedReceiverStream::new(rx).map(|data| {
-        Ok::<_, std::convert::Infallible>(axum::response::sse::Event::default().data(data))
-    });
-    Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default())
+    let stream = tokio_stream::wrappers::UnboundedReceiverStream::new(rx)
+        .filter_map(move |event| futures::future::ready(event.into_sse(protocol)))
+        .map(Ok::<_, std::convert::Infallible>);
+    let sse = Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default());
+    match turn_id {
+        Some(id) => ([(TURN_ID_HEADER, id)], sse).into_response(),
+        None => sse.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalDecision {
+    Approve,
+    Reject,
+}
+
+#[derive(Deserialize)]
+pub struct ApprovalAnswer {
+    pub decision: ApprovalDecision,
+    /// Run the call with these arguments instead; approvals only.
+    pub arguments: Option<Value>,
+    /// Also run this tool without asking for the rest of the session.
+    #[serde(default)]
+    pub always_allow: bool,
+    /// Passed on to the model with a rejection.
+    pub reason: Option<String>,
+}
+
+/// Answer an `approval_required` event; the waiting turn carries on.
+pub async fn answer_approval(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(answer): Json<ApprovalAnswer>,
+) -> Result<StatusCode, ErrorResponse> {
+    let decision = match (answer.decision, answer.arguments) {
+        (ApprovalDecision::Approve, None) => Decision::Approve,
+        (ApprovalDecision::Approve, Some(arguments)) => Decision::Edit(arguments),
+        (ApprovalDecision::Reject, _) => Decision::Reject {
+            reason: answer.reason,
+        },
+    };
+    if !state.approvals.resolve(&id, decision, answer.always_allow) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("No pending approval: {}", id) })),
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Stop a running turn. Whatever it finished stays in the session.
+pub async fn cancel_turn(
+    State(state): State<AppState>,
+    Path(turn_id): Path<String>,
+) -> Result<StatusCode, ErrorResponse> {
+    if !state.turns.cancel(&turn_id) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("No running turn: {}", turn_id) })),
+        ));
+    }
+    Ok(StatusCode::ACCEPTED)
 }
 
 #[cfg(test)]
@@ -111,16 +582,701 @@ mod tests {
     use super::*;
     use axum::body::Body;
     use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use openduo_agent::prompt::PromptBuilder;
+    use openduo_agent::testing::{ScriptedProvider, ScriptedTurn};
+    use std::time::Duration;
     use tower::ServiceExt;
 
+    fn test_state(gitlab_status: BTreeMap<String, Value>) -> AppState {
+        let config = openduo_core::config::Config::default();
+        AppState {
+            provider: openduo_agent::provider::provider_from_config(&config).unwrap(),
+            tools: Arc::new(ToolRegistry::empty()),
+            sessions: Arc::new(SessionStore::new(
+                PromptBuilder::build_initial("https://gitlab.example.com"),
+                None,
+            )),
+            turns: Arc::default(),
+            approvals: Arc::default(),
+            require_approval: config.require_approval,
+            store: None,
+            max_iterations: config.max_iterations,
+            history_limit: config.history_limit,
+            gitlab_status: Arc::new(gitlab_status),
+        }
+    }
+
+    fn scripted_state(turns: Vec<ScriptedTurn>) -> AppState {
+        AppState {
+            provider: Arc::new(ScriptedProvider::new(turns)),
+            ..test_state(BTreeMap::new())
+        }
+    }
+
+    async fn send(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, String) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
     #[tokio::test]
     async fn test_health_returns_ok() {
-        let app = Router::new().route("/health", get(health));
+        let status = BTreeMap::from([(
+            "default".to_string(),
+            json!({ "username": "alice", "missing_scopes": ["ai_features"] }),
+        )]);
+        let app = build_router(test_state(status));
         let req = Request::builder()
             .uri("/health")
             .body(Body::empty())
             .unwrap();
         let resp = app.oneshot(req).await.unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["status"], "ok");
+        assert_eq!(
+            body["gitlab"]["default"]["missing_scopes"][0],
+            "ai_features"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_lifecycle() {
+        let app = build_router(scripted_state(vec![ScriptedTurn::text("Hi there")]));
+        let (status, body) = send(&app, "POST", "/sessions", None).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id = serde_json::from_str::<Value>(&body).unwrap()["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(id.len(), 32);
+
+        let chat = format!("/sessions/{}/chat", id);
+        let (status, body) = send(&app, "POST", &chat, Some(json!({ "message": "Hello" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(
+            body.contains("event: token\ndata: {\"text\":\"Hi there\"}"),
+            "{body}"
+        );
+        assert!(body.contains("event: done\n"), "{body}");
+
+        let (status, body) = send(&app, "GET", &format!("/sessions/{}", id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let summary: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(summary["messages"][0]["content"], "Hello");
+        assert_eq!(summary["messages"][1]["content"], "Hi there");
+        assert_eq!(summary["busy"], false);
+
+        // The default session behind `/chat` is separate.
+        let (_, body) = send(&app, "GET", "/sessions/default", None).await;
+        let default: Value = serde_json::from_str(&body).unwrap();
+        assert!(default["messages"].as_array().unwrap().is_empty());
+
+        let (status, _) = send(&app, "DELETE", &format!("/sessions/{}", id), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, "GET", &format!("/sessions/{}", id), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, body) = send(&app, "POST", &chat, Some(json!({ "message": "Hello" }))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.contains("Unknown session"), "{body}");
+        let (status, _) = send(&app, "DELETE", "/sessions/default", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    /// `(event, payload)` pairs of an SSE body in the `events` protocol.
+    fn parse_events(body: &str) -> Vec<(String, Value)> {
+        body.split("\n\n")
+            .filter_map(|frame| {
+                let event = frame.strip_prefix("event: ")?;
+                let (name, data) = event.split_once("\ndata: ")?;
+                Some((name.to_string(), serde_json::from_str(data).unwrap()))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_session_chat_streams_typed_events() {
+        use openduo_agent::provider::{ModelResponse, Usage};
+        let mut registry = ToolRegistry::empty();
+        registry.register(Box::new(openduo_agent::testing::FakeTool::new(
+            "list_issues",
+            "[]",
+        )));
+        let app = build_router(AppState {
+            tools: Arc::new(registry),
+            ..scripted_state(vec![
+                ScriptedTurn::tool_call("list_issues", json!({ "project_id": "g/p" })),
+                ScriptedTurn::Events(vec![
+                    Ok(ModelResponse::Token("None open.".to_string())),
+                    Ok(ModelResponse::Usage(Usage {
+                        prompt_tokens: 80,
+                        completion_tokens: 4,
+                    })),
+                    Ok(ModelResponse::Done),
+                ]),
+            ])
+        });
+        let (_, body) = send(&app, "POST", "/sessions", None).await;
+        let id = serde_json::from_str::<Value>(&body).unwrap()["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let chat = format!("/sessions/{}/chat", id);
+        let (_, body) = send(&app, "POST", &chat, Some(json!({ "message": "Any?" }))).await;
+
+        let events = parse_events(&body);
+        let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "iteration",
+                "tool_call_started",
+                "tool_call_finished",
+                "iteration",
+                "token",
+                "done"
+            ],
+            "{body}"
+        );
+        assert_eq!(events[1].1["name"], "list_issues");
+        assert_eq!(events[1].1["arguments"]["project_id"], "g/p");
+        assert_eq!(events[2].1["result_bytes"], 2);
+        assert!(events[2].1["error"].is_null());
+        assert_eq!(events[4].1["text"], "None open.");
+        let done = &events[5].1;
+        assert_eq!(done["iterations"], 2);
+        assert_eq!(done["tool_calls"], 1);
+        assert_eq!(done["cancelled"], false);
+        assert_eq!(done["usage"]["prompt_tokens"], 80);
+        assert_eq!(done["usage"]["completion_tokens"], 4);
+        assert!(done["turn_id"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_errors_in_each_protocol() {
+        let app = build_router(scripted_state(vec![ScriptedTurn::Fail(
+            "connection refused".to_string(),
+        )]));
+        let empty = Some(json!({ "message": " " }));
+
+        let (_, body) = send(&app, "POST", "/chat", empty.clone()).await;
+        assert_eq!(
+            body,
+            "data: [ERROR] Message cannot be empty\n\ndata: [DONE]\n\n"
+        );
+
+        let (_, body) = send(&app, "POST", "/chat?protocol=events", empty).await;
+        let events = parse_events(&body);
+        assert_eq!(events[0].0, "error");
+        assert_eq!(events[0].1["code"], "invalid_request");
+        assert_eq!(events[0].1["message"], "Message cannot be empty");
+        assert_eq!(events[1].0, "done");
+
+        let (_, body) = send(
+            &app,
+            "POST",
+            "/sessions/default/chat",
+            Some(json!({ "message": "Hi" })),
+        )
+        .await;
+        let events = parse_events(&body);
+        let error = events.iter().find(|(name, _)| name == "error").unwrap();
+        assert_eq!(error.1["code"], "agent_error");
+        assert!(error.1["message"]
+            .as_str()
+            .unwrap()
+            .contains("connection refused"));
+        assert_eq!(events.last().unwrap().0, "done");
+    }
+
+    #[tokio::test]
+    async fn test_text_protocol_on_sessions() {
+        let state = scripted_state(vec![ScriptedTurn::text("Hi there")]);
+        let session = state.sessions.create();
+        let app = build_router(state);
+        let chat = format!("/sessions/{}/chat?protocol=text", session.id);
+        let (_, body) = send(&app, "POST", &chat, Some(json!({ "message": "Hello" }))).await;
+        assert_eq!(body, "data: Hi there\n\ndata: [DONE]\n\n");
+    }
+
+    fn approval_state() -> (AppState, Arc<std::sync::Mutex<Vec<Value>>>) {
+        use openduo_agent::testing::FakeTool;
+        use openduo_tools::registry::RiskLevel;
+        let close = FakeTool::new("close_issue", "closed").with_risk(RiskLevel::Destructive);
+        let calls = close.invocations();
+        let mut registry = ToolRegistry::empty();
+        registry.register(Box::new(close));
+        let state = AppState {
+            tools: Arc::new(registry),
+            require_approval: true,
+            ..scripted_state(vec![
+                ScriptedTurn::tool_call("close_issue", json!({ "issue_iid": 2 })),
+                ScriptedTurn::text("Closed."),
+                ScriptedTurn::tool_call("close_issue", json!({ "issue_iid": 4 })),
+                ScriptedTurn::text("Closed again."),
+            ])
+        };
+        (state, calls)
+    }
+
+    /// Read SSE frames from `body` until an event named `name` arrives.
+    async fn next_event(body: &mut Body, name: &str) -> Value {
+        let mut buffer = String::new();
+        loop {
+            let frame = body.frame().await.unwrap().unwrap().into_data().unwrap();
+            buffer.push_str(std::str::from_utf8(&frame).unwrap());
+            if let Some((_, payload)) = parse_events(&buffer).into_iter().find(|(n, _)| n == name) {
+                return payload;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_approval_round_trip() {
+        let (state, calls) = approval_state();
+        let session = state.sessions.create();
+        let app = build_router(state);
+        let chat = format!("/sessions/{}/chat", session.id);
+        let req = Request::builder()
+            .method("POST")
+            .uri(&chat)
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "message": "Close #2" }).to_string()))
+            .unwrap();
+        let mut body = app.clone().oneshot(req).await.unwrap().into_body();
+
+        let approval = next_event(&mut body, "approval_required").await;
+        assert_eq!(approval["tool"], "close_issue");
+        assert_eq!(approval["risk"], "destructive");
+        assert_eq!(approval["arguments"]["issue_iid"], 2);
+        assert_eq!(approval["requests"][0]["method"], "POST");
+        assert!(calls.lock().unwrap().is_empty());
+
+        let answer = format!("/approvals/{}", approval["approval_id"].as_str().unwrap());
+        let edit = json!({
+            "decision": "approve",
+            "arguments": { "issue_iid": 3 },
+            "always_allow": true
+        });
+        let (status, _) = send(&app, "POST", &answer, Some(edit.clone())).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let finished = next_event(&mut body, "tool_call_finished").await;
+        assert!(finished["error"].is_null());
+        next_event(&mut body, "done").await;
+        assert_eq!(*calls.lock().unwrap(), vec![json!({ "issue_iid": 3 })]);
+
+        // Answered approvals are gone.
+        let (status, _) = send(&app, "POST", &answer, Some(edit)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // The tool is now allowed for this session without asking.
+        let (_, summary) = send(&app, "GET", &format!("/sessions/{}", session.id), None).await;
+        let summary: Value = serde_json::from_str(&summary).unwrap();
+        assert_eq!(summary["always_allowed"], json!(["close_issue"]));
+        let (_, body) = send(&app, "POST", &chat, Some(json!({ "message": "And #4" }))).await;
+        let names: Vec<String> = parse_events(&body).into_iter().map(|(n, _)| n).collect();
+        assert!(!names.contains(&"approval_required".to_string()), "{body}");
+        assert_eq!(calls.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_text_protocol_rejects_calls_needing_approval() {
+        let (state, calls) = approval_state();
+        let app = build_router(state);
+        let (_, body) = send(
+            &app,
+            "POST",
+            "/chat",
+            Some(json!({ "message": "Close #2" })),
+        )
+        .await;
+        let lines: Vec<&str> = body.split("\n\n").collect();
+        assert!(
+            lines[0].starts_with("data: [ERROR] close_issue needs approval")
+                && lines[0].contains("/sessions/{id}/chat"),
+            "{body}"
+        );
+        assert_eq!(lines[1..], ["data: Closed.", "data: [DONE]", ""]);
+        assert!(calls.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_conversation_deleted_mid_turn_stays_deleted() {
+        let dir =
+            std::env::temp_dir().join(format!("openduo-routes-deleted-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store: Arc<dyn ConversationStore> =
+            Arc::new(crate::store::JsonlStore::open(dir, None).unwrap());
+        let (state, _) = approval_state();
+        let session = state.sessions.create();
+        let app = build_router(AppState {
+            store: Some(store.clone()),
+            ..state
+        });
+        let req = Request::builder()
+            .method("POST")
+            .uri(format!("/sessions/{}/chat", session.id))
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "message": "Close #2" }).to_string()))
+            .unwrap();
+        let mut body = app.clone().oneshot(req).await.unwrap().into_body();
+        let approval = next_event(&mut body, "approval_required").await;
+
+        let conversation = format!("/conversations/{}", session.id);
+        let (status, _) = send(&app, "DELETE", &conversation, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let answer = format!("/approvals/{}", approval["approval_id"].as_str().unwrap());
+        send(
+            &app,
+            "POST",
+            &answer,
+            Some(json!({ "decision": "approve" })),
+        )
+        .await;
+        next_event(&mut body, "done").await;
+
+        assert!(store.load(&session.id).unwrap().is_none());
+        assert_eq!(session.history.lock().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_busy_session_does_not_block_others() {
+        let state = scripted_state(vec![ScriptedTurn::text("B answered")]);
+        let busy = state.sessions.create();
+        let other = state.sessions.create();
+        let app = build_router(state);
+        let _turn = busy.turn_lock.lock().await;
+
+        let chat = format!("/sessions/{}/chat", other.id);
+        let (_, body) = tokio::time::timeout(
+            Duration::from_secs(5),
+            send(&app, "POST", &chat, Some(json!({ "message": "Hi" }))),
+        )
+        .await
+        .expect("second session was blocked by the first");
+        assert!(body.contains("B answered"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn test_idle_sessions_are_evicted() {
+        let store = SessionStore::new(Vec::new(), Some(Duration::ZERO));
+        let idle = store.create();
+        let busy = store.create();
+        let _turn = busy.turn_lock.lock().await;
+        assert_eq!(store.evict_idle(), 1);
+        assert!(store.get(&idle.id).is_none());
+        assert!(store.get(&busy.id).is_some());
+        assert!(store.get(DEFAULT_SESSION).is_some());
+
+        let keep = SessionStore::new(Vec::new(), None);
+        keep.create();
+        assert_eq!(keep.evict_idle(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_conversations_survive_restart() {
+        let dir =
+            std::env::temp_dir().join(format!("openduo-routes-history-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store: Arc<dyn ConversationStore> =
+            Arc::new(crate::store::JsonlStore::open(dir, None).unwrap());
+
+        let app = build_router(AppState {
+            store: Some(store.clone()),
+            ..scripted_state(vec![ScriptedTurn::text("Two pipelines failed")])
+        });
+        let (_, body) = send(&app, "POST", "/sessions", None).await;
+        let id = serde_json::from_str::<Value>(&body).unwrap()["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let chat = format!("/sessions/{}/chat", id);
+        send(
+            &app,
+            "POST",
+            &chat,
+            Some(json!({ "message": "Any failed pipelines?" })),
+        )
+        .await;
+
+        // A new server process: fresh sessions, same store.
+        let app = build_router(AppState {
+            store: Some(store),
+            ..scripted_state(vec![ScriptedTurn::text("Retried")])
+        });
+        let (_, body) = send(&app, "GET", "/conversations", None).await;
+        let list: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(list["conversations"][0]["id"], id.as_str());
+        assert_eq!(list["conversations"][0]["title"], "Any failed pipelines?");
+
+        let (status, _) = send(
+            &app,
+            "POST",
+            &chat,
+            Some(json!({ "message": "Retry them" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, "POST", &format!("/conversations/{}/resume", id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        send(
+            &app,
+            "POST",
+            &chat,
+            Some(json!({ "message": "Retry them" })),
+        )
+        .await;
+        let (_, body) = send(&app, "GET", &format!("/sessions/{}", id), None).await;
+        let summary: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(summary["messages"].as_array().unwrap().len(), 4);
+        assert_eq!(summary["messages"][1]["content"], "Two pipelines failed");
+
+        let export = format!("/conversations/{}/export", id);
+        let (_, body) = send(&app, "GET", &export, None).await;
+        let exported: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(exported["messages"].as_array().unwrap().len(), 4);
+        assert!(exported["messages"][0]["ts"].as_u64().unwrap() > 0);
+        let (_, body) = send(&app, "GET", &format!("{}?format=markdown", export), None).await;
+        assert!(body.contains("**User:** Retry them"), "{body}");
+        let (status, _) = send(&app, "GET", &format!("{}?format=pdf", export), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = send(&app, "DELETE", &format!("/conversations/{}", id), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, body) = send(&app, "GET", "/conversations", None).await;
+        assert_eq!(body, r#"{"conversations":[]}"#);
+        let (status, _) = send(&app, "GET", &format!("/sessions/{}", id), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_conversation_ids_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("openduo-routes-ids-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store: Arc<dyn ConversationStore> =
+            Arc::new(crate::store::JsonlStore::open(dir, None).unwrap());
+        let app = build_router(AppState {
+            store: Some(store),
+            ..test_state(BTreeMap::new())
+        });
+        for (method, uri) in [
+            ("GET", "/conversations/..%2F..%2Fetc%2Fpasswd/export"),
+            ("POST", "/conversations/..%2Fsecrets/resume"),
+            ("DELETE", "/conversations/a.b"),
+        ] {
+            let (status, body) = send(&app, method, uri, None).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            assert!(body.contains("Invalid conversation id"), "{body}");
+        }
+        let (status, _) = send(&app, "GET", "/conversations/abc123/export", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_only_webviews_may_call_from_a_browser() {
+        let app = build_router(test_state(BTreeMap::new()));
+        let get = |origin: &str| {
+            Request::builder()
+                .uri("/conversations")
+                .header(header::ORIGIN, origin)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let resp = app
+            .clone()
+            .oneshot(get("vscode-webview://1a2b3c"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "vscode-webview://1a2b3c"
+        );
+
+        let resp = app
+            .clone()
+            .oneshot(get("https://evil.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(resp
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+
+        // No Origin: the extension host, curl.
+        let (status, _) = send(&app, "GET", "/conversations", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_gitlab_resources_are_typed() {
+        use openduo_test_support::fixtures::PROJECT_PATH;
+        let mock = openduo_test_support::MockGitLab::start().await;
+        let app = build_router(AppState {
+            tools: Arc::new(ToolRegistry::new(mock.config()).unwrap()),
+            ..test_state(BTreeMap::new())
+        });
+        let project = urlencoding::encode(PROJECT_PATH);
+
+        let (status, body) = send(
+            &app,
+            "GET",
+            &format!("/gitlab/projects/{}/merge_requests/1", project),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let mr: MergeRequest = serde_json::from_str(&body).unwrap();
+        assert_eq!(mr.head_pipeline.unwrap().status, "failed");
+        // Only the modelled fields are sent.
+        assert!(!body.contains("changes"), "{body}");
+
+        let (status, body) =
+            send(&app, "GET", &format!("/gitlab/projects/{}", project), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let parsed: Project = serde_json::from_str(&body).unwrap();
+        assert_eq!(parsed.path_with_namespace, PROJECT_PATH);
+
+        let (status, _) = send(
+            &app,
+            "GET",
+            &format!("/gitlab/projects/{}/issues/999", project),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(
+            &app,
+            "GET",
+            &format!("/gitlab/projects/{}/pipelines/1001?instance=dr", project),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_trim_keeps_tool_results_with_their_calls() {
+        use openduo_agent::provider::{ChatRole, ToolCall};
+        let call = |id: &str| ChatMessage {
+            tool_calls: vec![ToolCall {
+                id: id.to_string(),
+                name: "list_pipelines".to_string(),
+                arguments: json!({}),
+            }],
+            ..ChatMessage::new(ChatRole::Assistant, "")
+        };
+        let result = |id: &str| ChatMessage {
+            tool_call_id: Some(id.to_string()),
+            ..ChatMessage::new(ChatRole::Tool, "[]")
+        };
+        let mut history = vec![
+            ChatMessage::new(ChatRole::System, "prompt"),
+            ChatMessage::new(ChatRole::User, "Any pipelines?"),
+            call("a"),
+            result("a"),
+            ChatMessage::new(ChatRole::Assistant, "None"),
+            ChatMessage::new(ChatRole::User, "And now?"),
+            call("b"),
+            result("b"),
+            ChatMessage::new(ChatRole::Assistant, "Still none"),
+        ];
+        // A plain cut of the last 6 would start at the result of call "a".
+        trim_history(&mut history, 6);
+        let roles: Vec<_> = history.iter().map(|m| format!("{:?}", m.role)).collect();
+        assert_eq!(roles, ["System", "User", "Assistant", "Tool", "Assistant"]);
+        assert_eq!(history[1].content, "And now?");
+    }
+
+    /// Poll until the session's turn has released its lock.
+    async fn wait_idle(session: &Session) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while session.turn_lock.try_lock().is_err() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("turn did not stop");
+    }
+
+    fn stalled() -> ScriptedTurn {
+        ScriptedTurn::Stall(vec![openduo_agent::provider::ModelResponse::Token(
+            "Looking".to_string(),
+        )])
+    }
+
+    #[tokio::test]
+    async fn test_cancel_endpoint_stops_turn() {
+        let state = scripted_state(vec![stalled()]);
+        let session = state.sessions.get(DEFAULT_SESSION).unwrap();
+        let app = build_router(state);
+        let req = Request::builder()
+            .method("POST")
+            .uri("/chat")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "message": "Find it" }).to_string()))
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        let turn_id = resp.headers()[TURN_ID_HEADER].to_str().unwrap().to_string();
+        let mut body = resp.into_body();
+        let first = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(first, "data: Looking\n\n");
+
+        let cancel = format!("/chat/{}/cancel", turn_id);
+        let (status, _) = send(&app, "POST", &cancel, None).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let body = body.collect().await.unwrap().to_bytes();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.ends_with("data: [DONE]\n\n"), "{body}");
+
+        let history = session.history.lock().await.clone();
+        assert_eq!(history.len(), 3);
+        assert!(history[2]
+            .content
+            .ends_with(openduo_agent::react_loop::CANCELLED_NOTE));
+        // Finished turns can no longer be cancelled.
+        let (status, _) = send(&app, "POST", &cancel, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_cancels_turn() {
+        let state = scripted_state(vec![stalled()]);
+        let session = state.sessions.create();
+        let app = build_router(state);
+        let req = Request::builder()
+            .method("POST")
+            .uri(format!("/sessions/{}/chat", session.id))
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "message": "Find it" }).to_string()))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        let mut body = resp.into_body();
+        // Wait for the turn to start streaming, then hang up.
+        body.frame().await.unwrap().unwrap();
+        drop(body);
+
+        wait_idle(&session).await;
+        let history = session.history.lock().await.clone();
+        assert_eq!(history.len(), 3);
+        assert!(history[2]
+            .content
+            .ends_with(openduo_agent::react_loop::CANCELLED_NOTE));
     }
 }
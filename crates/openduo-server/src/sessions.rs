@@ -0,0 +1,207 @@
+//! Chat sessions: one conversation history per client, each serialized by
+//! its own lock so separate sessions can run turns concurrently.
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use openduo_agent::provider::{ChatMessage, ChatRole};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Session behind the legacy `/chat` endpoint; never evicted.
+pub const DEFAULT_SESSION: &str = "default";
+
+pub struct Session {
+    pub id: String,
+    pub history: Mutex<Vec<ChatMessage>>,
+    /// Held for the length of a turn so a session runs one turn at a time.
+    pub turn_lock: Mutex<()>,
+    /// Tools the user chose to run without approval in this session.
+    always_allowed: std::sync::Mutex<BTreeSet<String>>,
+    /// Bumped, with `history` locked, when the session or its conversation
+    /// is deleted, so a turn that started earlier does not save into it.
+    generation: AtomicU64,
+    created: Instant,
+    last_active: std::sync::Mutex<Instant>,
+}
+
+impl Session {
+    fn new(id: String, history: Vec<ChatMessage>) -> Self {
+        let now = Instant::now();
+        Self {
+            id,
+            history: Mutex::new(history),
+            turn_lock: Mutex::new(()),
+            always_allowed: std::sync::Mutex::default(),
+            generation: AtomicU64::new(0),
+            created: now,
+            last_active: std::sync::Mutex::new(now),
+        }
+    }
+
+    pub fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    pub fn idle_for(&self) -> Duration {
+        self.last_active.lock().unwrap().elapsed()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Stop turns already running from saving their results. Call with
+    /// `history` locked.
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn always_allow(&self, tool: &str) {
+        self.always_allowed.lock().unwrap().insert(tool.to_string());
+    }
+
+    pub fn is_always_allowed(&self, tool: &str) -> bool {
+        self.always_allowed.lock().unwrap().contains(tool)
+    }
+
+    /// What `GET /sessions/:id` reports; the system prompt is left out.
+    pub async fn summary(&self) -> SessionSummary {
+        let history = self.history.lock().await;
+        SessionSummary {
+            id: self.id.clone(),
+            age_secs: self.created.elapsed().as_secs(),
+            idle_secs: self.idle_for().as_secs(),
+            busy: self.turn_lock.try_lock().is_err(),
+            always_allowed: self
+                .always_allowed
+                .lock()
+                .unwrap()
+                .iter()
+                .cloned()
+                .collect(),
+            messages: history.iter().skip(1).cloned().collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SessionSummary {
+    pub id: String,
+    pub age_secs: u64,
+    pub idle_secs: u64,
+    /// A turn is running.
+    pub busy: bool,
+    /// Tools that run without asking for approval.
+    pub always_allowed: Vec<String>,
+    pub messages: Vec<ChatMessage>,
+}
+
+pub struct SessionStore {
+    sessions: RwLock<HashMap<String, Arc<Session>>>,
+    /// History every new session starts from.
+    initial_history: Vec<ChatMessage>,
+    /// `None` keeps sessions until they are deleted.
+    idle_timeout: Option<Duration>,
+}
+
+impl SessionStore {
+    pub fn new(initial_history: Vec<ChatMessage>, idle_timeout: Option<Duration>) -> Self {
+        let default = Session::new(DEFAULT_SESSION.to_string(), initial_history.clone());
+        Self {
+            sessions: RwLock::new(HashMap::from([(
+                DEFAULT_SESSION.to_string(),
+                Arc::new(default),
+            )])),
+            initial_history,
+            idle_timeout,
+        }
+    }
+
+    pub fn create(&self) -> Arc<Session> {
+        let session = Arc::new(Session::new(new_id(), self.initial_history.clone()));
+        self.sessions
+            .write()
+            .unwrap()
+            .insert(session.id.clone(), session.clone());
+        session
+    }
+
+    /// Bring a stored conversation back as a live session under its old id.
+    pub fn restore(&self, id: &str, messages: Vec<ChatMessage>) -> Arc<Session> {
+        let mut history = self.initial_history.clone();
+        history.extend(messages);
+        let session = Arc::new(Session::new(id.to_string(), history));
+        self.sessions
+            .write()
+            .unwrap()
+            .insert(id.to_string(), session.clone());
+        session
+    }
+
+    /// History every new session starts from.
+    pub fn initial_history(&self) -> &[ChatMessage] {
+        &self.initial_history
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Session>> {
+        self.sessions.read().unwrap().get(id).cloned()
+    }
+
+    /// Forget a session. Invalidate it first so a turn already running
+    /// finishes but is not kept.
+    pub fn remove(&self, id: &str) -> bool {
+        self.sessions.write().unwrap().remove(id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.read().unwrap().len()
+    }
+
+    /// Drop sessions idle longer than the timeout, skipping the default
+    /// session and any with a turn in progress. Returns how many went.
+    pub fn evict_idle(&self) -> usize {
+        let Some(timeout) = self.idle_timeout else {
+            return 0;
+        };
+        let mut sessions = self.sessions.write().unwrap();
+        let before = sessions.len();
+        sessions.retain(|id, session| {
+            id == DEFAULT_SESSION
+                || session.idle_for() < timeout
+                || session.turn_lock.try_lock().is_err()
+        });
+        before - sessions.len()
+    }
+}
+
+/// Drop all but roughly the last `limit` messages of `history[from..]`.
+/// The cut moves forward to the next user message, so no tool result is
+/// kept without the assistant call it answers; providers reject those.
+pub fn keep_recent(history: &mut Vec<ChatMessage>, from: usize, limit: usize) {
+    let start = history.len().saturating_sub(limit).max(from);
+    if start == from {
+        return;
+    }
+    let rest = &history[start..];
+    let offset = rest
+        .iter()
+        .position(|m| matches!(m.role, ChatRole::User))
+        // No later user message: at least skip the tool exchange.
+        .or_else(|| {
+            rest.iter()
+                .position(|m| !matches!(m.role, ChatRole::Tool) && m.tool_calls.is_empty())
+        })
+        .unwrap_or(rest.len());
+    history.drain(from..start + offset);
+}
+
+/// An unguessable 128-bit hex id from the OS random number generator.
+pub(crate) fn new_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
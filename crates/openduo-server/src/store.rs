@@ -0,0 +1,404 @@
+//! Conversation persistence, so chat sessions survive a server restart.
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use openduo_agent::provider::{ChatMessage, ChatRole};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A message as stored, with the Unix time it was recorded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredMessage {
+    pub ts: u64,
+    #[serde(flatten)]
+    pub message: ChatMessage,
+}
+
+/// One stored conversation, as listed by `GET /conversations`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationInfo {
+    pub id: String,
+    /// The first user message, shortened.
+    pub title: String,
+    pub message_count: usize,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// Where conversations are kept. Messages are only ever appended; the
+/// system prompt is not stored.
+pub trait ConversationStore: Send + Sync {
+    fn append(&self, id: &str, messages: &[ChatMessage]) -> Result<()>;
+    /// `None` if nothing is stored under `id`.
+    fn load(&self, id: &str) -> Result<Option<Vec<StoredMessage>>>;
+    /// Newest first.
+    fn list(&self) -> Result<Vec<ConversationInfo>>;
+    /// `false` if nothing was stored under `id`.
+    fn delete(&self, id: &str) -> Result<bool>;
+}
+
+/// Ids become file names, so anything that could leave the history
+/// directory is refused.
+pub fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// One `<id>.jsonl` file per conversation, a message per line. With a key,
+/// each line is the base64 of a random nonce followed by the AES-256-GCM
+/// ciphertext of that JSON, authenticated with the conversation id, and
+/// plaintext lines are refused.
+pub struct JsonlStore {
+    dir: PathBuf,
+    cipher: Option<Aes256Gcm>,
+    /// Serializes appends so concurrent turns never interleave lines.
+    write_lock: Mutex<()>,
+}
+
+impl JsonlStore {
+    /// `key` is a base64-encoded 32-byte key.
+    pub fn open(dir: PathBuf, key: Option<&str>) -> Result<Self> {
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("creating history directory {}", dir.display()))?;
+        let cipher = match key {
+            Some(key) => {
+                let bytes = STANDARD
+                    .decode(key.trim())
+                    .map_err(|_| anyhow!("OPENDUO_HISTORY_KEY must be base64"))?;
+                if bytes.len() != 32 {
+                    bail!(
+                        "OPENDUO_HISTORY_KEY must decode to 32 bytes (got {})",
+                        bytes.len()
+                    );
+                }
+                Some(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)))
+            }
+            None => None,
+        };
+        Ok(Self {
+            dir,
+            cipher,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        if !valid_id(id) {
+            bail!("Invalid conversation id: {}", id);
+        }
+        Ok(self.dir.join(format!("{}.jsonl", id)))
+    }
+
+    fn encode(&self, id: &str, record: &StoredMessage) -> Result<String> {
+        let json = serde_json::to_string(record)?;
+        let Some(cipher) = &self.cipher else {
+            return Ok(json);
+        };
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut sealed = nonce.to_vec();
+        sealed.extend(
+            cipher
+                .encrypt(
+                    &nonce,
+                    Payload {
+                        msg: json.as_bytes(),
+                        aad: id.as_bytes(),
+                    },
+                )
+                .map_err(|_| anyhow!("encrypting conversation"))?,
+        );
+        Ok(STANDARD.encode(sealed))
+    }
+
+    fn decode(&self, id: &str, line: &str) -> Result<StoredMessage> {
+        let plaintext = line.starts_with('{');
+        let cipher = match (&self.cipher, plaintext) {
+            (None, true) => return Ok(serde_json::from_str(line)?),
+            (None, false) => {
+                bail!("conversation is encrypted; set OPENDUO_HISTORY_KEY to read it")
+            }
+            // Anyone able to write the file could otherwise slip in
+            // unauthenticated messages.
+            (Some(_), true) => bail!("unencrypted record in an encrypted history"),
+            (Some(cipher), false) => cipher,
+        };
+        let sealed = STANDARD.decode(line)?;
+        if sealed.len() < 12 {
+            bail!("truncated encrypted record");
+        }
+        let (nonce, ciphertext) = sealed.split_at(12);
+        let json = cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: id.as_bytes(),
+                },
+            )
+            .map_err(|_| anyhow!("cannot decrypt conversation; wrong OPENDUO_HISTORY_KEY?"))?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+impl ConversationStore for JsonlStore {
+    fn append(&self, id: &str, messages: &[ChatMessage]) -> Result<()> {
+        let path = self.path(id)?;
+        let ts = now();
+        let mut lines = String::new();
+        for message in messages {
+            lines.push_str(&self.encode(
+                id,
+                &StoredMessage {
+                    ts,
+                    message: message.clone(),
+                },
+            )?);
+            lines.push('\n');
+        }
+        let _guard = self.write_lock.lock().unwrap();
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        // Tool output can be sensitive; keep it private to the user.
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&path)?;
+        file.write_all(lines.as_bytes())?;
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> Result<Option<Vec<StoredMessage>>> {
+        let path = self.path(id)?;
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(n, line)| {
+                self.decode(id, line)
+                    .with_context(|| format!("{} line {}", path.display(), n + 1))
+            })
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+
+    fn list(&self) -> Result<Vec<ConversationInfo>> {
+        let mut conversations = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let Some(id) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_suffix(".jsonl"))
+            else {
+                continue;
+            };
+            let messages = match self.load(id) {
+                Ok(Some(messages)) => messages,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::warn!(conversation = %id, error = %format!("{:#}", e), "Skipping unreadable conversation");
+                    continue;
+                }
+            };
+            conversations.push(ConversationInfo {
+                id: id.to_string(),
+                title: title(&messages),
+                message_count: messages.len(),
+                created_at: messages.first().map_or(0, |m| m.ts),
+                updated_at: messages.last().map_or(0, |m| m.ts),
+            });
+        }
+        conversations.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(a.id.cmp(&b.id)));
+        Ok(conversations)
+    }
+
+    fn delete(&self, id: &str) -> Result<bool> {
+        match fs::remove_file(self.path(id)?) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn title(messages: &[StoredMessage]) -> String {
+    const MAX: usize = 80;
+    let first = messages
+        .iter()
+        .find(|m| matches!(m.message.role, ChatRole::User))
+        .map(|m| m.message.content.lines().next().unwrap_or_default())
+        .unwrap_or_default();
+    match first.char_indices().nth(MAX) {
+        Some((end, _)) => format!("{}…", &first[..end]),
+        None => first.to_string(),
+    }
+}
+
+/// A conversation as Markdown, tool calls and results included.
+pub fn to_markdown(id: &str, messages: &[StoredMessage]) -> String {
+    let mut out = format!("# {}\n\n", {
+        let title = title(messages);
+        if title.is_empty() {
+            format!("Conversation {}", id)
+        } else {
+            title
+        }
+    });
+    for StoredMessage { message, .. } in messages {
+        match message.role {
+            ChatRole::System => continue,
+            ChatRole::User => out.push_str(&format!("**User:** {}\n\n", message.content)),
+            ChatRole::Assistant => {
+                if !message.content.is_empty() {
+                    out.push_str(&format!("**Assistant:** {}\n\n", message.content));
+                }
+                for call in &message.tool_calls {
+                    out.push_str(&format!(
+                        "**Tool call:** `{}`\n\n```json\n{}\n```\n\n",
+                        call.name,
+                        serde_json::to_string_pretty(&call.arguments).unwrap_or_default()
+                    ));
+                }
+            }
+            ChatRole::Tool => out.push_str(&format!(
+                "**Tool result:**\n\n```\n{}\n```\n\n",
+                message.content.trim_end()
+            )),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openduo_agent::provider::ToolCall;
+    use serde_json::json;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("openduo-history-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn turn() -> Vec<ChatMessage> {
+        let mut call = ChatMessage::new(ChatRole::Assistant, "");
+        call.tool_calls.push(ToolCall {
+            id: "call_1".to_string(),
+            name: "list_issues".to_string(),
+            arguments: json!({ "project_id": "group/app" }),
+        });
+        let mut result = ChatMessage::new(ChatRole::Tool, "[{\"iid\": 1}]");
+        result.tool_call_id = Some("call_1".to_string());
+        vec![
+            ChatMessage::new(ChatRole::User, "Which issues are open?"),
+            call,
+            result,
+            ChatMessage::new(ChatRole::Assistant, "Issue #1 is open."),
+        ]
+    }
+
+    #[test]
+    fn test_jsonl_store_round_trip() {
+        let store = JsonlStore::open(temp_dir("plain"), None).unwrap();
+        store.append("abc", &turn()[..2]).unwrap();
+        store.append("abc", &turn()[2..]).unwrap();
+        let messages = store.load("abc").unwrap().unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[1].message.tool_calls[0].name, "list_issues");
+        assert_eq!(messages[2].message.tool_call_id.as_deref(), Some("call_1"));
+
+        let list = store.list().unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].title, "Which issues are open?");
+        assert_eq!(list[0].message_count, 4);
+
+        assert!(store.delete("abc").unwrap());
+        assert!(store.load("abc").unwrap().is_none());
+        assert!(!store.delete("abc").unwrap());
+    }
+
+    #[test]
+    fn test_encrypted_store_hides_content() {
+        let dir = temp_dir("encrypted");
+        let key = STANDARD.encode([7u8; 32]);
+        let store = JsonlStore::open(dir.clone(), Some(&key)).unwrap();
+        store.append("abc", &turn()).unwrap();
+        let raw = fs::read_to_string(dir.join("abc.jsonl")).unwrap();
+        assert!(!raw.contains("issues"), "{raw}");
+        assert_eq!(store.load("abc").unwrap().unwrap().len(), 4);
+
+        let without_key = JsonlStore::open(dir.clone(), None).unwrap();
+        let err = without_key.load("abc").unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("OPENDUO_HISTORY_KEY"),
+            "{err:#}"
+        );
+        let wrong_key = JsonlStore::open(dir, Some(&STANDARD.encode([8u8; 32]))).unwrap();
+        assert!(wrong_key.load("abc").is_err());
+
+        assert!(JsonlStore::open(temp_dir("short"), Some("c2hvcnQ=")).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_store_rejects_tampering() {
+        let dir = temp_dir("tampered");
+        let key = STANDARD.encode([7u8; 32]);
+        let store = JsonlStore::open(dir.clone(), Some(&key)).unwrap();
+        store.append("abc", &turn()).unwrap();
+
+        // A record copied from another conversation does not authenticate.
+        let raw = fs::read_to_string(dir.join("abc.jsonl")).unwrap();
+        fs::write(dir.join("xyz.jsonl"), &raw).unwrap();
+        assert!(store.load("xyz").is_err());
+
+        // Nor does a plaintext line slipped into an encrypted history.
+        let forged = serde_json::to_string(&StoredMessage {
+            ts: 1,
+            message: ChatMessage::new(ChatRole::Tool, "forged"),
+        })
+        .unwrap();
+        fs::write(dir.join("abc.jsonl"), format!("{}{}\n", raw, forged)).unwrap();
+        let err = store.load("abc").unwrap_err();
+        assert!(format!("{:#}", err).contains("unencrypted"), "{err:#}");
+    }
+
+    #[test]
+    fn test_ids_cannot_escape_the_directory() {
+        let store = JsonlStore::open(temp_dir("ids"), None).unwrap();
+        for id in ["../etc/passwd", "a/b", "", "a.b"] {
+            assert!(store.append(id, &turn()).is_err(), "{id}");
+        }
+    }
+
+    #[test]
+    fn test_markdown_export() {
+        let messages: Vec<StoredMessage> = turn()
+            .into_iter()
+            .map(|message| StoredMessage { ts: 1, message })
+            .collect();
+        let md = to_markdown("abc", &messages);
+        assert!(md.starts_with("# Which issues are open?\n"), "{md}");
+        assert!(md.contains("**Tool call:** `list_issues`"), "{md}");
+        assert!(md.contains("\"project_id\": \"group/app\""), "{md}");
+        assert!(md.contains("**Assistant:** Issue #1 is open."), "{md}");
+    }
+}
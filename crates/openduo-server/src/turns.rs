@@ -0,0 +1,42 @@
+//! Chat turns in progress, by id, so a client can cancel one.
+
+use crate::sessions::new_id;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Response header carrying the id `POST /chat/:turn_id/cancel` expects.
+pub const TURN_ID_HEADER: &str = "x-openduo-turn-id";
+
+#[derive(Default)]
+pub struct Turns {
+    running: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl Turns {
+    /// Register a new turn and return its id and cancellation token.
+    pub fn start(&self) -> (String, CancellationToken) {
+        let id = new_id();
+        let token = CancellationToken::new();
+        self.running
+            .lock()
+            .unwrap()
+            .insert(id.clone(), token.clone());
+        (id, token)
+    }
+
+    /// `false` if no turn with `id` is running.
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.running.lock().unwrap().get(id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn finish(&self, id: &str) {
+        self.running.lock().unwrap().remove(id);
+    }
+}
@@ -0,0 +1,338 @@
+use openduo_core::capabilities::Capability;
+use serde_json::{json, Value};
+
+pub const PROJECT_ID: u64 = 42;
+pub const PROJECT_PATH: &str = "openduo/demo";
+pub const OTHER_PROJECT_ID: u64 = 43;
+pub const OTHER_PROJECT_PATH: &str = "openduo/infra";
+pub const GROUP_ID: u64 = 10;
+
+/// A file stored in a fixture repository.
+#[derive(Debug, Clone)]
+pub struct RepoFile {
+    pub project_id: u64,
+    pub path: String,
+    pub content: String,
+}
+
+/// Mutable fixture data served by `MockGitLab`. Every project-scoped item
+/// carries a `project_id` field so handlers can filter by project.
+#[derive(Debug, Clone)]
+pub struct Fixtures {
+    /// `GET /metadata` and, trimmed to version and revision, `GET /version`.
+    pub metadata: Value,
+    /// Answer `GET /metadata` with 404 like releases before 15.2.
+    pub metadata_missing: bool,
+    /// Tier-gated features the instance is licensed for; the others answer 403.
+    pub licensed: Vec<Capability>,
+    pub current_user: Value,
+    /// `GET /personal_access_tokens/self`; `Null` answers 404 like GitLab
+    /// releases that predate the endpoint.
+    pub token: Value,
+    pub users: Vec<Value>,
+    pub projects: Vec<Value>,
+    pub issues: Vec<Value>,
+    pub merge_requests: Vec<Value>,
+    pub pipelines: Vec<Value>,
+    pub jobs: Vec<Value>,
+    pub job_traces: Vec<(u64, String)>,
+    pub notes: Vec<Value>,
+    pub labels: Vec<Value>,
+    pub milestones: Vec<Value>,
+    pub members: Vec<Value>,
+    pub runners: Vec<Value>,
+    pub commits: Vec<Value>,
+    pub branches: Vec<Value>,
+    pub files: Vec<RepoFile>,
+    pub groups: Vec<Value>,
+    /// Group epics; issues link to one through `epic_iid`.
+    pub epics: Vec<Value>,
+    pub merge_trains: Vec<Value>,
+    pub vulnerabilities: Vec<Value>,
+    /// Files received by `POST /projects/:id/uploads`.
+    pub uploads: Vec<Value>,
+    /// Files received by the generic package registry.
+    pub packages: Vec<Value>,
+    next_id: u64,
+}
+
+impl Fixtures {
+    /// Allocate a fresh global id for a created resource.
+    pub fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+fn user(id: u64, username: &str, name: &str) -> Value {
+    json!({
+        "id": id,
+        "username": username,
+        "name": name,
+        "state": "active",
+        "web_url": format!("https://gitlab.example.com/{}", username),
+    })
+}
+
+impl Default for Fixtures {
+    fn default() -> Self {
+        let alice = user(1, "alice", "Alice Admin");
+        let bob = user(2, "bob", "Bob Builder");
+
+        let project = |id: u64, path: &str, description: &str| {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            json!({
+                "id": id,
+                "name": name,
+                "path": name,
+                "path_with_namespace": path,
+                "description": description,
+                "default_branch": "main",
+                "visibility": "private",
+                "web_url": format!("https://gitlab.example.com/{}", path),
+            })
+        };
+
+        let issue = |id: u64, iid: u64, title: &str, state: &str, labels: &[&str]| {
+            json!({
+                "id": id,
+                "iid": iid,
+                "project_id": PROJECT_ID,
+                "title": title,
+                "description": format!("Details for {}", title),
+                "state": state,
+                "labels": labels,
+                "author": user(1, "alice", "Alice Admin"),
+                "assignees": [user(2, "bob", "Bob Builder")],
+                "assignee": user(2, "bob", "Bob Builder"),
+                "created_at": "2026-01-10T09:00:00Z",
+                "updated_at": "2026-01-12T09:00:00Z",
+                "web_url": format!("https://gitlab.example.com/{}/-/issues/{}", PROJECT_PATH, iid),
+            })
+        };
+
+        let pipeline = |id: u64, status: &str, git_ref: &str, sha: &str| {
+            json!({
+                "id": id,
+                "iid": id - 1000,
+                "project_id": PROJECT_ID,
+                "status": status,
+                "ref": git_ref,
+                "sha": sha,
+                "source": "push",
+                "created_at": "2026-01-11T10:00:00Z",
+                "web_url": format!("https://gitlab.example.com/{}/-/pipelines/{}", PROJECT_PATH, id),
+            })
+        };
+
+        let job = |id: u64, pipeline_id: u64, name: &str, stage: &str, status: &str| {
+            json!({
+                "id": id,
+                "project_id": PROJECT_ID,
+                "pipeline": { "id": pipeline_id, "project_id": PROJECT_ID },
+                "name": name,
+                "stage": stage,
+                "status": status,
+                "ref": "main",
+                "duration": 42.5,
+            })
+        };
+
+        let commit = |sha: &str, title: &str| {
+            json!({
+                "id": sha,
+                "short_id": &sha[..8],
+                "project_id": PROJECT_ID,
+                "title": title,
+                "message": format!("{}\n", title),
+                "author_name": "Alice Admin",
+                "author_email": "alice@example.com",
+                "created_at": "2026-01-09T08:00:00Z",
+                "parent_ids": [],
+            })
+        };
+
+        let mut login_issue = issue(
+            501,
+            1,
+            "Login page returns 500",
+            "opened",
+            &["bug", "backend"],
+        );
+        login_issue["epic_iid"] = json!(1);
+
+        Self {
+            metadata: json!({
+                "version": "17.5.0-ee",
+                "revision": "c2a2d1a0a3e",
+                "kas": { "enabled": false },
+                "enterprise": true,
+            }),
+            metadata_missing: false,
+            licensed: Capability::ALL.to_vec(),
+            current_user: alice.clone(),
+            token: json!({
+                "id": 7,
+                "name": "openduo",
+                "revoked": false,
+                "active": true,
+                "user_id": 1,
+                "scopes": ["api", "read_user", "ai_features"],
+                "expires_at": "2099-01-01",
+            }),
+            users: vec![alice.clone(), bob.clone()],
+            projects: vec![
+                project(PROJECT_ID, PROJECT_PATH, "Demo application"),
+                project(
+                    OTHER_PROJECT_ID,
+                    OTHER_PROJECT_PATH,
+                    "Infrastructure as code",
+                ),
+            ],
+            issues: vec![
+                login_issue,
+                issue(502, 2, "Add dark mode", "opened", &["feature"]),
+                issue(503, 3, "Upgrade dependencies", "closed", &["maintenance"]),
+            ],
+            merge_requests: vec![json!({
+                "id": 701,
+                "iid": 1,
+                "project_id": PROJECT_ID,
+                "title": "Fix login 500",
+                "description": "Closes #1",
+                "state": "opened",
+                "source_branch": "fix-login",
+                "target_branch": "main",
+                "author": alice,
+                "merge_status": "can_be_merged",
+                "blocking_discussions_resolved": true,
+                "head_pipeline": pipeline(
+                    1002,
+                    "failed",
+                    "fix-login",
+                    "b2c3d4e5f60718293a4b5c6d7e8f901234567890",
+                ),
+                "web_url": format!("https://gitlab.example.com/{}/-/merge_requests/1", PROJECT_PATH),
+                "changes": [{
+                    "old_path": "src/login.rs",
+                    "new_path": "src/login.rs",
+                    "new_file": false,
+                    "deleted_file": false,
+                    "diff": "@@ -1 +1 @@\n-panic!()\n+Ok(())\n",
+                }],
+            })],
+            pipelines: vec![
+                pipeline(
+                    1001,
+                    "success",
+                    "main",
+                    "a1b2c3d4e5f60718293a4b5c6d7e8f9012345678",
+                ),
+                pipeline(
+                    1002,
+                    "failed",
+                    "fix-login",
+                    "b2c3d4e5f60718293a4b5c6d7e8f901234567890",
+                ),
+            ],
+            jobs: vec![
+                job(2001, 1001, "build", "build", "success"),
+                job(2002, 1001, "test", "test", "success"),
+                job(2003, 1002, "test", "test", "failed"),
+            ],
+            job_traces: vec![
+                (2001, "$ cargo build\nFinished release\n".to_string()),
+                (2002, "$ cargo test\ntest result: ok\n".to_string()),
+                (
+                    2003,
+                    "$ cargo test\nthread 'login' panicked\nERROR: Job failed\n".to_string(),
+                ),
+            ],
+            notes: Vec::new(),
+            labels: vec![
+                json!({ "id": 801, "project_id": PROJECT_ID, "name": "bug", "color": "#d9534f" }),
+                json!({ "id": 802, "project_id": PROJECT_ID, "name": "feature", "color": "#428bca" }),
+            ],
+            milestones: vec![json!({
+                "id": 901,
+                "iid": 1,
+                "project_id": PROJECT_ID,
+                "title": "v1.0",
+                "state": "active",
+                "due_date": "2026-03-31",
+            })],
+            members: vec![
+                json!({ "id": 1, "project_id": PROJECT_ID, "username": "alice", "name": "Alice Admin", "access_level": 50 }),
+                json!({ "id": 2, "project_id": PROJECT_ID, "username": "bob", "name": "Bob Builder", "access_level": 30 }),
+            ],
+            runners: vec![
+                json!({ "id": 11, "description": "shared-linux", "active": true, "status": "online", "is_shared": true }),
+                json!({ "id": 12, "description": "old-windows", "active": false, "status": "offline", "is_shared": false }),
+            ],
+            commits: vec![
+                commit("a1b2c3d4e5f60718293a4b5c6d7e8f9012345678", "Initial commit"),
+                commit(
+                    "b2c3d4e5f60718293a4b5c6d7e8f901234567890",
+                    "Fix login handler",
+                ),
+            ],
+            branches: vec![
+                json!({ "project_id": PROJECT_ID, "name": "main", "protected": true, "default": true }),
+                json!({ "project_id": PROJECT_ID, "name": "feature/login", "protected": false, "default": false }),
+            ],
+            groups: vec![json!({
+                "id": GROUP_ID,
+                "name": "openduo",
+                "path": "openduo",
+                "full_path": "openduo",
+                "web_url": "https://gitlab.example.com/groups/openduo",
+            })],
+            epics: vec![json!({
+                "id": 1101,
+                "iid": 1,
+                "group_id": GROUP_ID,
+                "title": "Authentication overhaul",
+                "description": "Everything needed for the new login flow",
+                "state": "opened",
+                "labels": ["backend"],
+                "author": user(1, "alice", "Alice Admin"),
+                "web_url": "https://gitlab.example.com/groups/openduo/-/epics/1",
+            })],
+            merge_trains: vec![json!({
+                "id": 1201,
+                "project_id": PROJECT_ID,
+                "merge_request": { "id": 701, "iid": 1, "title": "Fix login 500", "state": "opened" },
+                "user": user(1, "alice", "Alice Admin"),
+                "target_branch": "main",
+                "status": "idle",
+                "created_at": "2026-01-12T10:00:00Z",
+            })],
+            vulnerabilities: vec![
+                json!({ "id": 1301, "project_id": PROJECT_ID, "title": "SQL injection in login", "severity": "high", "state": "detected", "report_type": "sast" }),
+                json!({ "id": 1302, "project_id": PROJECT_ID, "title": "Outdated openssl", "severity": "medium", "state": "confirmed", "report_type": "dependency_scanning" }),
+            ],
+            uploads: Vec::new(),
+            packages: Vec::new(),
+            files: vec![
+                RepoFile {
+                    project_id: PROJECT_ID,
+                    path: "README.md".to_string(),
+                    content: "# Demo\n\nA demo application.\n".to_string(),
+                },
+                RepoFile {
+                    project_id: PROJECT_ID,
+                    path: ".gitlab-ci.yml".to_string(),
+                    content:
+                        "stages: [build, test]\nbuild:\n  stage: build\n  script: cargo build\n"
+                            .to_string(),
+                },
+                RepoFile {
+                    project_id: PROJECT_ID,
+                    path: "src/login.rs".to_string(),
+                    content: "pub fn login() -> Result<(), Error> {\n    Ok(())\n}\n".to_string(),
+                },
+            ],
+            next_id: 10_000,
+        }
+    }
+}
@@ -0,0 +1,1116 @@
+use crate::fixtures::{Fixtures, RepoFile};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use openduo_core::capabilities::Capability;
+use openduo_core::config::Config;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+pub const MOCK_PAT: &str = "glpat-mock-token";
+
+/// A request received by the mock, as it arrived on the wire.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path below `/api/v4/`, still percent-encoded; `graphql` for
+    /// `/api/graphql`.
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub body: Value,
+}
+
+struct MockState {
+    token: String,
+    fixtures: RwLock<Fixtures>,
+    requests: Mutex<Vec<RecordedRequest>>,
+    failures: Mutex<VecDeque<Response>>,
+}
+
+/// In-process stand-in for the GitLab v4 REST API, bound to an ephemeral
+/// localhost port and torn down on drop.
+pub struct MockGitLab {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+    handle: JoinHandle<()>,
+}
+
+impl MockGitLab {
+    /// Start a server with the default fixtures.
+    pub async fn start() -> Self {
+        Self::with_fixtures(Fixtures::default()).await
+    }
+
+    pub async fn with_fixtures(fixtures: Fixtures) -> Self {
+        let state = Arc::new(MockState {
+            token: MOCK_PAT.to_string(),
+            fixtures: RwLock::new(fixtures),
+            requests: Mutex::new(Vec::new()),
+            failures: Mutex::new(VecDeque::new()),
+        });
+        let app = Router::new().fallback(handle).with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock GitLab listener");
+        let addr = listener.local_addr().expect("mock GitLab local addr");
+        let handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        Self {
+            addr,
+            state,
+            handle,
+        }
+    }
+
+    /// Base URL to use as `GITLAB_URL`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// A `Config` pointing at this server with a valid token.
+    pub fn config(&self) -> Config {
+        Config {
+            gitlab_url: self.url(),
+            pat: self.state.token.clone(),
+            server_port: 0,
+            ..Config::default()
+        }
+    }
+
+    /// Every request received so far, oldest first.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    /// The most recent request, if any.
+    pub fn last_request(&self) -> Option<RecordedRequest> {
+        self.state.requests.lock().unwrap().last().cloned()
+    }
+
+    /// Answer the next request with `status` and `headers` instead of
+    /// routing it. Calls queue up, one response per request.
+    pub fn fail_next(&self, status: u16, headers: &[(&str, &str)]) {
+        let code = StatusCode::from_u16(status).expect("valid status code");
+        let message = format!("{} {}", status, code.canonical_reason().unwrap_or("Error"));
+        self.fail_next_with(status, json!({ "message": message }), headers);
+    }
+
+    /// Like `fail_next`, with a custom JSON error body.
+    pub fn fail_next_with(&self, status: u16, body: Value, headers: &[(&str, &str)]) {
+        let status = StatusCode::from_u16(status).expect("valid status code");
+        let mut resp = (status, axum::Json(body)).into_response();
+        for (name, value) in headers {
+            resp.headers_mut().insert(
+                header::HeaderName::from_bytes(name.as_bytes()).expect("valid header name"),
+                value.parse().expect("valid header value"),
+            );
+        }
+        self.state.failures.lock().unwrap().push_back(resp);
+    }
+
+    /// Inspect or modify fixture data while the server is running.
+    pub fn with_data<R>(&self, f: impl FnOnce(&mut Fixtures) -> R) -> R {
+        f(&mut self.state.fixtures.write().unwrap())
+    }
+}
+
+impl Drop for MockGitLab {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, axum::Json(json!({ "message": message }))).into_response()
+}
+
+/// GitLab's answer for a feature the license does not include.
+fn unlicensed(data: &Fixtures, capability: Capability) -> Option<Response> {
+    (!data.licensed.contains(&capability)).then(|| error(StatusCode::FORBIDDEN, "403 Forbidden"))
+}
+
+fn not_found(what: &str) -> Response {
+    error(StatusCode::NOT_FOUND, &format!("404 {} Not Found", what))
+}
+
+fn ok(value: Value) -> Response {
+    axum::Json(value).into_response()
+}
+
+fn created(value: Value) -> Response {
+    (StatusCode::CREATED, axum::Json(value)).into_response()
+}
+
+/// JSON bodies as-is; `multipart/form-data` as an object of text fields
+/// plus `file: {filename, content_type, content}`; any other non-empty
+/// body as `{content_type, content}`.
+fn parse_body(headers: &HeaderMap, body: &[u8]) -> Value {
+    if body.is_empty() {
+        return Value::Null;
+    }
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    if let Some(boundary) = content_type
+        .strip_prefix("multipart/form-data")
+        .and_then(|rest| rest.split("boundary=").nth(1))
+    {
+        return parse_multipart(&String::from_utf8_lossy(body), boundary.trim_matches('"'));
+    }
+    if content_type.starts_with("application/json") {
+        return serde_json::from_slice(body).unwrap_or(Value::Null);
+    }
+    json!({
+        "content_type": content_type,
+        "content": String::from_utf8_lossy(body),
+    })
+}
+
+fn parse_multipart(body: &str, boundary: &str) -> Value {
+    let mut form = serde_json::Map::new();
+    for part in body.split(&format!("--{}", boundary)) {
+        let Some((head, content)) = part.split_once("\r\n\r\n") else {
+            continue;
+        };
+        let content = content.strip_suffix("\r\n").unwrap_or(content);
+        let param = |key: &str| {
+            let start = head.find(&format!("; {}=\"", key))? + key.len() + 4;
+            let len = head[start..].find('"')?;
+            Some(head[start..start + len].to_string())
+        };
+        let Some(name) = param("name") else {
+            continue;
+        };
+        let value = match param("filename") {
+            Some(filename) => json!({
+                "filename": filename,
+                "content_type": head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("Content-Type: "))
+                    .unwrap_or(""),
+                "content": content,
+            }),
+            None => json!(content),
+        };
+        form.insert(name, value);
+    }
+    Value::Object(form)
+}
+
+fn parse_query(raw: Option<&str>) -> HashMap<String, String> {
+    raw.unwrap_or("")
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |s: &str| {
+                urlencoding::decode(&s.replace('+', " "))
+                    .map(|c| c.into_owned())
+                    .unwrap_or_else(|_| s.to_string())
+            };
+            (decode(k), decode(v))
+        })
+        .collect()
+}
+
+/// Query parameters that select the next page; attached to list responses
+/// so `handle` can render a `Link` header against the request URL.
+#[derive(Clone)]
+struct NextPage(Vec<(&'static str, String)>);
+
+/// Apply GitLab's pagination parameters (default 20 per page) and set the
+/// `X-*` headers. Offset pagination uses `page`; `pagination=keyset` pages
+/// by ascending `id` with `id_after`, exposing the next page only via `Link`.
+fn paginate(items: Vec<Value>, query: &HashMap<String, String>) -> Response {
+    let per_page = query
+        .get("per_page")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(20)
+        .clamp(1, 100);
+
+    if query.get("pagination").map(String::as_str) == Some("keyset") {
+        let after = query
+            .get("id_after")
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(0);
+        let mut items: Vec<Value> = items
+            .into_iter()
+            .filter(|i| i["id"].as_u64().unwrap_or(0) > after)
+            .collect();
+        items.sort_by_key(|i| i["id"].as_u64());
+        let has_more = items.len() > per_page;
+        items.truncate(per_page);
+        let last_id = items.last().and_then(|i| i["id"].as_u64());
+        let mut resp = ok(Value::Array(items));
+        if let (true, Some(id)) = (has_more, last_id) {
+            resp.extensions_mut()
+                .insert(NextPage(vec![("id_after", id.to_string())]));
+        }
+        return resp;
+    }
+
+    let page = query
+        .get("page")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1);
+    let total = items.len();
+    let total_pages = total.div_ceil(per_page).max(1);
+    let next = if page < total_pages {
+        (page + 1).to_string()
+    } else {
+        String::new()
+    };
+    let page_items: Vec<Value> = items
+        .into_iter()
+        .skip((page - 1) * per_page)
+        .take(per_page)
+        .collect();
+
+    let mut resp = ok(Value::Array(page_items));
+    let headers = resp.headers_mut();
+    for (name, value) in [
+        ("x-page", page.to_string()),
+        ("x-per-page", per_page.to_string()),
+        ("x-total", total.to_string()),
+        ("x-total-pages", total_pages.to_string()),
+        ("x-next-page", next.clone()),
+    ] {
+        headers.insert(name, value.parse().unwrap());
+    }
+    if !next.is_empty() {
+        resp.extensions_mut().insert(NextPage(vec![("page", next)]));
+    }
+    resp
+}
+
+/// Render a `Link: <...>; rel="next"` header for a paginated response.
+fn link_header(
+    headers: &HeaderMap,
+    uri: &Uri,
+    query: &HashMap<String, String>,
+    next: &NextPage,
+) -> String {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("localhost");
+    let mut params: Vec<(String, String)> = query
+        .iter()
+        .filter(|(k, _)| !next.0.iter().any(|(n, _)| n == k))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    params.extend(next.0.iter().map(|(k, v)| (k.to_string(), v.clone())));
+    params.sort();
+    let query = params
+        .iter()
+        .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("<http://{}{}?{}>; rel=\"next\"", host, uri.path(), query)
+}
+
+/// Compare a numeric JSON id against a path segment.
+fn id_matches(value: &Value, segment: &str) -> bool {
+    value.as_u64().is_some() && value.as_u64() == segment.parse().ok()
+}
+
+fn for_project(items: &[Value], project_id: u64) -> impl Iterator<Item = &Value> {
+    items
+        .iter()
+        .filter(move |v| v["project_id"].as_u64() == Some(project_id))
+}
+
+fn matches_state(item: &Value, query: &HashMap<String, String>, default: &str) -> bool {
+    let wanted = query.get("state").map(String::as_str).unwrap_or(default);
+    wanted == "all" || item["state"] == wanted
+}
+
+fn matches_search(item: &Value, query: &HashMap<String, String>) -> bool {
+    match query.get("search") {
+        Some(term) => {
+            let term = term.to_lowercase();
+            ["title", "description"].iter().any(|f| {
+                item[*f]
+                    .as_str()
+                    .is_some_and(|s| s.to_lowercase().contains(&term))
+            })
+        }
+        None => true,
+    }
+}
+
+fn set_fields(target: &mut Value, body: &Value, fields: &[&str]) {
+    for f in fields {
+        if let Some(v) = body.get(*f) {
+            target[*f] = v.clone();
+        }
+    }
+}
+
+fn apply_state_event(target: &mut Value, body: &Value) {
+    match body["state_event"].as_str() {
+        Some("close") => target["state"] = json!("closed"),
+        Some("reopen") => target["state"] = json!("opened"),
+        _ => {}
+    }
+}
+
+fn labels_from(body: &Value) -> Option<Value> {
+    body["labels"].as_str().map(|l| {
+        json!(l
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>())
+    })
+}
+
+async fn handle(
+    State(state): State<Arc<MockState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let is_graphql = uri.path() == "/api/graphql";
+    let raw_path = match uri.path().strip_prefix("/api/v4/") {
+        Some(path) => path,
+        None if is_graphql => "graphql",
+        None => return not_found("Route"),
+    };
+    let query = parse_query(uri.query());
+    let body = parse_body(&headers, &body);
+    state.requests.lock().unwrap().push(RecordedRequest {
+        method: method.to_string(),
+        path: raw_path.to_string(),
+        query: query.clone(),
+        body: body.clone(),
+    });
+
+    if let Some(resp) = state.failures.lock().unwrap().pop_front() {
+        return resp;
+    }
+
+    let token = headers
+        .get("private-token")
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+        });
+    if token != Some(state.token.as_str()) {
+        return error(StatusCode::UNAUTHORIZED, "401 Unauthorized");
+    }
+
+    if is_graphql {
+        let data = state.fixtures.read().unwrap();
+        return graphql(&data, &body);
+    }
+
+    let segments: Vec<String> = raw_path
+        .split('/')
+        .map(|s| {
+            urlencoding::decode(s)
+                .map(|c| c.into_owned())
+                .unwrap_or_else(|_| s.to_string())
+        })
+        .collect();
+    let segs: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    let mut resp = {
+        let mut data = state.fixtures.write().unwrap();
+        route(&mut data, &method, &segs, &query, &body)
+    };
+    if let Some(next) = resp.extensions().get::<NextPage>().cloned() {
+        let link = link_header(&headers, &uri, &query, &next);
+        resp.headers_mut()
+            .insert(header::LINK, link.parse().unwrap());
+    }
+    if method == Method::GET && resp.status() == StatusCode::OK {
+        return with_etag(resp, &headers).await;
+    }
+    resp
+}
+
+/// Tag a response with a weak ETag of its body, answering 304 when the
+/// request's `If-None-Match` already has it.
+async fn with_etag(resp: Response, headers: &HeaderMap) -> Response {
+    use std::hash::{Hash, Hasher};
+    let (mut parts, body) = resp.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .unwrap_or_default();
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    let etag = format!("W/\"{:x}\"", hasher.finish());
+    if headers
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|v| v.as_bytes() == etag.as_bytes())
+    {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    parts.headers.insert(header::ETAG, etag.parse().unwrap());
+    Response::from_parts(parts, axum::body::Body::from(bytes))
+}
+
+fn route(
+    data: &mut Fixtures,
+    method: &Method,
+    segs: &[&str],
+    query: &HashMap<String, String>,
+    body: &Value,
+) -> Response {
+    match (method.as_str(), segs) {
+        ("GET", ["metadata"]) if !data.metadata_missing => ok(data.metadata.clone()),
+        ("GET", ["version"]) => ok(json!({
+            "version": data.metadata["version"],
+            "revision": data.metadata["revision"],
+        })),
+        ("GET", ["user"]) => ok(data.current_user.clone()),
+        ("GET", ["personal_access_tokens", "self"]) if !data.token.is_null() => {
+            ok(data.token.clone())
+        }
+        ("GET", ["users", id]) => data
+            .users
+            .iter()
+            .find(|u| id_matches(&u["id"], id))
+            .cloned()
+            .map(ok)
+            .unwrap_or_else(|| not_found("User")),
+        ("GET", ["projects"]) => {
+            let items: Vec<Value> = data
+                .projects
+                .iter()
+                .filter(|p| match query.get("search") {
+                    Some(term) => p["name"]
+                        .as_str()
+                        .is_some_and(|n| n.contains(term.as_str())),
+                    None => true,
+                })
+                .cloned()
+                .collect();
+            paginate(items, query)
+        }
+        ("GET", ["runners"]) => {
+            let items: Vec<Value> = data
+                .runners
+                .iter()
+                .filter(|r| match query.get("scope").map(String::as_str) {
+                    Some("active") => r["active"] == true,
+                    Some("paused") => r["active"] == false,
+                    _ => true,
+                })
+                .cloned()
+                .collect();
+            paginate(items, query)
+        }
+        ("GET", ["groups"]) => paginate(data.groups.clone(), query),
+        ("GET", ["groups", gid, "epics", rest @ ..]) => {
+            let Some(group) = data.groups.iter().find(|g| id_matches(&g["id"], gid)) else {
+                return not_found("Group");
+            };
+            if let Some(resp) = unlicensed(data, Capability::Epics) {
+                return resp;
+            }
+            let group_id = group["id"].clone();
+            let mut epics = data.epics.iter().filter(|e| e["group_id"] == group_id);
+            match rest {
+                [] => paginate(
+                    epics
+                        .filter(|e| matches_state(e, query, "all") && matches_search(e, query))
+                        .cloned()
+                        .collect(),
+                    query,
+                ),
+                [iid] => epics
+                    .find(|e| id_matches(&e["iid"], iid))
+                    .cloned()
+                    .map(ok)
+                    .unwrap_or_else(|| not_found("Epic")),
+                [iid, "issues"] => {
+                    let Some(epic) = epics.find(|e| id_matches(&e["iid"], iid)) else {
+                        return not_found("Epic");
+                    };
+                    let epic_iid = epic["iid"].clone();
+                    paginate(
+                        data.issues
+                            .iter()
+                            .filter(|i| i["epic_iid"] == epic_iid)
+                            .cloned()
+                            .collect(),
+                        query,
+                    )
+                }
+                _ => not_found("Route"),
+            }
+        }
+        ("POST", ["ci", "lint"]) => {
+            let content = body["content"].as_str().unwrap_or("");
+            if content.contains("stages:") || content.contains("script:") {
+                ok(json!({ "status": "valid", "errors": [], "warnings": [] }))
+            } else {
+                ok(json!({
+                    "status": "invalid",
+                    "errors": ["jobs config should contain at least one visible job"],
+                    "warnings": [],
+                }))
+            }
+        }
+        (_, ["projects", pid, rest @ ..]) => {
+            let Some(project) = data
+                .projects
+                .iter()
+                .find(|p| id_matches(&p["id"], pid) || p["path_with_namespace"] == *pid)
+                .cloned()
+            else {
+                return not_found("Project");
+            };
+            let project_id = project["id"].as_u64().unwrap_or_default();
+            project_route(data, method, project, project_id, rest, query, body)
+        }
+        _ => not_found("Route"),
+    }
+}
+
+fn project_route(
+    data: &mut Fixtures,
+    method: &Method,
+    project: Value,
+    project_id: u64,
+    rest: &[&str],
+    query: &HashMap<String, String>,
+    body: &Value,
+) -> Response {
+    match (method.as_str(), rest) {
+        ("GET", []) => ok(project),
+
+        // ── Issues ──────────────────────────────────────────────────
+        ("GET", ["issues"]) => {
+            let items: Vec<Value> = for_project(&data.issues, project_id)
+                .filter(|i| matches_state(i, query, "all"))
+                .filter(|i| matches_search(i, query))
+                .filter(|i| match query.get("labels") {
+                    Some(labels) => labels.split(',').all(|l| {
+                        i["labels"]
+                            .as_array()
+                            .is_some_and(|a| a.iter().any(|x| x == l.trim()))
+                    }),
+                    None => true,
+                })
+                .filter(|i| match query.get("assignee_username") {
+                    Some(u) => i["assignees"]
+                        .as_array()
+                        .is_some_and(|a| a.iter().any(|x| x["username"] == u.as_str())),
+                    None => true,
+                })
+                .cloned()
+                .collect();
+            paginate(items, query)
+        }
+        ("POST", ["issues"]) => {
+            let Some(title) = body["title"].as_str() else {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    "400 (Bad request) \"title\" not given",
+                );
+            };
+            let iid = for_project(&data.issues, project_id)
+                .filter_map(|i| i["iid"].as_u64())
+                .max()
+                .unwrap_or(0)
+                + 1;
+            let issue = json!({
+                "id": data.next_id(),
+                "iid": iid,
+                "project_id": project_id,
+                "title": title,
+                "description": body["description"].clone(),
+                "state": "opened",
+                "labels": labels_from(body).unwrap_or(json!([])),
+                "author": data.current_user.clone(),
+                "assignees": [],
+            });
+            data.issues.push(issue.clone());
+            created(issue)
+        }
+        (m @ ("GET" | "PUT"), ["issues", iid]) => {
+            let Some(issue) = data
+                .issues
+                .iter_mut()
+                .find(|i| i["project_id"] == project_id && id_matches(&i["iid"], iid))
+            else {
+                return not_found("Issue");
+            };
+            if m == "PUT" {
+                set_fields(issue, body, &["title", "description"]);
+                if let Some(labels) = labels_from(body) {
+                    issue["labels"] = labels;
+                }
+                apply_state_event(issue, body);
+            }
+            ok(issue.clone())
+        }
+        ("POST", [kind @ ("issues" | "merge_requests"), iid, "notes"]) => {
+            let items = if *kind == "issues" {
+                &data.issues
+            } else {
+                &data.merge_requests
+            };
+            if !for_project(items, project_id).any(|i| id_matches(&i["iid"], iid)) {
+                return not_found(if *kind == "issues" {
+                    "Issue"
+                } else {
+                    "Merge Request"
+                });
+            }
+            let note = json!({
+                "id": data.next_id(),
+                "project_id": project_id,
+                "noteable_type": if *kind == "issues" { "Issue" } else { "MergeRequest" },
+                "noteable_iid": iid.parse::<u64>().unwrap_or_default(),
+                "body": body["body"].clone(),
+                "author": data.current_user.clone(),
+                "system": false,
+            });
+            data.notes.push(note.clone());
+            created(note)
+        }
+
+        // ── Merge requests ──────────────────────────────────────────
+        ("GET", ["merge_requests"]) => {
+            let items: Vec<Value> = for_project(&data.merge_requests, project_id)
+                .filter(|i| matches_state(i, query, "all"))
+                .filter(|i| matches_search(i, query))
+                .map(|mr| without(mr, "changes"))
+                .collect();
+            paginate(items, query)
+        }
+        ("POST", ["merge_requests"]) => {
+            for f in ["source_branch", "target_branch", "title"] {
+                if body[f].as_str().is_none() {
+                    return error(
+                        StatusCode::BAD_REQUEST,
+                        &format!("400 (Bad request) \"{}\" not given", f),
+                    );
+                }
+            }
+            let iid = for_project(&data.merge_requests, project_id)
+                .filter_map(|i| i["iid"].as_u64())
+                .max()
+                .unwrap_or(0)
+                + 1;
+            let mr = json!({
+                "id": data.next_id(),
+                "iid": iid,
+                "project_id": project_id,
+                "title": body["title"].clone(),
+                "description": body["description"].clone(),
+                "state": "opened",
+                "source_branch": body["source_branch"].clone(),
+                "target_branch": body["target_branch"].clone(),
+                "author": data.current_user.clone(),
+                "merge_status": "checking",
+                "changes": [],
+            });
+            data.merge_requests.push(mr.clone());
+            created(without(&mr, "changes"))
+        }
+        (m @ ("GET" | "PUT"), ["merge_requests", iid, tail @ ..]) => {
+            let Some(mr) = data
+                .merge_requests
+                .iter_mut()
+                .find(|i| i["project_id"] == project_id && id_matches(&i["iid"], iid))
+            else {
+                return not_found("Merge Request");
+            };
+            match (m, tail) {
+                ("GET", []) => ok(without(mr, "changes")),
+                ("GET", ["changes"]) => ok(mr.clone()),
+                ("PUT", []) => {
+                    set_fields(mr, body, &["title", "description"]);
+                    apply_state_event(mr, body);
+                    ok(without(mr, "changes"))
+                }
+                ("PUT", ["merge"]) => {
+                    if mr["state"] != "opened" {
+                        return error(StatusCode::METHOD_NOT_ALLOWED, "405 Method Not Allowed");
+                    }
+                    mr["state"] = json!("merged");
+                    ok(without(mr, "changes"))
+                }
+                _ => not_found("Route"),
+            }
+        }
+
+        // ── Pipelines and jobs ──────────────────────────────────────
+        ("GET", ["pipelines"]) => {
+            let mut items: Vec<Value> = for_project(&data.pipelines, project_id).cloned().collect();
+            items.reverse();
+            paginate(items, query)
+        }
+        ("POST", ["pipeline"]) => {
+            let Some(git_ref) = body["ref"].as_str() else {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    "400 (Bad request) \"ref\" not given",
+                );
+            };
+            let pipeline = json!({
+                "id": data.next_id(),
+                "project_id": project_id,
+                "status": "created",
+                "ref": git_ref,
+                "source": "api",
+            });
+            data.pipelines.push(pipeline.clone());
+            created(pipeline)
+        }
+        (m, ["pipelines", id, tail @ ..]) => {
+            let Some(pipeline) = data
+                .pipelines
+                .iter_mut()
+                .find(|p| p["project_id"] == project_id && id_matches(&p["id"], id))
+            else {
+                return not_found("Pipeline");
+            };
+            match (m, tail) {
+                ("GET", []) => ok(pipeline.clone()),
+                ("POST", ["retry"]) => {
+                    pipeline["status"] = json!("pending");
+                    created(pipeline.clone())
+                }
+                ("POST", ["cancel"]) => {
+                    pipeline["status"] = json!("canceled");
+                    ok(pipeline.clone())
+                }
+                ("GET", ["jobs"]) => {
+                    let pipeline_id = pipeline["id"].clone();
+                    let items: Vec<Value> = data
+                        .jobs
+                        .iter()
+                        .filter(|j| j["pipeline"]["id"] == pipeline_id)
+                        .cloned()
+                        .collect();
+                    paginate(items, query)
+                }
+                _ => not_found("Route"),
+            }
+        }
+        ("GET", ["jobs", id, "trace"]) => data
+            .job_traces
+            .iter()
+            .find(|(job_id, _)| job_id.to_string() == *id)
+            .map(|(_, trace)| {
+                ([(header::CONTENT_TYPE, "text/plain")], trace.clone()).into_response()
+            })
+            .unwrap_or_else(|| not_found("Job")),
+
+        // ── Repository ──────────────────────────────────────────────
+        ("GET", ["repository", "files", file_path]) => {
+            let git_ref = query.get("ref").cloned().unwrap_or_else(|| "main".into());
+            find_file(&data.files, project_id, file_path)
+                .map(|f| {
+                    ok(json!({
+                        "file_name": f.path.rsplit('/').next().unwrap_or(&f.path),
+                        "file_path": f.path,
+                        "size": f.content.len(),
+                        "encoding": "base64",
+                        "content": STANDARD.encode(&f.content),
+                        "ref": git_ref,
+                    }))
+                })
+                .unwrap_or_else(|| not_found("File"))
+        }
+        ("GET", ["repository", "tree"]) => {
+            let dir = query.get("path").map(String::as_str).unwrap_or("");
+            let prefix = if dir.is_empty() {
+                String::new()
+            } else {
+                format!("{}/", dir.trim_end_matches('/'))
+            };
+            let mut entries: Vec<(String, &str)> = Vec::new();
+            for f in data.files.iter().filter(|f| f.project_id == project_id) {
+                let Some(rest) = f.path.strip_prefix(&prefix) else {
+                    continue;
+                };
+                let entry = match rest.split_once('/') {
+                    Some((sub, _)) => (sub.to_string(), "tree"),
+                    None => (rest.to_string(), "blob"),
+                };
+                if !entries.contains(&entry) {
+                    entries.push(entry);
+                }
+            }
+            let items: Vec<Value> = entries
+                .into_iter()
+                .map(|(name, kind)| {
+                    json!({ "name": name, "type": kind, "path": format!("{}{}", prefix, name) })
+                })
+                .collect();
+            paginate(items, query)
+        }
+        ("GET", ["search"]) => {
+            if query.get("scope").map(String::as_str) != Some("blobs") {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    "400 (Bad request) \"scope\" does not have a valid value",
+                );
+            }
+            let term = query.get("search").cloned().unwrap_or_default();
+            let items: Vec<Value> = data
+                .files
+                .iter()
+                .filter(|f| f.project_id == project_id && f.content.contains(&term))
+                .map(|f| {
+                    json!({
+                        "basename": f.path.rsplit('/').next().unwrap_or(&f.path),
+                        "path": f.path,
+                        "filename": f.path,
+                        "ref": "main",
+                        "project_id": project_id,
+                        "data": f.content,
+                    })
+                })
+                .collect();
+            paginate(items, query)
+        }
+        ("DELETE", ["repository", "branches", name]) => {
+            let Some(pos) = data
+                .branches
+                .iter()
+                .position(|b| b["project_id"] == project_id && b["name"] == *name)
+            else {
+                return not_found("Branch");
+            };
+            if data.branches[pos]["protected"] == true {
+                return error(
+                    StatusCode::FORBIDDEN,
+                    "Protected branches cannot be deleted",
+                );
+            }
+            data.branches.remove(pos);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        ("GET", ["repository", "commits"]) => {
+            let mut items: Vec<Value> = for_project(&data.commits, project_id).cloned().collect();
+            items.reverse();
+            paginate(items, query)
+        }
+        ("GET", ["repository", "commits", sha]) => for_project(&data.commits, project_id)
+            .find(|c| c["id"] == *sha || c["short_id"] == *sha)
+            .cloned()
+            .map(ok)
+            .unwrap_or_else(|| not_found("Commit")),
+        ("GET", ["repository", "compare"]) => {
+            let (Some(from), Some(to)) = (query.get("from"), query.get("to")) else {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    "400 (Bad request) \"from\" is missing",
+                );
+            };
+            let commits: Vec<Value> = for_project(&data.commits, project_id).cloned().collect();
+            ok(json!({
+                "commit": commits.last().cloned(),
+                "commits": commits,
+                "diffs": [{
+                    "old_path": "src/login.rs",
+                    "new_path": "src/login.rs",
+                    "diff": format!("@@ {}..{} @@\n-panic!()\n+Ok(())\n", from, to),
+                }],
+                "compare_timeout": false,
+                "compare_same_ref": from == to,
+            }))
+        }
+
+        // ── Members, labels, milestones ─────────────────────────────
+        ("GET", ["members"]) => paginate(
+            for_project(&data.members, project_id).cloned().collect(),
+            query,
+        ),
+        ("GET", ["labels"]) => paginate(
+            for_project(&data.labels, project_id).cloned().collect(),
+            query,
+        ),
+        ("POST", ["labels"]) => {
+            let (Some(name), Some(color)) = (body["name"].as_str(), body["color"].as_str()) else {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    "400 (Bad request) \"name\" not given",
+                );
+            };
+            if for_project(&data.labels, project_id).any(|l| l["name"] == name) {
+                return error(StatusCode::CONFLICT, "Label already exists");
+            }
+            let label = json!({
+                "id": data.next_id(),
+                "project_id": project_id,
+                "name": name,
+                "color": color,
+            });
+            data.labels.push(label.clone());
+            created(label)
+        }
+        ("DELETE", ["labels", label]) => {
+            let Some(pos) = data.labels.iter().position(|l| {
+                l["project_id"] == project_id
+                    && (l["name"] == *label || id_matches(&l["id"], label))
+            }) else {
+                return not_found("Label");
+            };
+            data.labels.remove(pos);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        ("GET", ["milestones"]) => paginate(
+            for_project(&data.milestones, project_id).cloned().collect(),
+            query,
+        ),
+
+        // ── Licensed features ───────────────────────────────────────
+        ("GET", ["merge_trains", rest @ ..]) => {
+            if let Some(resp) = unlicensed(data, Capability::MergeTrains) {
+                return resp;
+            }
+            let items = for_project(&data.merge_trains, project_id)
+                .filter(|t| match rest {
+                    [branch] => t["target_branch"] == *branch,
+                    _ => true,
+                })
+                .cloned()
+                .collect();
+            paginate(items, query)
+        }
+        ("GET", ["vulnerabilities"]) => {
+            if let Some(resp) = unlicensed(data, Capability::Vulnerabilities) {
+                return resp;
+            }
+            let items = for_project(&data.vulnerabilities, project_id)
+                .filter(|v| {
+                    ["severity", "state"]
+                        .iter()
+                        .all(|key| query.get(*key).is_none_or(|want| v[*key] == want.as_str()))
+                })
+                .cloned()
+                .collect();
+            paginate(items, query)
+        }
+
+        // ── Uploads and packages ────────────────────────────────────
+        ("POST", ["uploads"]) => {
+            let Some(filename) = body["file"]["filename"].as_str() else {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    "400 (Bad request) \"file\" not given",
+                );
+            };
+            let secret = format!("{:032x}", data.next_id());
+            let url = format!("/uploads/{}/{}", secret, filename);
+            let upload = json!({
+                "id": data.next_id(),
+                "project_id": project_id,
+                "alt": filename,
+                "url": url,
+                "full_path": format!("/{}{}", project["path_with_namespace"].as_str().unwrap_or(""), url),
+                "markdown": format!("[{}]({})", filename, url),
+                "content": body["file"]["content"],
+            });
+            data.uploads.push(upload.clone());
+            created(without(&upload, "content"))
+        }
+        ("PUT", ["packages", "generic", name, version, filename]) => {
+            data.packages.push(json!({
+                "project_id": project_id,
+                "name": name,
+                "version": version,
+                "file_name": filename,
+                "content": body["content"],
+            }));
+            created(json!({ "message": "201 Created" }))
+        }
+
+        _ => not_found("Route"),
+    }
+}
+
+/// A small slice of GitLab's GraphQL schema: `currentUser` and a project's
+/// `workItems` connection (built from the issue fixtures), paged by cursor.
+fn graphql(data: &Fixtures, body: &Value) -> Response {
+    let query = body["query"].as_str().unwrap_or("");
+    let vars = &body["variables"];
+    if query.contains("workItems") {
+        let path = vars["fullPath"].as_str().unwrap_or("");
+        let Some(project) = data
+            .projects
+            .iter()
+            .find(|p| p["path_with_namespace"] == path)
+        else {
+            return ok(json!({ "data": { "project": null } }));
+        };
+        let project_id = project["id"].as_u64().unwrap_or_default();
+        let first = vars["first"].as_u64().unwrap_or(20) as usize;
+        let start = vars["after"]
+            .as_str()
+            .and_then(|c| c.strip_prefix("cursor:"))
+            .and_then(|n| n.parse::<usize>().ok())
+            .unwrap_or(0);
+        let items: Vec<&Value> = for_project(&data.issues, project_id).collect();
+        let end = (start + first).min(items.len());
+        let nodes: Vec<Value> = items[start.min(end)..end]
+            .iter()
+            .map(|i| {
+                json!({
+                    "iid": i["iid"].to_string(),
+                    "title": i["title"],
+                    "state": if i["state"] == "opened" { "OPEN" } else { "CLOSED" },
+                })
+            })
+            .collect();
+        return ok(json!({
+            "data": { "project": { "workItems": {
+                "nodes": nodes,
+                "pageInfo": {
+                    "hasNextPage": end < items.len(),
+                    "endCursor": format!("cursor:{}", end),
+                },
+            } } }
+        }));
+    }
+    if query.contains("currentUser") {
+        return ok(json!({ "data": { "currentUser": {
+            "username": data.current_user["username"],
+            "name": data.current_user["name"],
+            "duoChatAvailable": data.licensed.contains(&Capability::DuoChat),
+        } } }));
+    }
+    ok(json!({
+        "data": null,
+        "errors": [{
+            "message": "Field is not supported by the mock GitLab schema",
+            "locations": [{ "line": 1, "column": 1 }],
+        }],
+    }))
+}
+
+fn find_file<'a>(files: &'a [RepoFile], project_id: u64, path: &str) -> Option<&'a RepoFile> {
+    files
+        .iter()
+        .find(|f| f.project_id == project_id && f.path == path)
+}
+
+fn without(value: &Value, key: &str) -> Value {
+    let mut v = value.clone();
+    if let Some(obj) = v.as_object_mut() {
+        obj.remove(key);
+    }
+    v
+}
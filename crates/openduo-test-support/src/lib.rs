@@ -0,0 +1,7 @@
+//! Offline test fixtures for OpenDuo: an in-process mock of the GitLab v4
+//! REST API that tools can be pointed at through `Config.gitlab_url`.
+
+pub mod fixtures;
+pub mod gitlab_mock;
+
+pub use gitlab_mock::{MockGitLab, RecordedRequest, MOCK_PAT};
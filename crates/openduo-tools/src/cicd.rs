@@ -1,4 +1,4 @@
-use crate::registry::Tool;
+use crate::registry::{max_results, RiskLevel, Tool};
 use anyhow::Result;
 use async_trait::async_trait;
 use openduo_core::gitlab_client::GitLabClient;
@@ -85,6 +85,13 @@ impl Tool for ValidatePipelineYaml {
             "required": ["content"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
+    /// Only asks GitLab to lint the YAML; nothing is changed.
+    fn risk_level(&self) -> RiskLevel {
+        RiskLevel::Read
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let v: Value = self
             .client
@@ -108,12 +115,17 @@ impl Tool for ListRunners {
     fn parameters_schema(&self) -> Value {
         json!({
             "type": "object",
-            "properties": {},
+            "properties": {
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
+            },
             "required": []
         })
     }
-    async fn execute(&self, _args: Value) -> Result<String> {
-        let v: Vec<Value> = self.client.get("runners?scope=active").await?;
+    async fn execute(&self, args: Value) -> Result<String> {
+        let v: Vec<Value> = self
+            .client
+            .get_paginated("runners?scope=active", max_results(&args))
+            .await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
 }
@@ -0,0 +1,110 @@
+use crate::registry::{max_results, Tool};
+use anyhow::Result;
+use async_trait::async_trait;
+use openduo_core::capabilities::Capability;
+use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::types::Issue;
+use serde_json::{json, Value};
+
+pub struct EpicTools;
+
+impl EpicTools {
+    pub fn all(client: GitLabClient) -> Vec<Box<dyn Tool>> {
+        vec![
+            Box::new(ListEpics {
+                client: client.clone(),
+            }),
+            Box::new(GetEpic {
+                client: client.clone(),
+            }),
+        ]
+    }
+}
+
+struct ListEpics {
+    client: GitLabClient,
+}
+#[async_trait]
+impl Tool for ListEpics {
+    fn name(&self) -> &str {
+        "list_epics"
+    }
+    fn description(&self) -> &str {
+        "List epics in a GitLab group. Supports filtering by state and keyword."
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "group_id": { "type": "string", "description": "Group ID or URL-encoded path" },
+                "state": { "type": "string", "enum": ["opened", "closed", "all"], "default": "opened" },
+                "search": { "type": "string" },
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
+            },
+            "required": ["group_id"]
+        })
+    }
+    fn required_capabilities(&self) -> &[Capability] {
+        &[Capability::Epics]
+    }
+    async fn execute(&self, args: Value) -> Result<String> {
+        let gid = urlencoding::encode(
+            args["group_id"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("group_id required"))?,
+        );
+        let state = args["state"].as_str().unwrap_or("opened");
+        let mut path = format!("groups/{}/epics?state={}", gid, state);
+        if let Some(q) = args["search"].as_str() {
+            path.push_str(&format!("&search={}", urlencoding::encode(q)));
+        }
+        let v: Vec<Value> = self.client.get_paginated(&path, max_results(&args)).await?;
+        Ok(serde_json::to_string_pretty(&v)?)
+    }
+}
+
+struct GetEpic {
+    client: GitLabClient,
+}
+#[async_trait]
+impl Tool for GetEpic {
+    fn name(&self) -> &str {
+        "get_epic"
+    }
+    fn description(&self) -> &str {
+        "Get an epic by IID together with the issues assigned to it."
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "group_id": { "type": "string", "description": "Group ID or URL-encoded path" },
+                "epic_iid": { "type": "integer" }
+            },
+            "required": ["group_id", "epic_iid"]
+        })
+    }
+    fn required_capabilities(&self) -> &[Capability] {
+        &[Capability::Epics]
+    }
+    async fn execute(&self, args: Value) -> Result<String> {
+        let gid = urlencoding::encode(
+            args["group_id"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("group_id required"))?,
+        );
+        let iid = args["epic_iid"]
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("epic_iid required"))?;
+        let mut epic: Value = self
+            .client
+            .get(&format!("groups/{}/epics/{}", gid, iid))
+            .await?;
+        let issues: Vec<Issue> = self
+            .client
+            .get(&format!("groups/{}/epics/{}/issues", gid, iid))
+            .await?;
+        epic["issues"] = serde_json::to_value(issues)?;
+        Ok(serde_json::to_string_pretty(&epic)?)
+    }
+}
@@ -1,7 +1,8 @@
-use crate::registry::Tool;
+use crate::registry::{max_results, RiskLevel, Tool};
 use anyhow::Result;
 use async_trait::async_trait;
 use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::types::{Issue, Note};
 use serde_json::{json, Value};
 
 pub struct IssuesTools;
@@ -53,7 +54,7 @@ impl Tool for ListIssues {
                 "state": { "type": "string", "enum": ["opened", "closed", "all"], "default": "opened" },
                 "assignee_username": { "type": "string" },
                 "labels": { "type": "string" },
-                "per_page": { "type": "integer", "default": 20, "maximum": 100 }
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
             },
             "required": ["project_id"]
         })
@@ -64,18 +65,14 @@ impl Tool for ListIssues {
             .ok_or_else(|| anyhow::anyhow!("project_id required"))?;
         let encoded = urlencoding::encode(project_id);
         let state = args["state"].as_str().unwrap_or("opened");
-        let per_page = args["per_page"].as_u64().unwrap_or(20);
-        let mut path = format!(
-            "projects/{}/issues?state={}&per_page={}",
-            encoded, state, per_page
-        );
+        let mut path = format!("projects/{}/issues?state={}", encoded, state);
         if let Some(a) = args["assignee_username"].as_str() {
             path.push_str(&format!("&assignee_username={}", urlencoding::encode(a)));
         }
         if let Some(l) = args["labels"].as_str() {
             path.push_str(&format!("&labels={}", urlencoding::encode(l)));
         }
-        let issues: Vec<Value> = self.client.get(&path).await?;
+        let issues: Vec<Issue> = self.client.get_paginated(&path, max_results(&args)).await?;
         Ok(serde_json::to_string_pretty(&issues)?)
     }
 }
@@ -110,7 +107,7 @@ impl Tool for GetIssue {
         let iid = args["issue_iid"]
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("issue_iid required"))?;
-        let v: Value = self
+        let v: Issue = self
             .client
             .get(&format!("projects/{}/issues/{}", pid, iid))
             .await?;
@@ -141,6 +138,9 @@ impl Tool for CreateIssue {
             "required": ["project_id", "title"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
@@ -158,7 +158,7 @@ impl Tool for CreateIssue {
         if let Some(labels) = args["labels"].as_str() {
             body["labels"] = json!(labels);
         }
-        let v: Value = self
+        let v: Issue = self
             .client
             .post(&format!("projects/{}/issues", pid), body)
             .await?;
@@ -191,6 +191,9 @@ impl Tool for UpdateIssue {
             "required": ["project_id", "issue_iid"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
@@ -214,7 +217,7 @@ impl Tool for UpdateIssue {
         if let Some(v) = args["state_event"].as_str() {
             body["state_event"] = json!(v);
         }
-        let v: Value = self
+        let v: Issue = self
             .client
             .put(&format!("projects/{}/issues/{}", pid, iid), body)
             .await?;
@@ -243,6 +246,12 @@ impl Tool for CloseIssue {
             "required": ["project_id", "issue_iid"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
+    fn risk_level(&self) -> RiskLevel {
+        RiskLevel::Destructive
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
@@ -252,7 +261,7 @@ impl Tool for CloseIssue {
         let iid = args["issue_iid"]
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("issue_iid required"))?;
-        let v: Value = self
+        let v: Issue = self
             .client
             .put(
                 &format!("projects/{}/issues/{}", pid, iid),
@@ -285,6 +294,9 @@ impl Tool for AddIssueComment {
             "required": ["project_id", "issue_iid", "body"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
@@ -294,7 +306,7 @@ impl Tool for AddIssueComment {
         let iid = args["issue_iid"]
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("issue_iid required"))?;
-        let v: Value = self
+        let v: Note = self
             .client
             .post(
                 &format!("projects/{}/issues/{}/notes", pid, iid),
@@ -341,7 +353,7 @@ impl Tool for SearchIssues {
         );
         let state = args["state"].as_str().unwrap_or("all");
         let per_page = args["per_page"].as_u64().unwrap_or(20);
-        let issues: Vec<Value> = self
+        let issues: Vec<Issue> = self
             .client
             .get(&format!(
                 "projects/{}/issues?search={}&state={}&per_page={}",
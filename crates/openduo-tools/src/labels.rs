@@ -1,7 +1,8 @@
-use crate::registry::Tool;
+use crate::registry::{max_results, RiskLevel, Tool};
 use anyhow::Result;
 use async_trait::async_trait;
 use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::types::Label;
 use serde_json::{json, Value};
 
 pub struct LabelTools;
@@ -15,6 +16,9 @@ impl LabelTools {
             Box::new(CreateLabel {
                 client: client.clone(),
             }),
+            Box::new(DeleteLabel {
+                client: client.clone(),
+            }),
         ]
     }
 }
@@ -34,7 +38,8 @@ impl Tool for ListLabels {
         json!({
             "type": "object",
             "properties": {
-                "project_id": { "type": "string" }
+                "project_id": { "type": "string" },
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
             },
             "required": ["project_id"]
         })
@@ -45,7 +50,10 @@ impl Tool for ListLabels {
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
         );
-        let v: Vec<Value> = self.client.get(&format!("projects/{}/labels", pid)).await?;
+        let v: Vec<Label> = self
+            .client
+            .get_paginated(&format!("projects/{}/labels", pid), max_results(&args))
+            .await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
 }
@@ -72,6 +80,9 @@ impl Tool for CreateLabel {
             "required": ["project_id", "name", "color"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
@@ -86,10 +97,57 @@ impl Tool for CreateLabel {
         if let Some(v) = args["color"].as_str() {
             body["color"] = json!(v);
         }
-        let v: Value = self
+        let v: Label = self
             .client
             .post(&format!("projects/{}/labels", pid), body)
             .await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
 }
+
+struct DeleteLabel {
+    client: GitLabClient,
+}
+#[async_trait]
+impl Tool for DeleteLabel {
+    fn name(&self) -> &str {
+        "delete_label"
+    }
+    fn description(&self) -> &str {
+        "Delete a label from a GitLab project. Issues and merge requests lose the label."
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "project_id": { "type": "string" },
+                "label": { "type": "string", "description": "Label name or ID" }
+            },
+            "required": ["project_id", "label"]
+        })
+    }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
+    fn risk_level(&self) -> RiskLevel {
+        RiskLevel::Destructive
+    }
+    async fn execute(&self, args: Value) -> Result<String> {
+        let pid = urlencoding::encode(
+            args["project_id"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
+        );
+        let label = args["label"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("label required"))?;
+        self.client
+            .delete(&format!(
+                "projects/{}/labels/{}",
+                pid,
+                urlencoding::encode(label)
+            ))
+            .await?;
+        Ok(serde_json::to_string_pretty(&json!({ "deleted": label }))?)
+    }
+}
@@ -1,4 +1,5 @@
 pub mod cicd;
+pub mod epics;
 pub mod issues;
 pub mod labels;
 pub mod merge_requests;
@@ -7,4 +8,5 @@ pub mod pipelines;
 pub mod projects;
 pub mod registry;
 pub mod repositories;
+pub mod security;
 pub mod users;
@@ -1,7 +1,9 @@
-use crate::registry::Tool;
+use crate::registry::{max_results, RiskLevel, Tool};
 use anyhow::Result;
 use async_trait::async_trait;
+use openduo_core::capabilities::Capability;
 use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::types::{MergeRequest, Note};
 use serde_json::{json, Value};
 
 pub struct MergeRequestTools;
@@ -33,6 +35,9 @@ impl MergeRequestTools {
             Box::new(SearchMergeRequests {
                 client: client.clone(),
             }),
+            Box::new(ListMergeTrains {
+                client: client.clone(),
+            }),
         ]
     }
 }
@@ -54,7 +59,7 @@ impl Tool for ListMrs {
             "properties": {
                 "project_id": { "type": "string" },
                 "state": { "type": "string", "enum": ["opened", "closed", "merged", "all"], "default": "opened" },
-                "per_page": { "type": "integer", "default": 20 }
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
             },
             "required": ["project_id"]
         })
@@ -66,13 +71,12 @@ impl Tool for ListMrs {
                 .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
         );
         let state = args["state"].as_str().unwrap_or("opened");
-        let per_page = args["per_page"].as_u64().unwrap_or(20);
-        let mrs: Vec<Value> = self
+        let mrs: Vec<MergeRequest> = self
             .client
-            .get(&format!(
-                "projects/{}/merge_requests?state={}&per_page={}",
-                pid, state, per_page
-            ))
+            .get_paginated(
+                &format!("projects/{}/merge_requests?state={}", pid, state),
+                max_results(&args),
+            )
             .await?;
         Ok(serde_json::to_string_pretty(&mrs)?)
     }
@@ -108,7 +112,7 @@ impl Tool for GetMr {
         let iid = args["mr_iid"]
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("mr_iid required"))?;
-        let v: Value = self
+        let v: MergeRequest = self
             .client
             .get(&format!("projects/{}/merge_requests/{}", pid, iid))
             .await?;
@@ -140,6 +144,9 @@ impl Tool for CreateMr {
             "required": ["project_id", "source_branch", "target_branch", "title"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
@@ -160,7 +167,7 @@ impl Tool for CreateMr {
         if let Some(v) = args["description"].as_str() {
             body["description"] = json!(v);
         }
-        let v: Value = self
+        let v: MergeRequest = self
             .client
             .post(&format!("projects/{}/merge_requests", pid), body)
             .await?;
@@ -192,6 +199,9 @@ impl Tool for UpdateMr {
             "required": ["project_id", "mr_iid"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
@@ -212,7 +222,7 @@ impl Tool for UpdateMr {
         if let Some(v) = args["state_event"].as_str() {
             body["state_event"] = json!(v);
         }
-        let v: Value = self
+        let v: MergeRequest = self
             .client
             .put(&format!("projects/{}/merge_requests/{}", pid, iid), body)
             .await?;
@@ -241,6 +251,12 @@ impl Tool for MergeMr {
             "required": ["project_id", "mr_iid"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
+    fn risk_level(&self) -> RiskLevel {
+        RiskLevel::Destructive
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
@@ -250,7 +266,7 @@ impl Tool for MergeMr {
         let iid = args["mr_iid"]
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("mr_iid required"))?;
-        let v: Value = self
+        let v: MergeRequest = self
             .client
             .put(
                 &format!("projects/{}/merge_requests/{}/merge", pid, iid),
@@ -283,6 +299,9 @@ impl Tool for AddMrComment {
             "required": ["project_id", "mr_iid", "body"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
@@ -292,7 +311,7 @@ impl Tool for AddMrComment {
         let iid = args["mr_iid"]
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("mr_iid required"))?;
-        let v: Value = self
+        let v: Note = self
             .client
             .post(
                 &format!("projects/{}/merge_requests/{}/notes", pid, iid),
@@ -377,7 +396,7 @@ impl Tool for SearchMergeRequests {
         );
         let state = args["state"].as_str().unwrap_or("all");
         let per_page = args["per_page"].as_u64().unwrap_or(20);
-        let mrs: Vec<Value> = self
+        let mrs: Vec<MergeRequest> = self
             .client
             .get(&format!(
                 "projects/{}/merge_requests?search={}&state={}&per_page={}",
@@ -387,3 +406,50 @@ impl Tool for SearchMergeRequests {
         Ok(serde_json::to_string_pretty(&mrs)?)
     }
 }
+
+struct ListMergeTrains {
+    client: GitLabClient,
+}
+#[async_trait]
+impl Tool for ListMergeTrains {
+    fn name(&self) -> &str {
+        "list_merge_trains"
+    }
+    fn description(&self) -> &str {
+        "List merge train cars for a project, showing which merge requests are queued to merge."
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "project_id": { "type": "string", "description": "Project ID or URL-encoded path" },
+                "scope": { "type": "string", "enum": ["active", "complete"], "default": "active" },
+                "target_branch": { "type": "string" },
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
+            },
+            "required": ["project_id"]
+        })
+    }
+    fn required_capabilities(&self) -> &[Capability] {
+        &[Capability::MergeTrains]
+    }
+    async fn execute(&self, args: Value) -> Result<String> {
+        let pid = urlencoding::encode(
+            args["project_id"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
+        );
+        let scope = args["scope"].as_str().unwrap_or("active");
+        let path = match args["target_branch"].as_str() {
+            Some(branch) => format!(
+                "projects/{}/merge_trains/{}?scope={}",
+                pid,
+                urlencoding::encode(branch),
+                scope
+            ),
+            None => format!("projects/{}/merge_trains?scope={}", pid, scope),
+        };
+        let v: Vec<Value> = self.client.get_paginated(&path, max_results(&args)).await?;
+        Ok(serde_json::to_string_pretty(&v)?)
+    }
+}
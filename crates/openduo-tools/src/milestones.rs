@@ -1,7 +1,8 @@
-use crate::registry::Tool;
+use crate::registry::{max_results, Tool};
 use anyhow::Result;
 use async_trait::async_trait;
 use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::types::Milestone;
 use serde_json::{json, Value};
 
 pub struct MilestoneTools;
@@ -29,7 +30,8 @@ impl Tool for ListMilestones {
         json!({
             "type": "object",
             "properties": {
-                "project_id": { "type": "string" }
+                "project_id": { "type": "string" },
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
             },
             "required": ["project_id"]
         })
@@ -40,9 +42,9 @@ impl Tool for ListMilestones {
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
         );
-        let v: Vec<Value> = self
+        let v: Vec<Milestone> = self
             .client
-            .get(&format!("projects/{}/milestones", pid))
+            .get_paginated(&format!("projects/{}/milestones", pid), max_results(&args))
             .await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
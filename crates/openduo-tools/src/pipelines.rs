@@ -1,7 +1,8 @@
-use crate::registry::Tool;
+use crate::registry::{max_results, RiskLevel, Tool};
 use anyhow::Result;
 use async_trait::async_trait;
 use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::types::{Job, Pipeline};
 use serde_json::{json, Value};
 
 pub struct PipelineTools;
@@ -50,7 +51,7 @@ impl Tool for ListPipelines {
             "type": "object",
             "properties": {
                 "project_id": { "type": "string" },
-                "per_page": { "type": "integer", "default": 20 }
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
             },
             "required": ["project_id"]
         })
@@ -61,10 +62,9 @@ impl Tool for ListPipelines {
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
         );
-        let per_page = args["per_page"].as_u64().unwrap_or(20);
-        let v: Vec<Value> = self
+        let v: Vec<Pipeline> = self
             .client
-            .get(&format!("projects/{}/pipelines?per_page={}", pid, per_page))
+            .get_paginated(&format!("projects/{}/pipelines", pid), max_results(&args))
             .await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
@@ -100,7 +100,7 @@ impl Tool for GetPipeline {
         let pipeline_id = args["pipeline_id"]
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("pipeline_id required"))?;
-        let v: Value = self
+        let v: Pipeline = self
             .client
             .get(&format!("projects/{}/pipelines/{}", pid, pipeline_id))
             .await?;
@@ -129,13 +129,16 @@ impl Tool for TriggerPipeline {
             "required": ["project_id", "ref"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
         );
-        let v: Value = self
+        let v: Pipeline = self
             .client
             .post(
                 &format!("projects/{}/pipeline", pid),
@@ -167,6 +170,9 @@ impl Tool for RetryPipeline {
             "required": ["project_id", "pipeline_id"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
@@ -176,7 +182,7 @@ impl Tool for RetryPipeline {
         let pipeline_id = args["pipeline_id"]
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("pipeline_id required"))?;
-        let v: Value = self
+        let v: Pipeline = self
             .client
             .post(
                 &format!("projects/{}/pipelines/{}/retry", pid, pipeline_id),
@@ -208,6 +214,12 @@ impl Tool for CancelPipeline {
             "required": ["project_id", "pipeline_id"]
         })
     }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
+    fn risk_level(&self) -> RiskLevel {
+        RiskLevel::Destructive
+    }
     async fn execute(&self, args: Value) -> Result<String> {
         let pid = urlencoding::encode(
             args["project_id"]
@@ -217,7 +229,7 @@ impl Tool for CancelPipeline {
         let pipeline_id = args["pipeline_id"]
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("pipeline_id required"))?;
-        let v: Value = self
+        let v: Pipeline = self
             .client
             .post(
                 &format!("projects/{}/pipelines/{}/cancel", pid, pipeline_id),
@@ -260,7 +272,7 @@ impl Tool for GetPipelineJobs {
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("pipeline_id required"))?;
         let per_page = args["per_page"].as_u64().unwrap_or(100);
-        let v: Vec<Value> = self
+        let v: Vec<Job> = self
             .client
             .get(&format!(
                 "projects/{}/pipelines/{}/jobs?per_page={}",
@@ -1,7 +1,9 @@
-use crate::registry::Tool;
+use crate::registry::{max_results, Tool};
 use anyhow::Result;
 use async_trait::async_trait;
-use openduo_core::gitlab_client::GitLabClient;
+use base64::Engine;
+use openduo_core::gitlab_client::{GitLabClient, UploadFile};
+use openduo_core::types::Project;
 use serde_json::{json, Value};
 
 pub struct ProjectTools;
@@ -18,6 +20,12 @@ impl ProjectTools {
             Box::new(SearchProjects {
                 client: client.clone(),
             }),
+            Box::new(UploadAttachment {
+                client: client.clone(),
+            }),
+            Box::new(PublishGenericPackage {
+                client: client.clone(),
+            }),
         ]
     }
 }
@@ -48,7 +56,7 @@ impl Tool for GetProject {
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
         );
-        let v: Value = self.client.get(&format!("projects/{}", pid)).await?;
+        let v: Project = self.client.get(&format!("projects/{}", pid)).await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
 }
@@ -68,16 +76,19 @@ impl Tool for ListProjects {
         json!({
             "type": "object",
             "properties": {
-                "per_page": { "type": "integer", "default": 20 }
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
             },
             "required": []
         })
     }
     async fn execute(&self, args: Value) -> Result<String> {
-        let per_page = args["per_page"].as_u64().unwrap_or(20);
-        let v: Vec<Value> = self
+        // Keyset pagination stays fast on instances with many projects.
+        let v: Vec<Project> = self
             .client
-            .get(&format!("projects?membership=true&per_page={}", per_page))
+            .get_paginated(
+                "projects?membership=true&pagination=keyset&order_by=id&sort=asc",
+                max_results(&args),
+            )
             .await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
@@ -109,10 +120,129 @@ impl Tool for SearchProjects {
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("query required"))?,
         );
-        let v: Vec<Value> = self
+        let v: Vec<Project> = self
             .client
             .get(&format!("projects?search={}", query))
             .await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
 }
+
+/// The file described by `filename` plus `content` or `content_base64`.
+fn upload_file(args: &Value) -> Result<UploadFile> {
+    let filename = args["filename"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("filename required"))?;
+    let data = match (args["content"].as_str(), args["content_base64"].as_str()) {
+        (Some(text), _) => text.as_bytes().to_vec(),
+        (None, Some(encoded)) => base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| anyhow::anyhow!("content_base64 is not valid base64: {}", e))?,
+        (None, None) => anyhow::bail!("content or content_base64 required"),
+    };
+    let file = UploadFile::new(filename, data);
+    Ok(match args["content_type"].as_str() {
+        Some(content_type) => file.with_content_type(content_type),
+        None => file,
+    })
+}
+
+struct UploadAttachment {
+    client: GitLabClient,
+}
+#[async_trait]
+impl Tool for UploadAttachment {
+    fn name(&self) -> &str {
+        "upload_attachment"
+    }
+    fn description(&self) -> &str {
+        "Upload a file to a GitLab project. Returns Markdown that can be put in an issue or merge request comment to attach it."
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "project_id": { "type": "string" },
+                "filename": { "type": "string" },
+                "content": { "type": "string", "description": "File content as text" },
+                "content_base64": { "type": "string", "description": "File content as base64, for binary files" },
+                "content_type": { "type": "string", "description": "MIME type, e.g. image/png" }
+            },
+            "required": ["project_id", "filename"]
+        })
+    }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
+    async fn execute(&self, args: Value) -> Result<String> {
+        let pid = urlencoding::encode(
+            args["project_id"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
+        );
+        let file = upload_file(&args)?;
+        let v: Value = self
+            .client
+            .upload(&format!("projects/{}/uploads", pid), &file, &[])
+            .await?;
+        Ok(serde_json::to_string_pretty(&v)?)
+    }
+}
+
+struct PublishGenericPackage {
+    client: GitLabClient,
+}
+#[async_trait]
+impl Tool for PublishGenericPackage {
+    fn name(&self) -> &str {
+        "publish_generic_package"
+    }
+    fn description(&self) -> &str {
+        "Upload a file to a project's generic package registry under a package name and version."
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "project_id": { "type": "string" },
+                "package_name": { "type": "string" },
+                "package_version": { "type": "string", "description": "e.g. 1.2.3" },
+                "filename": { "type": "string" },
+                "content": { "type": "string", "description": "File content as text" },
+                "content_base64": { "type": "string", "description": "File content as base64, for binary files" }
+            },
+            "required": ["project_id", "package_name", "package_version", "filename"]
+        })
+    }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
+    async fn execute(&self, args: Value) -> Result<String> {
+        let pid = urlencoding::encode(
+            args["project_id"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
+        );
+        let name = args["package_name"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("package_name required"))?;
+        let version = args["package_version"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("package_version required"))?;
+        let file = upload_file(&args)?;
+        let v: Value = self
+            .client
+            .put_file(
+                &format!(
+                    "projects/{}/packages/generic/{}/{}/{}",
+                    pid,
+                    urlencoding::encode(name),
+                    urlencoding::encode(version),
+                    urlencoding::encode(&file.filename)
+                ),
+                &file,
+            )
+            .await?;
+        Ok(serde_json::to_string_pretty(&v)?)
+    }
+}
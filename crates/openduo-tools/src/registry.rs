@@ -1,4 +1,5 @@
 use crate::cicd::CicdTools;
+use crate::epics::EpicTools;
 use crate::issues::IssuesTools;
 use crate::labels::LabelTools;
 use crate::merge_requests::MergeRequestTools;
@@ -6,19 +7,61 @@ use crate::milestones::MilestoneTools;
 use crate::pipelines::PipelineTools;
 use crate::projects::ProjectTools;
 use crate::repositories::RepositoryTools;
+use crate::security::SecurityTools;
 use crate::users::UserTools;
 use anyhow::Result;
 use async_trait::async_trait;
-use openduo_core::{config::Config, gitlab_client::GitLabClient, types::ToolDefinition};
-use std::collections::HashMap;
+use openduo_core::cache::CacheStats;
+use openduo_core::capabilities::{Capabilities, Capability};
+use openduo_core::config::{Config, DEFAULT_INSTANCE};
+use openduo_core::error::GitLabError;
+use openduo_core::gitlab_client::{preview_writes, PlannedRequest};
+use openduo_core::redact::Redactor;
+use openduo_core::{gitlab_client::GitLabClient, types::ToolDefinition};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// How much harm a tool call can do, which decides whether the user is
+/// asked before it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskLevel {
+    Read,
+    Write,
+    /// Hard or impossible to undo: merging, closing, cancelling, deleting.
+    Destructive,
+}
 
 #[async_trait]
 pub trait Tool: Send + Sync {
     fn name(&self) -> &str;
     fn description(&self) -> &str;
     fn parameters_schema(&self) -> serde_json::Value;
+    /// Whether the tool calls POST/PUT endpoints, which a `read_api` token
+    /// cannot use.
+    fn needs_api_scope(&self) -> bool {
+        false
+    }
+    /// Tier-gated features the instance must have for the tool to work.
+    fn required_capabilities(&self) -> &[Capability] {
+        &[]
+    }
+    fn risk_level(&self) -> RiskLevel {
+        if self.needs_api_scope() {
+            RiskLevel::Write
+        } else {
+            RiskLevel::Read
+        }
+    }
     async fn execute(&self, args: serde_json::Value) -> Result<String>;
 
+    /// The writes `execute` would make with `args`, found by running it
+    /// with every write withheld. Stops at the first write.
+    async fn preview(&self, args: serde_json::Value) -> Vec<PlannedRequest> {
+        preview_writes(self.execute(args)).await.1
+    }
+
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             name: self.name().to_string(),
@@ -28,70 +71,338 @@ pub trait Tool: Send + Sync {
     }
 }
 
+/// Number of items a list tool returns when `max_results` is omitted.
+pub const DEFAULT_MAX_RESULTS: usize = 20;
+/// Upper bound on `max_results`, keeping tool observations a manageable size.
+pub const MAX_RESULTS_LIMIT: usize = 500;
+
+/// Read a list tool's `max_results` argument. `per_page` is still accepted
+/// from callers written against the older single-page schema.
+pub fn max_results(args: &serde_json::Value) -> usize {
+    args["max_results"]
+        .as_u64()
+        .or_else(|| args["per_page"].as_u64())
+        .map(|n| (n as usize).clamp(1, MAX_RESULTS_LIMIT))
+        .unwrap_or(DEFAULT_MAX_RESULTS)
+}
+
+/// Constructor for every tool in one group, keyed by `TOOL_GROUPS` name.
+type ToolGroup = fn(GitLabClient) -> Vec<Box<dyn Tool>>;
+
 pub struct ToolRegistry {
-    tools: HashMap<String, Box<dyn Tool>>,
+    /// Tools keyed by instance profile; every profile gets the same set,
+    /// each bound to its own `GitLabClient`.
+    instances: BTreeMap<String, HashMap<String, Box<dyn Tool>>>,
+    /// One client per instance, kept for its cache counters.
+    clients: BTreeMap<String, GitLabClient>,
+    default_instance: String,
+    default_project: Option<String>,
+    /// Instances whose token only has `read_api`.
+    read_only: BTreeSet<String>,
+    /// Probed features per instance. Instances that were not probed keep
+    /// every tool.
+    capabilities: BTreeMap<String, Capabilities>,
+    /// Applied to logged arguments and to every result before it leaves
+    /// the registry.
+    redactor: Redactor,
 }
 
 impl ToolRegistry {
     pub fn new(config: Config) -> anyhow::Result<Self> {
-        let client = GitLabClient::new(config)?;
-        let mut tools: HashMap<String, Box<dyn Tool>> = HashMap::new();
-
-        for tool in IssuesTools::all(client.clone()) {
-            tools.insert(tool.name().to_string(), tool);
-        }
-        for tool in MergeRequestTools::all(client.clone()) {
-            tools.insert(tool.name().to_string(), tool);
-        }
-        for tool in PipelineTools::all(client.clone()) {
-            tools.insert(tool.name().to_string(), tool);
-        }
-        for tool in RepositoryTools::all(client.clone()) {
-            tools.insert(tool.name().to_string(), tool);
-        }
-        for tool in ProjectTools::all(client.clone()) {
-            tools.insert(tool.name().to_string(), tool);
+        let default_project = config.default_project.clone();
+        let groups: Vec<(&str, ToolGroup)> = vec![
+            ("issues", IssuesTools::all),
+            ("merge_requests", MergeRequestTools::all),
+            ("pipelines", PipelineTools::all),
+            ("repositories", RepositoryTools::all),
+            ("projects", ProjectTools::all),
+            ("users", UserTools::all),
+            ("cicd", CicdTools::all),
+            ("milestones", MilestoneTools::all),
+            ("labels", LabelTools::all),
+            ("epics", EpicTools::all),
+            ("security", SecurityTools::all),
+        ];
+        let enabled: Vec<_> = groups
+            .into_iter()
+            .filter(|(group, _)| config.tool_group_enabled(group))
+            .collect();
+        let mut registry = Self::empty();
+        registry.instances.clear();
+        for profile in config.instances() {
+            let client = GitLabClient::for_instance(&profile, &config)?;
+            let tools = enabled
+                .iter()
+                .flat_map(|(_, group)| group(client.clone()))
+                .map(|tool| (tool.name().to_string(), tool))
+                .collect();
+            registry.instances.insert(profile.name.clone(), tools);
+            registry.clients.insert(profile.name, client);
         }
-        for tool in UserTools::all(client.clone()) {
-            tools.insert(tool.name().to_string(), tool);
-        }
-        for tool in CicdTools::all(client.clone()) {
-            tools.insert(tool.name().to_string(), tool);
-        }
-        for tool in MilestoneTools::all(client.clone()) {
-            tools.insert(tool.name().to_string(), tool);
-        }
-        for tool in LabelTools::all(client.clone()) {
-            tools.insert(tool.name().to_string(), tool);
+        registry.redactor = Redactor::new(&config.redact_patterns)?;
+        registry.default_instance = config.default_instance;
+        registry.default_project = default_project;
+
+        Ok(registry)
+    }
+
+    /// A registry with no tools, for callers that register their own.
+    pub fn empty() -> Self {
+        Self {
+            instances: BTreeMap::from([(DEFAULT_INSTANCE.to_string(), HashMap::new())]),
+            clients: BTreeMap::new(),
+            default_instance: DEFAULT_INSTANCE.to_string(),
+            default_project: None,
+            read_only: BTreeSet::new(),
+            capabilities: BTreeMap::new(),
+            redactor: Redactor::default(),
         }
+    }
+
+    /// Add a tool to the default instance, replacing any existing tool with
+    /// the same name.
+    pub fn register(&mut self, tool: Box<dyn Tool>) {
+        self.instances
+            .entry(self.default_instance.clone())
+            .or_default()
+            .insert(tool.name().to_string(), tool);
+    }
+
+    /// Names of the configured instance profiles.
+    pub fn instance_names(&self) -> Vec<&str> {
+        self.instances.keys().map(String::as_str).collect()
+    }
+
+    pub fn default_instance(&self) -> &str {
+        &self.default_instance
+    }
+
+    /// The client for `instance`, or for the default profile.
+    pub fn client(&self, instance: Option<&str>) -> Option<&GitLabClient> {
+        self.clients.get(instance.unwrap_or(&self.default_instance))
+    }
 
-        Ok(Self { tools })
+    /// Response cache counters per instance; empty when caching is off.
+    pub fn cache_stats(&self) -> BTreeMap<String, CacheStats> {
+        self.clients
+            .iter()
+            .filter_map(|(name, client)| Some((name.clone(), client.cache_stats()?)))
+            .collect()
+    }
+
+    /// Withhold tools that need the `api` scope on `instance`.
+    pub fn set_read_only(&mut self, instance: &str) {
+        self.read_only.insert(instance.to_string());
+    }
+
+    /// Withhold tools needing features `instance` lacks.
+    pub fn set_capabilities(&mut self, instance: &str, capabilities: Capabilities) {
+        self.capabilities.insert(instance.to_string(), capabilities);
+    }
+
+    /// The first capability `tool` needs that `instance` is known to lack.
+    fn missing_capability(&self, instance: &str, tool: &dyn Tool) -> Option<Capability> {
+        let caps = self.capabilities.get(instance)?;
+        tool.required_capabilities()
+            .iter()
+            .copied()
+            .find(|c| !caps.supports(*c))
     }
 
     pub fn definitions(&self) -> Vec<ToolDefinition> {
-        self.tools.values().map(|t| t.definition()).collect()
+        let Some(tools) = self.instances.get(&self.default_instance) else {
+            return Vec::new();
+        };
+        let read_only = self.read_only.contains(&self.default_instance);
+        tools
+            .values()
+            .filter(|t| !(read_only && t.needs_api_scope()))
+            .filter(|t| {
+                self.missing_capability(&self.default_instance, t.as_ref())
+                    .is_none()
+            })
+            .map(|t| {
+                let mut def = t.definition();
+                if let Some(project) = &self.default_project {
+                    apply_default_project(&mut def.parameters, project);
+                }
+                if self.instances.len() > 1 {
+                    apply_instance_choice(
+                        &mut def.parameters,
+                        &self.instance_names(),
+                        &self.default_instance,
+                    );
+                }
+                def
+            })
+            .collect()
     }
 
     pub async fn execute(&self, name: &str, args: serde_json::Value) -> Result<String> {
+        self.execute_on(None, name, args).await
+    }
+
+    /// Run a tool against the instance named by its `instance` argument,
+    /// else `instance` (e.g. a session's choice), else the default profile.
+    pub async fn execute_on(
+        &self,
+        instance: Option<&str>,
+        name: &str,
+        args: serde_json::Value,
+    ) -> Result<String> {
         use tracing::Instrument;
-        let span = tracing::info_span!("tool_execute", tool_name = %name);
-        tracing::info!(tool = %name, args = %args, "Tool invocation");
+        let (instance, tool, args) = self.resolve(instance, name, args)?;
+        let span = tracing::info_span!("tool_execute", tool_name = %name, instance = %instance);
+        tracing::info!(
+            tool = %name,
+            instance = %instance,
+            args = %self.redactor.redact(&args.to_string()),
+            "Tool invocation"
+        );
         async {
-            match self.tools.get(name) {
-                Some(tool) => {
-                    let result = tool.execute(args).await;
-                    match &result {
-                        Ok(r) => {
-                            tracing::info!(tool = %name, result_len = r.len(), "Tool success")
-                        }
-                        Err(e) => tracing::error!(tool = %name, error = %e, "Tool failed"),
-                    }
-                    result
+            let result = tool
+                .execute(args)
+                .await
+                .map(|r| self.redactor.redact(&r).into_owned())
+                .map_err(|e| self.redact_error(into_observation(e)));
+            match &result {
+                Ok(r) => {
+                    tracing::info!(tool = %name, result_len = r.len(), "Tool success")
                 }
-                None => anyhow::bail!("Unknown tool: {}", name),
+                Err(e) => tracing::error!(tool = %name, error = %e, "Tool failed"),
             }
+            result
         }
         .instrument(span)
         .await
     }
+
+    /// The GitLab writes a call would make, with secrets redacted; empty if
+    /// the call would fail first or makes none.
+    pub async fn preview(&self, name: &str, args: serde_json::Value) -> Vec<PlannedRequest> {
+        let Ok((_, tool, args)) = self.resolve(None, name, args) else {
+            return Vec::new();
+        };
+        let mut planned = tool.preview(args).await;
+        for request in &mut planned {
+            if let Some(body) = &request.body {
+                let redacted = self.redactor.redact(&body.to_string()).into_owned();
+                request.body = Some(serde_json::from_str(&redacted).unwrap_or(redacted.into()));
+            }
+        }
+        planned
+    }
+
+    /// Risk of the named tool; unknown tools count as reads, since calling
+    /// them only fails.
+    pub fn risk_level(&self, name: &str) -> RiskLevel {
+        self.instances
+            .get(&self.default_instance)
+            .and_then(|tools| tools.get(name))
+            .map_or(RiskLevel::Read, |t| t.risk_level())
+    }
+
+    /// Pick the instance and tool for a call and fill in defaulted
+    /// arguments, refusing tools the instance cannot run.
+    fn resolve<'a>(
+        &'a self,
+        instance: Option<&'a str>,
+        name: &str,
+        mut args: serde_json::Value,
+    ) -> Result<(Cow<'a, str>, &'a dyn Tool, serde_json::Value)> {
+        let requested = args
+            .as_object_mut()
+            .and_then(|obj| obj.remove("instance"))
+            .and_then(|v| v.as_str().map(str::to_string));
+        let instance: Cow<str> = match requested {
+            Some(requested) => Cow::Owned(requested),
+            None => Cow::Borrowed(instance.unwrap_or(&self.default_instance)),
+        };
+        let Some(tools) = self.instances.get(instance.as_ref()) else {
+            anyhow::bail!(
+                "Unknown GitLab instance: {} (configured: {})",
+                instance,
+                self.instance_names().join(", ")
+            );
+        };
+        let Some(tool) = tools.get(name) else {
+            anyhow::bail!("Unknown tool: {}", name);
+        };
+        if self.read_only.contains(instance.as_ref()) && tool.needs_api_scope() {
+            anyhow::bail!(
+                "Tool {} is disabled: the GitLab token for instance '{}' only has the read_api \
+                 scope. Ask the user to issue a token with the api scope.",
+                name,
+                instance
+            );
+        }
+        if let Some(missing) = self.missing_capability(&instance, tool.as_ref()) {
+            anyhow::bail!(
+                "Tool {} is unavailable: GitLab instance '{}' ({}) does not support {}.",
+                name,
+                instance,
+                self.capabilities[instance.as_ref()].version,
+                missing
+            );
+        }
+        if let (Some(project), Some(obj)) = (&self.default_project, args.as_object_mut()) {
+            let takes_project = tool.parameters_schema()["properties"]["project_id"].is_object();
+            if takes_project && !obj.contains_key("project_id") {
+                obj.insert("project_id".into(), project.clone().into());
+            }
+        }
+        Ok((instance, tool.as_ref(), args))
+    }
+
+    /// Put a scrubbed message on top of `e` if its message leaks a secret,
+    /// keeping the chain intact for `downcast_ref`.
+    fn redact_error(&self, e: anyhow::Error) -> anyhow::Error {
+        match self.redactor.redact(&e.to_string()) {
+            Cow::Owned(clean) => e.context(clean),
+            Cow::Borrowed(_) => e,
+        }
+    }
+}
+
+/// Reword a failed GitLab API call so the model can act on it. The original
+/// `GitLabError` stays reachable via `downcast_ref`.
+fn into_observation(e: anyhow::Error) -> anyhow::Error {
+    let Some(gitlab) = e.downcast_ref::<GitLabError>() else {
+        return e;
+    };
+    let mut message = format!("GitLab API error: {}", gitlab);
+    if let Some(hint) = gitlab.remediation() {
+        message.push_str("\nHint: ");
+        message.push_str(&hint);
+    }
+    e.context(message)
+}
+
+/// Add an optional `instance` argument listing the configured profiles.
+fn apply_instance_choice(schema: &mut serde_json::Value, names: &[&str], default: &str) {
+    let Some(props) = schema["properties"].as_object_mut() else {
+        return;
+    };
+    props.insert(
+        "instance".into(),
+        serde_json::json!({
+            "type": "string",
+            "enum": names,
+            "description": format!("GitLab instance to query; defaults to `{}`", default),
+        }),
+    );
+}
+
+/// Make `project_id` optional in a tool schema, documenting the fallback.
+fn apply_default_project(schema: &mut serde_json::Value, project: &str) {
+    let Some(prop) = schema["properties"]["project_id"].as_object_mut() else {
+        return;
+    };
+    prop.insert(
+        "description".into(),
+        format!("Project ID or path; defaults to `{}`", project).into(),
+    );
+    if let Some(required) = schema["required"].as_array_mut() {
+        required.retain(|r| r != "project_id");
+    }
 }
@@ -1,7 +1,8 @@
-use crate::registry::Tool;
+use crate::registry::{max_results, RiskLevel, Tool};
 use anyhow::Result;
 use async_trait::async_trait;
 use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::types::Commit;
 use serde_json::{json, Value};
 
 pub struct RepositoryTools;
@@ -27,6 +28,9 @@ impl RepositoryTools {
             Box::new(CompareRefs {
                 client: client.clone(),
             }),
+            Box::new(DeleteBranch {
+                client: client.clone(),
+            }),
         ]
     }
 }
@@ -195,7 +199,7 @@ impl Tool for GetCommit {
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("sha required"))?,
         );
-        let v: Value = self
+        let v: Commit = self
             .client
             .get(&format!("projects/{}/repository/commits/{}", pid, sha))
             .await?;
@@ -220,7 +224,7 @@ impl Tool for ListCommits {
             "properties": {
                 "project_id": { "type": "string" },
                 "ref_name": { "type": "string", "default": "main" },
-                "per_page": { "type": "integer", "default": 20 }
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
             },
             "required": ["project_id"]
         })
@@ -232,13 +236,12 @@ impl Tool for ListCommits {
                 .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
         );
         let ref_name = urlencoding::encode(args["ref_name"].as_str().unwrap_or("main"));
-        let per_page = args["per_page"].as_u64().unwrap_or(20);
-        let v: Vec<Value> = self
+        let v: Vec<Commit> = self
             .client
-            .get(&format!(
-                "projects/{}/repository/commits?ref_name={}&per_page={}",
-                pid, ref_name, per_page
-            ))
+            .get_paginated(
+                &format!("projects/{}/repository/commits?ref_name={}", pid, ref_name),
+                max_results(&args),
+            )
             .await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
@@ -292,3 +295,50 @@ impl Tool for CompareRefs {
         Ok(serde_json::to_string_pretty(&v)?)
     }
 }
+
+struct DeleteBranch {
+    client: GitLabClient,
+}
+#[async_trait]
+impl Tool for DeleteBranch {
+    fn name(&self) -> &str {
+        "delete_branch"
+    }
+    fn description(&self) -> &str {
+        "Delete a branch from a GitLab repository. Protected and default branches cannot be deleted."
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "project_id": { "type": "string" },
+                "branch": { "type": "string" }
+            },
+            "required": ["project_id", "branch"]
+        })
+    }
+    fn needs_api_scope(&self) -> bool {
+        true
+    }
+    fn risk_level(&self) -> RiskLevel {
+        RiskLevel::Destructive
+    }
+    async fn execute(&self, args: Value) -> Result<String> {
+        let pid = urlencoding::encode(
+            args["project_id"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
+        );
+        let branch = args["branch"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("branch required"))?;
+        self.client
+            .delete(&format!(
+                "projects/{}/repository/branches/{}",
+                pid,
+                urlencoding::encode(branch)
+            ))
+            .await?;
+        Ok(serde_json::to_string_pretty(&json!({ "deleted": branch }))?)
+    }
+}
@@ -0,0 +1,62 @@
+use crate::registry::{max_results, Tool};
+use anyhow::Result;
+use async_trait::async_trait;
+use openduo_core::capabilities::Capability;
+use openduo_core::gitlab_client::GitLabClient;
+use serde_json::{json, Value};
+
+pub struct SecurityTools;
+
+impl SecurityTools {
+    pub fn all(client: GitLabClient) -> Vec<Box<dyn Tool>> {
+        vec![Box::new(ListVulnerabilities {
+            client: client.clone(),
+        })]
+    }
+}
+
+struct ListVulnerabilities {
+    client: GitLabClient,
+}
+#[async_trait]
+impl Tool for ListVulnerabilities {
+    fn name(&self) -> &str {
+        "list_vulnerabilities"
+    }
+    fn description(&self) -> &str {
+        "List vulnerabilities from a project's vulnerability report, optionally filtered by severity and state."
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "project_id": { "type": "string", "description": "Project ID or URL-encoded path" },
+                "severity": { "type": "string", "enum": ["critical", "high", "medium", "low", "info", "unknown"] },
+                "state": { "type": "string", "enum": ["detected", "confirmed", "resolved", "dismissed"] },
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
+            },
+            "required": ["project_id"]
+        })
+    }
+    fn required_capabilities(&self) -> &[Capability] {
+        &[Capability::Vulnerabilities]
+    }
+    async fn execute(&self, args: Value) -> Result<String> {
+        let pid = urlencoding::encode(
+            args["project_id"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("project_id required"))?,
+        );
+        let mut path = format!("projects/{}/vulnerabilities", pid);
+        let filters: Vec<String> = ["severity", "state"]
+            .iter()
+            .filter_map(|key| Some(format!("{}={}", key, args[*key].as_str()?)))
+            .collect();
+        if !filters.is_empty() {
+            path.push('?');
+            path.push_str(&filters.join("&"));
+        }
+        let v: Vec<Value> = self.client.get_paginated(&path, max_results(&args)).await?;
+        Ok(serde_json::to_string_pretty(&v)?)
+    }
+}
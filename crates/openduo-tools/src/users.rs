@@ -1,7 +1,8 @@
-use crate::registry::Tool;
+use crate::registry::{max_results, Tool};
 use anyhow::Result;
 use async_trait::async_trait;
 use openduo_core::gitlab_client::GitLabClient;
+use openduo_core::types::User;
 use serde_json::{json, Value};
 
 pub struct UserTools;
@@ -41,7 +42,7 @@ impl Tool for GetCurrentUser {
         })
     }
     async fn execute(&self, _args: Value) -> Result<String> {
-        let v: Value = self.client.get("user").await?;
+        let v: User = self.client.get("user").await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
 }
@@ -70,7 +71,7 @@ impl Tool for GetUser {
         let user_id = args["user_id"]
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("user_id required"))?;
-        let v: Value = self.client.get(&format!("users/{}", user_id)).await?;
+        let v: User = self.client.get(&format!("users/{}", user_id)).await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
 }
@@ -90,7 +91,8 @@ impl Tool for ListProjectMembers {
         json!({
             "type": "object",
             "properties": {
-                "project_id": { "type": "string" }
+                "project_id": { "type": "string" },
+                "max_results": { "type": "integer", "default": 20, "maximum": 500, "description": "Maximum number of items to return, fetched across pages" }
             },
             "required": ["project_id"]
         })
@@ -103,7 +105,7 @@ impl Tool for ListProjectMembers {
         );
         let v: Vec<Value> = self
             .client
-            .get(&format!("projects/{}/members", pid))
+            .get_paginated(&format!("projects/{}/members", pid), max_results(&args))
             .await?;
         Ok(serde_json::to_string_pretty(&v)?)
     }
@@ -28,10 +28,15 @@ export class ServerManager {
     if (this.isRunning()) return;
     this.outputChannel = outputChannel;
 
+    // The server looks for .openduo.toml from here; without it, it would
+    // search from wherever VS Code itself was launched.
+    const workspace = vscode.workspace.workspaceFolders?.[0]?.uri.fsPath;
     this.process = cp.spawn(this.binaryPath, [], {
+      cwd: workspace,
       env: {
         ...process.env,
         ...this.env,
+        ...(workspace ? { OPENDUO_WORKSPACE: workspace } : {}),
         OPENDUO_PORT: String(this.port),
         RUST_LOG: 'info',
       },
@@ -0,0 +1,91 @@
+import React, { useState } from 'react';
+import type { ApprovalAnswer, PendingApproval } from '../hooks/useChat';
+
+interface Props {
+  approval: PendingApproval;
+  onAnswer: (approvalId: string, answer: ApprovalAnswer) => void;
+}
+
+const buttonStyle = (primary: boolean): React.CSSProperties => ({
+  padding: '0.3rem 0.8rem',
+  background: primary
+    ? 'var(--vscode-button-background)'
+    : 'var(--vscode-button-secondaryBackground)',
+  color: primary
+    ? 'var(--vscode-button-foreground)'
+    : 'var(--vscode-button-secondaryForeground)',
+  border: 'none',
+  borderRadius: '4px',
+  cursor: 'pointer',
+});
+
+export const ApprovalCard: React.FC<Props> = ({ approval, onAnswer }) => {
+  const original = JSON.stringify(approval.arguments, null, 2);
+  const [args, setArgs] = useState(original);
+  const [invalid, setInvalid] = useState(false);
+
+  const approve = (always: boolean) => {
+    let edited: unknown;
+    if (args !== original) {
+      try {
+        edited = JSON.parse(args);
+      } catch {
+        setInvalid(true);
+        return;
+      }
+    }
+    onAnswer(approval.id, { decision: 'approve', arguments: edited, always_allow: always });
+  };
+
+  return (
+    <div style={{ marginTop: '0.5rem' }}>
+      <div style={{
+        color: approval.risk === 'destructive'
+          ? 'var(--vscode-errorForeground)'
+          : 'var(--vscode-editorWarning-foreground)',
+      }}>
+        {approval.risk === 'destructive' ? 'Cannot be undone' : 'Changes GitLab'}
+      </div>
+      {approval.requests.map((r, i) => (
+        <pre key={i} style={{ margin: '0.4rem 0', whiteSpace: 'pre-wrap' }}>
+          {`${r.method} ${r.url}`}
+          {r.body !== undefined && `\n${JSON.stringify(r.body, null, 2)}`}
+        </pre>
+      ))}
+      {approval.answer ? (
+        <div style={{ opacity: 0.75 }}>
+          {approval.answer === 'approved' ? 'Approved' : 'Rejected'}
+        </div>
+      ) : (
+        <>
+          <textarea
+            value={args}
+            onChange={e => { setArgs(e.target.value); setInvalid(false); }}
+            rows={Math.min(args.split('\n').length, 10)}
+            style={{
+              width: '100%',
+              fontFamily: 'var(--vscode-editor-font-family)',
+              background: 'var(--vscode-input-background)',
+              color: 'var(--vscode-input-foreground)',
+              border: `1px solid ${invalid
+                ? 'var(--vscode-inputValidation-errorBorder)'
+                : 'var(--vscode-input-border)'}`,
+            }}
+          />
+          <div style={{ display: 'flex', gap: '0.5rem', marginTop: '0.4rem' }}>
+            <button style={buttonStyle(true)} onClick={() => approve(false)}>Approve</button>
+            <button style={buttonStyle(false)} onClick={() => approve(true)}>
+              Always allow {approval.tool}
+            </button>
+            <button
+              style={buttonStyle(false)}
+              onClick={() => onAnswer(approval.id, { decision: 'reject' })}
+            >
+              Reject
+            </button>
+          </div>
+        </>
+      )}
+    </div>
+  );
+};
@@ -9,7 +9,7 @@ declare const window: Window & { __OPENDUO_SERVER_URL__?: string };
 const SERVER_URL = window.__OPENDUO_SERVER_URL__ || 'http://127.0.0.1:8745';
 
 export const ChatApp: React.FC = () => {
-  const { messages, isLoading, sendMessage } = useChat(SERVER_URL);
+  const { messages, isLoading, sendMessage, cancel, answerApproval } = useChat(SERVER_URL);
   const [connected, setConnected] = useState(false);
 
   useEffect(() => {
@@ -29,8 +29,8 @@ export const ChatApp: React.FC = () => {
   return (
     <div style={{ height: '100vh', display: 'flex', flexDirection: 'column' }}>
       <StatusBar connected={connected} model="claude-sonnet-4-5" />
-      <ChatWindow messages={messages} />
-      <InputBar onSend={(text) => sendMessage(text)} disabled={isLoading} />
+      <ChatWindow messages={messages} onApprovalAnswer={answerApproval} />
+      <InputBar onSend={(text) => sendMessage(text)} onStop={cancel} disabled={isLoading} />
     </div>
   );
 };
@@ -1,10 +1,13 @@
 import React, { useEffect, useRef } from 'react';
 import { MessageBubble } from './MessageBubble';
-import type { ChatMessage } from '../hooks/useChat';
+import type { ApprovalAnswer, ChatMessage } from '../hooks/useChat';
 
-interface Props { messages: ChatMessage[]; }
+interface Props {
+  messages: ChatMessage[];
+  onApprovalAnswer: (messageId: string, approvalId: string, answer: ApprovalAnswer) => void;
+}
 
-export const ChatWindow: React.FC<Props> = ({ messages }) => {
+export const ChatWindow: React.FC<Props> = ({ messages, onApprovalAnswer }) => {
   const bottomRef = useRef<HTMLDivElement>(null);
 
   useEffect(() => {
@@ -24,7 +27,13 @@ export const ChatWindow: React.FC<Props> = ({ messages }) => {
           Ask me anything about your GitLab projects.
         </div>
       )}
-      {messages.map(msg => <MessageBubble key={msg.id} message={msg} />)}
+      {messages.map(msg => (
+        <MessageBubble
+          key={msg.id}
+          message={msg}
+          onApprovalAnswer={(approvalId, answer) => onApprovalAnswer(msg.id, approvalId, answer)}
+        />
+      ))}
       <div ref={bottomRef} />
     </div>
   );
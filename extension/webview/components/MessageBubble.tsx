@@ -1,10 +1,15 @@
 import React from 'react';
-import type { ChatMessage } from '../hooks/useChat';
+import { ApprovalCard } from './ApprovalCard';
+import type { ApprovalAnswer, ChatMessage } from '../hooks/useChat';
 
-interface Props { message: ChatMessage; }
+interface Props {
+  message: ChatMessage;
+  onApprovalAnswer?: (approvalId: string, answer: ApprovalAnswer) => void;
+}
 
-export const MessageBubble: React.FC<Props> = ({ message }) => {
+export const MessageBubble: React.FC<Props> = ({ message, onApprovalAnswer }) => {
   const isUser = message.role === 'user';
+  const isTool = message.role === 'tool';
   return (
     <div style={{
       display: 'flex',
@@ -24,10 +29,14 @@ export const MessageBubble: React.FC<Props> = ({ message }) => {
           : 'var(--vscode-editor-foreground)',
         whiteSpace: 'pre-wrap',
         wordBreak: 'break-word',
-        fontSize: '0.9rem',
+        fontSize: isTool ? '0.8rem' : '0.9rem',
+        opacity: isTool && !message.approval ? 0.75 : 1,
         lineHeight: '1.5',
       }}>
         {message.content}
+        {message.approval && onApprovalAnswer && (
+          <ApprovalCard approval={message.approval} onAnswer={onApprovalAnswer} />
+        )}
         {message.isStreaming && <span style={{ opacity: 0.5 }}>▋</span>}
       </div>
     </div>
@@ -1,5 +1,5 @@
 import { describe, it, expect } from 'vitest';
-import { appendToken, createMessage } from './useChat';
+import { appendToken, createMessage, parseSseFrames, toolCallStatus } from './useChat';
 
 describe('useChat utilities', () => {
   it('creates a user message', () => {
@@ -14,4 +14,22 @@ describe('useChat utilities', () => {
     const updated = appendToken(msg, 'Hello');
     expect(updated.content).toBe('Hello');
   });
+
+  it('parses complete SSE frames and keeps the rest', () => {
+    const { events, rest } = parseSseFrames(
+      'event: token\ndata: {"text":"Hi"}\n\nevent: done\ndata: {}\n\nevent: tok',
+    );
+    expect(events).toEqual([
+      { event: 'token', data: '{"text":"Hi"}' },
+      { event: 'done', data: '{}' },
+    ]);
+    expect(rest).toBe('event: tok');
+  });
+
+  it('describes finished tool calls', () => {
+    expect(toolCallStatus({ name: 'list_issues', duration_ms: 42, error: null }))
+      .toBe('list_issues (42 ms)');
+    expect(toolCallStatus({ name: 'get_project', duration_ms: 5, error: '404 Not Found' }))
+      .toBe('get_project failed: 404 Not Found');
+  });
 });
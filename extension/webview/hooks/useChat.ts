@@ -1,12 +1,38 @@
-import { useState, useCallback } from 'react';
+import { useState, useCallback, useRef } from 'react';
 
 export type MessageRole = 'user' | 'assistant' | 'tool';
 
+export interface PlannedRequest {
+  method: string;
+  url: string;
+  body?: unknown;
+}
+
+/** A tool call the server will not run until the user answers. */
+export interface PendingApproval {
+  id: string;
+  tool: string;
+  risk: 'write' | 'destructive';
+  arguments: unknown;
+  requests: PlannedRequest[];
+  /** Set once the user has answered. */
+  answer?: 'approved' | 'rejected';
+}
+
+export interface ApprovalAnswer {
+  decision: 'approve' | 'reject';
+  /** Run with these arguments instead. */
+  arguments?: unknown;
+  always_allow?: boolean;
+  reason?: string;
+}
+
 export interface ChatMessage {
   id: string;
   role: MessageRole;
   content: string;
   isStreaming?: boolean;
+  approval?: PendingApproval;
 }
 
 export function createMessage(role: MessageRole, content: string): ChatMessage {
@@ -17,9 +43,80 @@ export function appendToken(msg: ChatMessage, token: string): ChatMessage {
   return { ...msg, content: msg.content + token };
 }
 
+export interface SseEvent {
+  event: string;
+  data: string;
+}
+
+/** Split complete SSE frames off `buffer`; `rest` is an unfinished frame. */
+export function parseSseFrames(buffer: string): { events: SseEvent[]; rest: string } {
+  const frames = buffer.split('\n\n');
+  const rest = frames.pop() ?? '';
+  const events: SseEvent[] = [];
+  for (const frame of frames) {
+    let event = 'message';
+    const data: string[] = [];
+    for (const line of frame.split('\n')) {
+      if (line.startsWith('event: ')) event = line.slice(7);
+      else if (line.startsWith('data: ')) data.push(line.slice(6));
+    }
+    if (data.length > 0) events.push({ event, data: data.join('\n') });
+  }
+  return { events, rest };
+}
+
+export interface ToolCallFinished {
+  name: string;
+  duration_ms: number;
+  error: string | null;
+}
+
+export function toolCallStatus(call: ToolCallFinished): string {
+  return call.error
+    ? `${call.name} failed: ${call.error}`
+    : `${call.name} (${call.duration_ms} ms)`;
+}
+
+async function createSession(serverUrl: string): Promise<string> {
+  const resp = await fetch(`${serverUrl}/sessions`, { method: 'POST' });
+  if (!resp.ok) {
+    throw new Error(`could not create session (${resp.status})`);
+  }
+  const body = await resp.json();
+  return body.id;
+}
+
 export function useChat(serverUrl: string) {
   const [messages, setMessages] = useState<ChatMessage[]>([]);
   const [isLoading, setIsLoading] = useState(false);
+  // Each chat panel keeps its own server-side conversation.
+  const sessionId = useRef<string | null>(null);
+  // Id of the turn being streamed, for cancelling it.
+  const turnId = useRef<string | null>(null);
+
+  const postMessage = useCallback(async (text: string): Promise<Response> => {
+    const post = (id: string) => fetch(`${serverUrl}/sessions/${id}/chat`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ message: text }),
+    });
+    if (!sessionId.current) {
+      sessionId.current = await createSession(serverUrl);
+    }
+    const resp = await post(sessionId.current);
+    if (resp.status !== 404) {
+      return resp;
+    }
+    // The server restarted or dropped the idle session: pick the stored
+    // conversation back up, or start a new one if it is gone.
+    const resumed = await fetch(`${serverUrl}/conversations/${sessionId.current}/resume`, {
+      method: 'POST',
+    });
+    if (!resumed.ok) {
+      sessionId.current = await createSession(serverUrl);
+    }
+    return post(sessionId.current);
+  }, [serverUrl]);
 
   const sendMessage = useCallback(async (text: string) => {
     const userMsg = createMessage('user', text);
@@ -29,11 +126,7 @@ export function useChat(serverUrl: string) {
     setIsLoading(true);
 
     try {
-      const resp = await fetch(`${serverUrl}/chat`, {
-        method: 'POST',
-        headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ message: text }),
-      });
+      const resp = await postMessage(text);
 
       if (!resp.ok) {
         const errorText = await resp.text().catch(() => 'Unknown error');
@@ -54,29 +147,70 @@ export function useChat(serverUrl: string) {
         return;
       }
 
+      turnId.current = resp.headers.get('x-openduo-turn-id');
       const reader = resp.body.getReader();
       const decoder = new TextDecoder();
+      // Tool call ids to the chat messages showing them.
+      const toolMessages = new Map<string, string>();
+      let buffer = '';
       let done = false;
 
-      while (!done) {
-        const result = await reader.read();
-        if (result.done) break;
-        const chunk = decoder.decode(result.value);
-        const lines = chunk.split('\n');
-        for (const line of lines) {
-          if (line.startsWith('data: ')) {
-            const data = line.slice(6);
-            if (data === '[DONE]') {
-              done = true;
-              break;
-            }
+      // Tool activity goes above the answer it leads to.
+      const insertBeforeAnswer = (msg: ChatMessage) => setMessages(prev => {
+        const at = prev.findIndex(m => m.id === assistantMsg.id);
+        return [...prev.slice(0, at), msg, ...prev.slice(at)];
+      });
+
+      const handle = ({ event, data }: SseEvent) => {
+        const payload = JSON.parse(data);
+        switch (event) {
+          case 'token':
             setMessages(prev => prev.map(m =>
-              m.id === assistantMsg.id
-                ? appendToken(m, data)
-                : m
+              m.id === assistantMsg.id ? appendToken(m, payload.text) : m
             ));
+            break;
+          case 'tool_call_started': {
+            const toolMsg = createMessage('tool', `Running ${payload.name}…`);
+            toolMessages.set(payload.id, toolMsg.id);
+            insertBeforeAnswer(toolMsg);
+            break;
           }
+          case 'approval_required':
+            insertBeforeAnswer({
+              ...createMessage('tool', `${payload.tool} needs your approval`),
+              approval: {
+                id: payload.approval_id,
+                tool: payload.tool,
+                risk: payload.risk,
+                arguments: payload.arguments,
+                requests: payload.requests,
+              },
+            });
+            break;
+          case 'tool_call_finished':
+            setMessages(prev => prev.map(m =>
+              m.id === toolMessages.get(payload.id)
+                ? { ...m, content: toolCallStatus(payload) }
+                : m
+            ));
+            break;
+          case 'error':
+            setMessages(prev => prev.map(m =>
+              m.id === assistantMsg.id ? appendToken(m, `Error: ${payload.message}`) : m
+            ));
+            break;
+          case 'done':
+            done = true;
+            break;
         }
+      };
+
+      while (!done) {
+        const result = await reader.read();
+        if (result.done) break;
+        const parsed = parseSseFrames(buffer + decoder.decode(result.value, { stream: true }));
+        buffer = parsed.rest;
+        parsed.events.forEach(handle);
       }
     } catch (err) {
       const message = err instanceof Error ? err.message : 'Unknown error';
@@ -89,9 +223,31 @@ export function useChat(serverUrl: string) {
       setMessages(prev => prev.map(m =>
         m.id === assistantMsg.id ? { ...m, isStreaming: false } : m
       ));
+      turnId.current = null;
       setIsLoading(false);
     }
+  }, [postMessage]);
+
+  // The server stops the turn, keeps what it finished and ends the stream.
+  const cancel = useCallback(async () => {
+    if (!turnId.current) return;
+    await fetch(`${serverUrl}/chat/${turnId.current}/cancel`, { method: 'POST' }).catch(() => {});
+  }, [serverUrl]);
+
+  const answerApproval = useCallback(async (messageId: string, approvalId: string, answer: ApprovalAnswer) => {
+    const resp = await fetch(`${serverUrl}/approvals/${approvalId}`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify(answer),
+    }).catch(() => null);
+    // A 404 means the turn ended meanwhile; the card is stale either way.
+    const result = answer.decision === 'approve' && resp?.ok ? 'approved' : 'rejected';
+    setMessages(prev => prev.map(m =>
+      m.id === messageId && m.approval
+        ? { ...m, approval: { ...m.approval, answer: result } }
+        : m
+    ));
   }, [serverUrl]);
 
-  return { messages, isLoading, sendMessage };
+  return { messages, isLoading, sendMessage, cancel, answerApproval };
 }